@@ -0,0 +1,80 @@
+//! Base58Check account addresses.
+//!
+//! An address is `base58(version || key_hash || checksum)` where `key_hash` is the first 20
+//! bytes of `SHA3-256(SHA-256(compressed SEC1 public key))` and `checksum` is the first four
+//! bytes of the double SHA-256 of `version || key_hash`. The checksum catches typos before
+//! they send funds to an address nobody holds the key for.
+
+use p256::ecdsa::VerifyingKey;
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use std::fmt;
+
+/// Version byte of mainnet P-256 account addresses.
+pub const ADDRESS_VERSION: u8 = 0x3c;
+pub const KEY_HASH_LEN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    InvalidBase58,
+    InvalidChecksum,
+    InvalidLength(usize),
+    UnknownVersion(u8),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::InvalidBase58 => write!(f, "address contains characters that are not base58"),
+            AddressError::InvalidChecksum => write!(f, "address checksum does not match, check it for typos"),
+            AddressError::InvalidLength(len) => write!(f, "address decodes to {} bytes, expected {}", len, KEY_HASH_LEN + 1),
+            AddressError::UnknownVersion(version) => write!(f, "unknown address version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+pub fn key_hash(public_key: &VerifyingKey) -> [u8; KEY_HASH_LEN] {
+    let sha2 = Sha256::digest(public_key.to_encoded_point(true).as_bytes());
+    let sha3 = Sha3_256::digest(sha2);
+    let mut hash = [0u8; KEY_HASH_LEN];
+    hash.copy_from_slice(&sha3[..KEY_HASH_LEN]);
+    hash
+}
+
+/// Derives the address that funds controlled by `public_key` are held under. Transactions
+/// are only accepted from an address if they reveal a key that maps to it.
+pub fn address_from_public_key(public_key: &VerifyingKey) -> String {
+    bs58::encode(key_hash(public_key)).with_check_version(ADDRESS_VERSION).into_string()
+}
+
+/// Decodes an address back to its key hash, checking the checksum and version.
+pub fn parse_address(address: &str) -> Result<[u8; KEY_HASH_LEN], AddressError> {
+    let decoded = bs58::decode(address).with_check(None).into_vec().map_err(|e| match e {
+        bs58::decode::Error::InvalidChecksum { .. } => AddressError::InvalidChecksum,
+        bs58::decode::Error::NoChecksum => AddressError::InvalidLength(0),
+        _ => AddressError::InvalidBase58,
+    })?;
+
+    // `decoded` still contains the version byte but not the checksum
+    if decoded.len() != KEY_HASH_LEN + 1 {
+        return Err(AddressError::InvalidLength(decoded.len()));
+    }
+    if decoded[0] != ADDRESS_VERSION {
+        return Err(AddressError::UnknownVersion(decoded[0]));
+    }
+
+    let mut hash = [0u8; KEY_HASH_LEN];
+    hash.copy_from_slice(&decoded[1..]);
+    Ok(hash)
+}
+
+pub fn validate_address(address: &str) -> Result<(), AddressError> {
+    parse_address(address).map(|_| ())
+}
+
+/// Checks that `address` is the address of `public_key`.
+pub fn address_matches_key(address: &str, public_key: &VerifyingKey) -> bool {
+    parse_address(address).map(|hash| hash == key_hash(public_key)).unwrap_or(false)
+}
@@ -0,0 +1,269 @@
+//! Canonical binary encoding used for hashing and storing chain data.
+//!
+//! Every encoded value starts with a single version byte, the [`Encode::VERSION`] of its type.
+//! After that, fields are written in declaration order with no padding or separators:
+//!
+//! - integers are fixed-width big-endian (`u8`, `u32`, `u64`, `u128`; `i64` as two's complement)
+//! - byte strings and UTF-8 strings are a `u32` length followed by the bytes
+//! - sequences are a `u32` element count followed by the elements
+//! - `Option`s are a `0`/`1` tag byte followed by the value when present
+//! - decimals are normalized (trailing zeros stripped) and written as the signed big-endian
+//!   bytes of their digits followed by their `i64` scale, so `10`, `10.0` and `1e1` encode
+//!   identically
+//!
+//! Each type is versioned on its own, so changing the layout of one type changes neither the
+//! hashes nor the stored form of the others. A type's version must be bumped when its own
+//! fields change or when a type it contains changes. Only the current version decodes, since
+//! the version byte is part of every hash. Changing the rules above changes every hash.
+
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    UnsupportedVersion(u8),
+    InvalidUtf8,
+    InvalidTag(u8),
+    TrailingBytes(usize),
+    Invalid(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "unsupported encoding version {}", version),
+            DecodeError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            DecodeError::InvalidTag(tag) => write!(f, "invalid tag byte {}", tag),
+            DecodeError::TrailingBytes(count) => write!(f, "{} trailing bytes after value", count),
+            DecodeError::Invalid(message) => write!(f, "invalid value: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub trait Encode {
+    /// Version of this type's layout, written before it when it is encoded on its own. Types
+    /// only ever encoded inside others keep the default.
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder);
+}
+
+pub trait Decode: Encode + Sized {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError>;
+}
+
+/// Encodes `value` with the version prefix.
+pub fn encode<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder::with_version(T::VERSION);
+    value.encode_to(&mut encoder);
+    encoder.finish()
+}
+
+/// Decodes a value written by [`encode`], rejecting other versions and trailing bytes.
+pub fn decode<T: Decode>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let (&version, body) = bytes.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+    if version != T::VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let mut decoder = Decoder::new(body);
+    let value = T::decode_from(&mut decoder)?;
+    decoder.finish()?;
+    Ok(value)
+}
+
+#[derive(Debug)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder {
+    /// An encoder for fields only, without a version byte.
+    pub fn new() -> Self {
+        Encoder { buf: Vec::new() }
+    }
+
+    /// An encoder that starts with `version`, for hashing a value the way [`encode`] writes it.
+    pub fn with_version(version: u8) -> Self {
+        Encoder { buf: vec![version] }
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u128(&mut self, value: u128) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_bool(&mut self, value: bool) {
+        self.put_u8(value as u8);
+    }
+
+    fn put_len(&mut self, len: usize) {
+        let len = u32::try_from(len).expect("encoded length exceeds u32::MAX");
+        self.put_u32(len);
+    }
+
+    pub fn put_bytes(&mut self, value: &[u8]) {
+        self.put_len(value.len());
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+
+    pub fn put_decimal(&mut self, value: &BigDecimal) {
+        let (digits, scale) = value.normalized().into_bigint_and_exponent();
+        self.put_bytes(&digits.to_signed_bytes_be());
+        self.put_i64(scale);
+    }
+
+    pub fn put<T: Encode + ?Sized>(&mut self, value: &T) {
+        value.encode_to(self);
+    }
+
+    pub fn put_option<T: Encode>(&mut self, value: Option<&T>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                value.encode_to(self);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    pub fn put_seq<T: Encode>(&mut self, values: &[T]) {
+        self.put_len(values.len());
+        for value in values {
+            value.encode_to(self);
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Starts decoding the fields in `buf`, which has no version byte.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Decoder { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.buf.len() < len {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64, DecodeError> {
+        Ok(i64::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn get_u128(&mut self) -> Result<u128, DecodeError> {
+        Ok(u128::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool, DecodeError> {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.get_u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn get_string(&mut self) -> Result<String, DecodeError> {
+        let bytes = self.get_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    pub fn get_decimal(&mut self) -> Result<BigDecimal, DecodeError> {
+        let digits = BigInt::from_signed_bytes_be(self.get_bytes()?);
+        let scale = self.get_i64()?;
+        Ok(BigDecimal::new(digits, scale))
+    }
+
+    pub fn get<T: Decode>(&mut self) -> Result<T, DecodeError> {
+        T::decode_from(self)
+    }
+
+    pub fn get_option<T: Decode>(&mut self) -> Result<Option<T>, DecodeError> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode_from(self)?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+
+    pub fn get_seq<T: Decode>(&mut self) -> Result<Vec<T>, DecodeError> {
+        let len = self.get_u32()? as usize;
+        // Don't trust the length for the allocation; every element takes at least one byte.
+        let mut values = Vec::with_capacity(len.min(self.buf.len()));
+        for _ in 0..len {
+            values.push(T::decode_from(self)?);
+        }
+        Ok(values)
+    }
+
+    /// Fails if any input is left over.
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::TrailingBytes(self.buf.len()))
+        }
+    }
+}
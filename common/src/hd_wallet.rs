@@ -0,0 +1,305 @@
+//! Hierarchical deterministic wallet backed by a BIP-39 mnemonic.
+//!
+//! Account keys are derived with SLIP-0010 for the NIST P-256 curve along the path
+//! `m/44'/COIN_TYPE'/account'/0'/index'`. Every level is hardened, so a leaked child key
+//! reveals nothing about its siblings or the seed.
+//!
+//! Single-key [`Wallet`]s created before HD support can be kept alongside the derived keys
+//! as imported accounts. They can spend, but no further addresses are derived from them.
+
+use crate::keystore::{change_keystore_passphrase, is_keystore_file, keystore_kind, read_keystore, write_keystore, KeystoreKind, WalletError};
+use crate::wallet::Wallet;
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use p256::NonZeroScalar;
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// SLIP-0044 style coin type used in derivation paths ("IM").
+pub const COIN_TYPE: u32 = 0x494d;
+/// Number of consecutive unused addresses after which restoring stops looking for more.
+pub const GAP_LIMIT: u32 = 20;
+pub const MNEMONIC_WORDS: usize = 24;
+
+const HARDENED: u32 = 0x8000_0000;
+const CURVE_SEED_KEY: &[u8] = b"Nist256p1 seed";
+
+type HmacSha512 = Hmac<Sha512>;
+
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// A private key together with the chain code needed to derive its children.
+struct ExtendedKey {
+    key: SigningKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    fn master(seed: &[u8]) -> Self {
+        let mut i = hmac_sha512(CURVE_SEED_KEY, &[seed]);
+        loop {
+            // SLIP-0010: retry with I as the new input while IL is not a valid scalar
+            if let Ok(key) = SigningKey::from_slice(&i[..32]) {
+                return ExtendedKey { key, chain_code: i[32..].try_into().unwrap() };
+            }
+            i = hmac_sha512(CURVE_SEED_KEY, &[&i]);
+        }
+    }
+
+    fn derive_hardened(&self, index: u32) -> Self {
+        let index = (index | HARDENED).to_be_bytes();
+        let mut i = hmac_sha512(&self.chain_code, &[&[0x00], &self.key.to_bytes(), &index]);
+        loop {
+            if let Ok(tweak) = SigningKey::from_slice(&i[..32]) {
+                let sum = **self.key.as_nonzero_scalar() + **tweak.as_nonzero_scalar();
+                if let Some(scalar) = Option::<NonZeroScalar>::from(NonZeroScalar::new(sum)) {
+                    return ExtendedKey { key: SigningKey::from(scalar), chain_code: i[32..].try_into().unwrap() };
+                }
+            }
+            // SLIP-0010: IL was out of range or the child key was zero
+            i = hmac_sha512(&self.chain_code, &[&[0x01], &i[32..], &index]);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivedAddress {
+    pub index: u32,
+    pub address: String,
+    pub used: bool,
+}
+
+#[derive(Clone)]
+pub struct HdWallet {
+    mnemonic: Mnemonic,
+    seed: [u8; 64],
+    pub account: u32,
+    pub addresses: Vec<DerivedAddress>, // Every address derived so far, by index
+    pub imported: Vec<Wallet>,          // Legacy single-key accounts
+}
+
+// The mnemonic and seed are left out, so that logging or unwrapping a wallet cannot leak them
+impl fmt::Debug for HdWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HdWallet")
+            .field("mnemonic", &"<redacted>")
+            .field("seed", &"<redacted>")
+            .field("account", &self.account)
+            .field("addresses", &self.addresses)
+            .field("imported", &self.imported)
+            .finish()
+    }
+}
+
+// The mnemonic zeroizes itself
+impl Drop for HdWallet {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+// What is encrypted in the wallet file. Addresses are re-derived on load, only their
+// `used` flags need storing.
+#[derive(Serialize, Deserialize)]
+struct HdWalletData {
+    mnemonic: String,
+    account: u32,
+    used: Vec<bool>,
+    imported: Vec<Wallet>,
+}
+
+impl HdWallet {
+    /// Creates a wallet from a fresh random mnemonic.
+    pub fn generate() -> Self {
+        let mnemonic = Mnemonic::generate(MNEMONIC_WORDS).expect("supported word count");
+        Self::from_parsed_mnemonic(mnemonic)
+    }
+
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, WalletError> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|e| WalletError::Corrupt(format!("invalid mnemonic: {}", e)))?;
+        Ok(Self::from_parsed_mnemonic(mnemonic))
+    }
+
+    fn from_parsed_mnemonic(mnemonic: Mnemonic) -> Self {
+        let seed = mnemonic.to_seed("");
+        HdWallet { mnemonic, seed, account: 0, addresses: vec![], imported: vec![] }
+    }
+
+    /// Rebuilds a wallet from its mnemonic, deriving addresses until [`GAP_LIMIT`] in a row
+    /// have never been used according to `is_used`.
+    pub fn restore<F: FnMut(&str) -> bool>(phrase: &str, mut is_used: F) -> Result<Self, WalletError> {
+        let mut wallet = Self::from_mnemonic(phrase)?;
+        let mut candidates = Vec::new();
+        let mut unused_run = 0;
+        let mut index = 0;
+        while unused_run < GAP_LIMIT {
+            let address = wallet.derive_wallet(index).get_address();
+            let used = is_used(&address);
+            unused_run = if used { 0 } else { unused_run + 1 };
+            candidates.push(DerivedAddress { index, address, used });
+            index += 1;
+        }
+
+        // Keep everything up to the last used address, and at least the first one
+        let keep = candidates.iter().rposition(|a| a.used).map_or(1, |last| last + 1);
+        candidates.truncate(keep);
+        wallet.addresses = candidates;
+        Ok(wallet)
+    }
+
+    pub fn mnemonic(&self) -> String {
+        self.mnemonic.to_string()
+    }
+
+    /// Derivation path of the key at `index`.
+    pub fn path(&self, index: u32) -> String {
+        format!("m/44'/{}'/{}'/0'/{}'", COIN_TYPE, self.account, index)
+    }
+
+    pub fn derive_key(&self, index: u32) -> SigningKey {
+        [44, COIN_TYPE, self.account, 0, index]
+            .iter()
+            .fold(ExtendedKey::master(&self.seed), |parent, &child| parent.derive_hardened(child))
+            .key
+    }
+
+    pub fn derive_wallet(&self, index: u32) -> Wallet {
+        let private_key = self.derive_key(index);
+        Wallet { public_key: VerifyingKey::from(&private_key), private_key }
+    }
+
+    /// Derives the next address, or returns the newest one if it has not been used yet.
+    pub fn receive_address(&mut self) -> String {
+        if let Some(last) = self.addresses.last() {
+            if !last.used {
+                return last.address.clone();
+            }
+        }
+        self.new_address()
+    }
+
+    /// Always derives a new address.
+    pub fn new_address(&mut self) -> String {
+        let index = self.addresses.len() as u32;
+        let address = self.derive_wallet(index).get_address();
+        self.addresses.push(DerivedAddress { index, address: address.clone(), used: false });
+        address
+    }
+
+    /// Records that `address` has received or sent funds. Returns false if it is not ours.
+    pub fn mark_used(&mut self, address: &str) -> bool {
+        match self.addresses.iter_mut().find(|a| a.address == address) {
+            Some(derived) => {
+                derived.used = true;
+                true
+            }
+            None => self.imported.iter().any(|w| w.get_address() == address),
+        }
+    }
+
+    pub fn import_legacy(&mut self, wallet: Wallet) {
+        if !self.imported.iter().any(|w| w.get_address() == wallet.get_address()) {
+            self.imported.push(wallet);
+        }
+    }
+
+    /// Every address this wallet can spend from: derived ones first, then imported ones.
+    pub fn all_addresses(&self) -> Vec<String> {
+        self.addresses.iter()
+            .map(|a| a.address.clone())
+            .chain(self.imported.iter().map(Wallet::get_address))
+            .collect()
+    }
+
+    /// The key pair that controls `address`, if it belongs to this wallet.
+    pub fn wallet_for(&self, address: &str) -> Option<Wallet> {
+        if let Some(derived) = self.addresses.iter().find(|a| a.address == address) {
+            return Some(self.derive_wallet(derived.index));
+        }
+        self.imported.iter().find(|w| w.get_address() == address).cloned()
+    }
+
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<(), WalletError> {
+        let data = HdWalletData {
+            mnemonic: self.mnemonic(),
+            account: self.account,
+            used: self.addresses.iter().map(|a| a.used).collect(),
+            imported: self.imported.clone(),
+        };
+        let secret = serde_json::to_vec(&data).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        write_keystore(path, KeystoreKind::HdWallet, &secret, passphrase)
+    }
+
+    pub fn unlock<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, WalletError> {
+        let secret = read_keystore(path, KeystoreKind::HdWallet, passphrase)?;
+        let data: HdWalletData = serde_json::from_slice(&secret).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+
+        let mut wallet = Self::from_mnemonic(&data.mnemonic)?;
+        wallet.account = data.account;
+        for used in data.used {
+            wallet.new_address();
+            wallet.addresses.last_mut().unwrap().used = used;
+        }
+        wallet.imported = data.imported;
+        Ok(wallet)
+    }
+}
+
+/// Either kind of wallet file: an HD wallet, or a single key from before HD wallets existed.
+#[derive(Debug, Clone)]
+pub enum WalletFile {
+    Hd(HdWallet),
+    Legacy(Wallet),
+}
+
+impl WalletFile {
+    /// Decrypts whichever kind of wallet is stored at `path`. Unencrypted legacy files are
+    /// loaded too so they can be migrated.
+    pub fn unlock<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, WalletError> {
+        let path = path.as_ref();
+        if !is_keystore_file(path)? {
+            return Ok(WalletFile::Legacy(Wallet::load_from_file(path)?));
+        }
+        match keystore_kind(path)? {
+            KeystoreKind::HdWallet => Ok(WalletFile::Hd(HdWallet::unlock(path, passphrase)?)),
+            KeystoreKind::SingleKey => Ok(WalletFile::Legacy(Wallet::unlock(path, passphrase)?)),
+        }
+    }
+
+    /// Re-encrypts the wallet at `path`, whichever kind it is, under `new_passphrase`.
+    pub fn change_passphrase<P: AsRef<Path>>(path: P, old_passphrase: &str, new_passphrase: &str) -> Result<(), WalletError> {
+        let path = path.as_ref();
+        change_keystore_passphrase(path, keystore_kind(path)?, old_passphrase, new_passphrase)
+    }
+
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<(), WalletError> {
+        match self {
+            WalletFile::Hd(wallet) => wallet.save_encrypted(path, passphrase),
+            WalletFile::Legacy(wallet) => wallet.save_encrypted(path, passphrase),
+        }
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        match self {
+            WalletFile::Hd(wallet) => wallet.all_addresses(),
+            WalletFile::Legacy(wallet) => vec![wallet.get_address()],
+        }
+    }
+
+    pub fn wallet_for(&self, address: &str) -> Option<Wallet> {
+        match self {
+            WalletFile::Hd(wallet) => wallet.wallet_for(address),
+            WalletFile::Legacy(wallet) => Some(wallet.clone()).filter(|w| w.get_address() == address),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Passphrase-encrypted key files.
+//!
+//! File layout, all integers big-endian:
+//!
+//! ```text
+//! magic      4 bytes   "IMCW"
+//! version    u8        KEYSTORE_VERSION
+//! kind       u8        what the plaintext holds, see `KeystoreKind`
+//! m_cost     u32       Argon2id memory cost in KiB
+//! t_cost     u32       Argon2id iterations
+//! p_cost     u32       Argon2id lanes
+//! salt       16 bytes
+//! nonce      12 bytes
+//! ciphertext ..        ChaCha20-Poly1305, with every byte above as associated data
+//! ```
+//!
+//! The KDF parameters live in the header so they can be raised later without breaking files
+//! that were written with the old ones.
+
+use crate::storage::write_atomic;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand_core::{OsRng, RngCore};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const KEYSTORE_MAGIC: &[u8; 4] = b"IMCW";
+pub const KEYSTORE_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 * 3 + SALT_LEN + NONCE_LEN;
+
+// OWASP's minimum recommendation for Argon2id
+const DEFAULT_M_COST: u32 = 19 * 1024;
+const DEFAULT_T_COST: u32 = 2;
+const DEFAULT_P_COST: u32 = 1;
+
+// Upper bounds on the parameters read from a file, so that an edited header is reported as
+// corrupt instead of exhausting memory or time
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystoreKind {
+    SingleKey = 1,
+    HdWallet = 2,
+}
+
+impl KeystoreKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(KeystoreKind::SingleKey),
+            2 => Some(KeystoreKind::HdWallet),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    Io(io::Error),
+    NotEncrypted,
+    UnsupportedVersion(u8),
+    UnexpectedKind(u8),
+    WrongPassphrase,
+    Corrupt(String),
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Io(e) => write!(f, "wallet file error: {}", e),
+            WalletError::NotEncrypted => write!(f, "wallet file is not an encrypted wallet"),
+            WalletError::UnsupportedVersion(version) => write!(f, "unsupported wallet file version {}", version),
+            WalletError::UnexpectedKind(kind) => write!(f, "wallet file holds key type {}, which cannot be loaded here", kind),
+            WalletError::WrongPassphrase => write!(f, "wrong passphrase or damaged wallet file"),
+            WalletError::Corrupt(message) => write!(f, "wallet file is corrupt: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<io::Error> for WalletError {
+    fn from(e: io::Error) -> Self {
+        WalletError::Io(e)
+    }
+}
+
+/// Whether the file at `path` starts with the keystore magic bytes.
+pub fn is_keystore_file<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let data = fs::read(path)?;
+    Ok(data.starts_with(KEYSTORE_MAGIC))
+}
+
+/// Reads which kind of secret the keystore at `path` holds from its unencrypted header.
+pub fn keystore_kind<P: AsRef<Path>>(path: P) -> Result<KeystoreKind, WalletError> {
+    let data = fs::read(path)?;
+    if !data.starts_with(KEYSTORE_MAGIC) {
+        return Err(WalletError::NotEncrypted);
+    }
+    match data.get(5) {
+        Some(&kind) => KeystoreKind::from_byte(kind).ok_or(WalletError::UnexpectedKind(kind)),
+        None => Err(WalletError::Corrupt("header is truncated".to_string())),
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Key, WalletError> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| WalletError::Corrupt(e.to_string()))?;
+    Ok(key)
+}
+
+/// Encrypts `secret` under `passphrase` with a fresh salt and nonce.
+pub fn seal(kind: KeystoreKind, secret: &[u8], passphrase: &str) -> Result<Vec<u8>, WalletError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(KEYSTORE_MAGIC);
+    header.push(KEYSTORE_VERSION);
+    header.push(kind as u8);
+    header.extend_from_slice(&DEFAULT_M_COST.to_be_bytes());
+    header.extend_from_slice(&DEFAULT_T_COST.to_be_bytes());
+    header.extend_from_slice(&DEFAULT_P_COST.to_be_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, DEFAULT_M_COST, DEFAULT_T_COST, DEFAULT_P_COST)?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret, aad: &header })
+        .map_err(|_| WalletError::Corrupt("encryption failed".to_string()))?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Decrypts data written by [`seal`], checking that it holds a secret of the expected kind.
+pub fn unseal(data: &[u8], expected_kind: KeystoreKind, passphrase: &str) -> Result<Vec<u8>, WalletError> {
+    if !data.starts_with(KEYSTORE_MAGIC) {
+        return Err(WalletError::NotEncrypted);
+    }
+    if data.len() < HEADER_LEN {
+        return Err(WalletError::Corrupt("header is truncated".to_string()));
+    }
+    if data[4] != KEYSTORE_VERSION {
+        return Err(WalletError::UnsupportedVersion(data[4]));
+    }
+    if KeystoreKind::from_byte(data[5]) != Some(expected_kind) {
+        return Err(WalletError::UnexpectedKind(data[5]));
+    }
+
+    let read_u32 = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+    let (m_cost, t_cost, p_cost) = (read_u32(6), read_u32(10), read_u32(14));
+    if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+        return Err(WalletError::Corrupt(format!("key derivation parameters {}/{}/{} are out of range", m_cost, t_cost, p_cost)));
+    }
+    let salt = &data[18..18 + SALT_LEN];
+    let nonce = &data[18 + SALT_LEN..HEADER_LEN];
+    let (header, ciphertext) = data.split_at(HEADER_LEN);
+
+    let key = derive_key(passphrase, salt, m_cost, t_cost, p_cost)?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| WalletError::WrongPassphrase)
+}
+
+pub fn write_keystore<P: AsRef<Path>>(path: P, kind: KeystoreKind, secret: &[u8], passphrase: &str) -> Result<(), WalletError> {
+    write_atomic(path, &seal(kind, secret, passphrase)?)?;
+    Ok(())
+}
+
+pub fn read_keystore<P: AsRef<Path>>(path: P, kind: KeystoreKind, passphrase: &str) -> Result<Vec<u8>, WalletError> {
+    unseal(&fs::read(path)?, kind, passphrase)
+}
+
+/// Re-encrypts the keystore at `path` under `new_passphrase`, with a fresh salt and nonce.
+pub fn change_keystore_passphrase<P: AsRef<Path>>(path: P, kind: KeystoreKind, old_passphrase: &str, new_passphrase: &str) -> Result<(), WalletError> {
+    let path = path.as_ref();
+    let secret = read_keystore(path, kind, old_passphrase)?;
+    write_keystore(path, kind, &secret, new_passphrase)
+}
@@ -1 +1,27 @@
-pub mod wallet;
\ No newline at end of file
+//! Building blocks shared by the InfiniMath chains: canonical encoding, addresses, key and
+//! signature types, and file storage helpers.
+//!
+//! Wallets and encrypted key files are behind the `wallet` feature (on by default), which
+//! pulls in the key-derivation and encryption dependencies. Crates that only verify
+//! transactions can turn it off.
+
+pub mod address;
+pub mod encoding;
+pub mod signature;
+pub mod storage;
+#[cfg(feature = "wallet")]
+pub mod hd_wallet;
+#[cfg(feature = "wallet")]
+pub mod keystore;
+#[cfg(feature = "wallet")]
+pub mod wallet;
+
+pub mod prelude {
+    pub use crate::address::{address_from_public_key, parse_address, validate_address, AddressError};
+    pub use crate::encoding::{decode, encode, Decode, DecodeError, Decoder, Encode, Encoder};
+    pub use crate::signature::{OptionalSerializableSignature, SerializablePublicKey, SerializableSignature};
+    #[cfg(feature = "wallet")]
+    pub use crate::keystore::WalletError;
+    #[cfg(feature = "wallet")]
+    pub use crate::wallet::{HdWallet, Wallet, WalletFile};
+}
@@ -0,0 +1,84 @@
+//! Serde and canonical-encoding wrappers for P-256 keys and signatures, as carried in
+//! transactions. These need no private keys, so they are available without the `wallet`
+//! feature.
+
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize, Serializer, Deserializer};
+use serde::de::Error as DeError;
+use crate::encoding::{Decode, DecodeError, Decoder, Encode, Encoder};
+
+/// A verifying key that serializes as its compressed SEC1 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializablePublicKey(pub VerifyingKey);
+
+impl Serialize for SerializablePublicKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0.to_encoded_point(true).as_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializablePublicKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes: Vec<u8> = Deserialize::deserialize(deserializer)?;
+        VerifyingKey::from_sec1_bytes(&bytes).map(SerializablePublicKey).map_err(DeError::custom)
+    }
+}
+
+impl Encode for SerializablePublicKey {
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_bytes(self.0.to_encoded_point(true).as_bytes());
+    }
+}
+
+impl Decode for SerializablePublicKey {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        let bytes = decoder.get_bytes()?;
+        VerifyingKey::from_sec1_bytes(bytes).map(SerializablePublicKey).map_err(|e| DecodeError::Invalid(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OptionalSerializableSignature(pub Option<SerializableSignature>);
+
+#[derive(Debug, Clone)]
+pub struct SerializableSignature(pub Signature);
+
+impl Serialize for SerializableSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let der_encoded = self.0.to_der();
+        let bytes = der_encoded.as_bytes();
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializableSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes: Vec<u8> = Deserialize::deserialize(deserializer)?;
+        Signature::from_der(&bytes).map(SerializableSignature).map_err(DeError::custom)
+    }
+}
+
+impl Encode for SerializableSignature {
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_bytes(self.0.to_der().as_bytes());
+    }
+}
+
+impl Decode for SerializableSignature {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        let bytes = decoder.get_bytes()?;
+        Signature::from_der(bytes).map(SerializableSignature).map_err(|e| DecodeError::Invalid(e.to_string()))
+    }
+}
@@ -0,0 +1,44 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes `data` to `path` so that readers only ever see the old or the new contents.
+///
+/// The bytes go to a temporary file next to the destination, are synced to disk and then
+/// renamed over `path`. A crash part-way through leaves at most a stray `.tmp` file behind.
+pub fn write_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+
+    {
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, path)?;
+
+    // Persist the rename itself; directories cannot be opened for syncing on every platform.
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+pub fn write_json_atomic<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> io::Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    write_atomic(path, &data)
+}
+
+/// Reads a JSON file written by [`write_json_atomic`], returning `None` if it does not exist.
+pub fn read_json<P: AsRef<Path>, T: DeserializeOwned>(path: P) -> io::Result<Option<T>> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(serde_json::from_reader(io::BufReader::new(file))?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
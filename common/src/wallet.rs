@@ -1,20 +1,29 @@
 use p256::ecdsa::{SigningKey, VerifyingKey};
-use p256::ecdsa::Signature;
 use rand_core::OsRng;
 use serde::{Deserialize, Serialize, Serializer, Deserializer};
 use serde::ser::SerializeStruct;
 use serde::de::Error as DeError;
+use crate::address::address_from_public_key;
+use crate::keystore::{change_keystore_passphrase, read_keystore, write_keystore, KeystoreKind, WalletError};
 use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{self};
 use std::path::Path;
 
+pub use crate::hd_wallet::{DerivedAddress, HdWallet, WalletFile};
+
 #[derive(Debug, Clone)]
 pub struct Wallet {
     pub public_key: VerifyingKey,
     pub private_key: SigningKey,
 }
 
+impl Default for Wallet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Wallet {
     pub fn new() -> Self {
         let private_key = SigningKey::random(&mut OsRng);
@@ -23,16 +32,32 @@ impl Wallet {
     }
 
     pub fn get_address(&self) -> String {
-        // Simplified address generation for demonstration purposes
-        format!("{:?}", self.public_key.to_encoded_point(false))
+        address_from_public_key(&self.public_key)
+    }
+
+    fn from_private_key_bytes(bytes: &[u8]) -> Result<Self, WalletError> {
+        let private_key = SigningKey::try_from(bytes).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let public_key = VerifyingKey::from(&private_key);
+        Ok(Self { private_key, public_key })
     }
 
-    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        let file = File::create(path)?;
-        serde_json::to_writer(file, self)?;
-        Ok(())
+    /// Writes the private key to `path`, encrypted under `passphrase`.
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<(), WalletError> {
+        write_keystore(path, KeystoreKind::SingleKey, &self.private_key.to_bytes(), passphrase)
     }
 
+    /// Decrypts a wallet written by [`Wallet::save_encrypted`].
+    pub fn unlock<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, WalletError> {
+        let secret = read_keystore(path, KeystoreKind::SingleKey, passphrase)?;
+        Self::from_private_key_bytes(&secret)
+    }
+
+    pub fn change_passphrase<P: AsRef<Path>>(path: P, old_passphrase: &str, new_passphrase: &str) -> Result<(), WalletError> {
+        change_keystore_passphrase(path, KeystoreKind::SingleKey, old_passphrase, new_passphrase)
+    }
+
+    /// Loads an old unencrypted JSON wallet file. Only used to migrate such files to
+    /// [`Wallet::save_encrypted`]; new wallets are never written in this format.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let file = File::open(path)?;
         let wallet = serde_json::from_reader(file)?;
@@ -70,30 +95,3 @@ impl<'de> Deserialize<'de> for Wallet {
         Ok(Wallet { public_key, private_key })
     }
 }
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct OptionalSerializableSignature(pub Option<SerializableSignature>);
-
-#[derive(Debug, Clone)]
-pub struct SerializableSignature(pub Signature);
-
-impl Serialize for SerializableSignature {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let der_encoded = self.0.to_der();
-        let bytes = der_encoded.as_bytes();
-        serializer.serialize_bytes(bytes)
-    }
-}
-
-impl<'de> Deserialize<'de> for SerializableSignature {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let bytes: Vec<u8> = Deserialize::deserialize(deserializer)?;
-        Signature::from_der(&bytes).map(SerializableSignature).map_err(DeError::custom)
-    }
-}
\ No newline at end of file
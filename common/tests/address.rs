@@ -0,0 +1,59 @@
+// Base58Check addresses: a known key's address, and the errors a mistyped or damaged
+// address gives.
+
+use common::address::{address_from_public_key, address_matches_key, key_hash, parse_address, validate_address, AddressError, ADDRESS_VERSION};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+
+fn public_key(n: u8) -> VerifyingKey {
+    VerifyingKey::from(&SigningKey::from_slice(&[n; 32]).unwrap())
+}
+
+// Replaces the character at `at` with another base58 character
+fn mistype(address: &str, at: usize) -> String {
+    let mut chars: Vec<char> = address.chars().collect();
+    chars[at] = if chars[at] == '2' { '3' } else { '2' };
+    chars.into_iter().collect()
+}
+
+#[test]
+fn addresses_round_trip() {
+    let address = address_from_public_key(&public_key(1));
+    assert!(address.starts_with('R'), "{}", address);
+    validate_address(&address).unwrap();
+    assert!(address_matches_key(&address, &public_key(1)));
+    assert!(!address_matches_key(&address, &public_key(2)));
+    assert_eq!(parse_address(&address).unwrap(), key_hash(&public_key(1)));
+}
+
+#[test]
+fn a_mistyped_character_fails_the_checksum() {
+    let address = address_from_public_key(&public_key(1));
+    for at in 0..address.len() {
+        let typo = mistype(&address, at);
+        assert_eq!(parse_address(&typo), Err(AddressError::InvalidChecksum), "{}", typo);
+        assert!(!address_matches_key(&typo, &public_key(1)));
+    }
+
+    // Swapping two neighbouring characters is caught as well
+    let mut chars: Vec<char> = address.chars().collect();
+    let at = (1..chars.len()).find(|&i| chars[i] != chars[i - 1]).unwrap();
+    chars.swap(at - 1, at);
+    let swapped: String = chars.into_iter().collect();
+    assert_eq!(parse_address(&swapped), Err(AddressError::InvalidChecksum), "{}", swapped);
+}
+
+#[test]
+fn malformed_addresses_are_refused() {
+    let address = address_from_public_key(&public_key(1));
+
+    // 0, O, I and l are not base58
+    let zero = format!("{}0", &address[..address.len() - 1]);
+    assert_eq!(parse_address(&zero), Err(AddressError::InvalidBase58));
+    assert_eq!(parse_address(&address[..address.len() - 1]), Err(AddressError::InvalidChecksum));
+    assert_eq!(parse_address(""), Err(AddressError::InvalidLength(0)));
+
+    let short = bs58::encode([7u8; 10]).with_check_version(ADDRESS_VERSION).into_string();
+    assert_eq!(parse_address(&short), Err(AddressError::InvalidLength(11)));
+    let other_version = bs58::encode([7u8; 20]).with_check_version(ADDRESS_VERSION + 1).into_string();
+    assert_eq!(parse_address(&other_version), Err(AddressError::UnknownVersion(ADDRESS_VERSION + 1)));
+}
@@ -0,0 +1,100 @@
+// Golden vectors for the primitive encoding rules. These are independent of any type's
+// version; if one of them fails, every hash has changed.
+
+use bigdecimal::BigDecimal;
+use common::encoding::{decode, encode, Decode, DecodeError, Decoder, Encode, Encoder};
+use std::str::FromStr;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn primitive_vector() {
+    let mut encoder = Encoder::new();
+    encoder.put_u8(7);
+    encoder.put_u32(0x01020304);
+    encoder.put_u64(1);
+    encoder.put_i64(-2);
+    encoder.put_u128(3);
+    encoder.put_bool(true);
+    encoder.put_str("hi");
+    encoder.put_decimal(&BigDecimal::from_str("10.50").unwrap());
+    encoder.put_decimal(&BigDecimal::from_str("-0.001").unwrap());
+
+    assert_eq!(
+        hex(&encoder.finish()),
+        concat!(
+            "07",                               // u8
+            "01020304",                         // u32
+            "0000000000000001",                 // u64
+            "fffffffffffffffe",                 // i64
+            "00000000000000000000000000000003", // u128
+            "01",                               // bool
+            "000000026869",                     // "hi"
+            "0000000169", "0000000000000001",   // 10.50 = 105e-1
+            "00000001ff", "0000000000000003",   // -0.001 = -1e-3
+        )
+    );
+}
+
+#[test]
+fn equal_decimals_encode_identically() {
+    let encode = |s: &str| {
+        let mut encoder = Encoder::new();
+        encoder.put_decimal(&BigDecimal::from_str(s).unwrap());
+        encoder.finish()
+    };
+    assert_eq!(encode("10"), encode("10.000"));
+    assert_eq!(encode("10"), encode("1e1"));
+    assert_ne!(encode("10"), encode("1"));
+}
+
+#[test]
+fn length_prefix_separates_fields() {
+    let encode = |a: &str, b: &str| {
+        let mut encoder = Encoder::new();
+        encoder.put_str(a);
+        encoder.put_str(b);
+        encoder.finish()
+    };
+    assert_ne!(encode("1", "23"), encode("12", "3"));
+}
+
+struct Pair(u64, String);
+
+impl Encode for Pair {
+    const VERSION: u8 = 3;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_u64(self.0);
+        encoder.put_str(&self.1);
+    }
+}
+
+impl Decode for Pair {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Pair(decoder.get_u64()?, decoder.get_string()?))
+    }
+}
+
+#[test]
+fn decode_rejects_bad_input() {
+    let mut bytes = encode(&Pair(5, "x".to_string()));
+    assert_eq!(hex(&bytes), concat!("03", "0000000000000005", "0000000178"));
+
+    let pair: Pair = decode(&bytes).unwrap();
+    assert_eq!((pair.0, pair.1.as_str()), (5, "x"));
+
+    assert_eq!(decode::<Pair>(&bytes[..bytes.len() - 1]).err(), Some(DecodeError::UnexpectedEnd));
+    assert_eq!(decode::<Pair>(&[]).err(), Some(DecodeError::UnexpectedEnd));
+
+    bytes.push(0);
+    assert_eq!(decode::<Pair>(&bytes).err(), Some(DecodeError::TrailingBytes(1)));
+    bytes.pop();
+
+    bytes[0] = 4;
+    assert_eq!(decode::<Pair>(&bytes).err(), Some(DecodeError::UnsupportedVersion(4)));
+    bytes[0] = 2;
+    assert_eq!(decode::<Pair>(&bytes).err(), Some(DecodeError::UnsupportedVersion(2)));
+}
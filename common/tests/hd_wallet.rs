@@ -0,0 +1,94 @@
+// HD wallet derivation from a known mnemonic, restoring from the mnemonic, refusing
+// mnemonics with a bad checksum, and keeping the secrets out of debug output. The expected
+// keys were computed independently from the BIP-39 seed and SLIP-0010 for P-256.
+
+use common::keystore::WalletError;
+use common::wallet::HdWallet;
+use std::collections::HashSet;
+use std::fs;
+
+// The all-zero entropy BIP-39 vector; its seed (with no passphrase) starts 5eb00bbd
+const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn a_known_mnemonic_derives_known_keys() {
+    let wallet = HdWallet::from_mnemonic(MNEMONIC).unwrap();
+    assert_eq!(wallet.path(0), "m/44'/18765'/0'/0'/0'");
+    assert_eq!(hex(&wallet.derive_key(0).to_bytes()), "d4fcbec2c13d98b5b31730e78551e1f7a40fe8a013acada89530f62840adb182");
+    assert_eq!(wallet.derive_wallet(0).get_address(), "RMpachTymRHat9jN4Rb14MYiABeTDZ4t7v");
+    assert_eq!(hex(&wallet.derive_key(1).to_bytes()), "f68380b46479df088d2a9838c097cfd2b6803b341e3b15f15fb2e9323e18c305");
+    assert_eq!(wallet.derive_wallet(1).get_address(), "RRbgLVxW3zrsoSJV5Y3cxE2HPYMxHK1SS8");
+}
+
+#[test]
+fn restoring_finds_the_same_keys() {
+    let mut original = HdWallet::generate();
+    let addresses: Vec<String> = (0..6).map(|_| original.new_address()).collect();
+    let used: HashSet<&str> = [&addresses[1], &addresses[4]].into_iter().map(String::as_str).collect();
+    for address in &used {
+        assert!(original.mark_used(address));
+    }
+
+    let restored = HdWallet::restore(&original.mnemonic(), |address| used.contains(address)).unwrap();
+    assert_eq!(restored.all_addresses(), addresses[..5]);
+    for (index, address) in addresses[..5].iter().enumerate() {
+        assert_eq!(restored.addresses[index].used, used.contains(address.as_str()));
+        let key = restored.wallet_for(address).unwrap().private_key.to_bytes();
+        assert_eq!(key, original.derive_key(index as u32).to_bytes());
+    }
+
+    // A wallet that was never used restores to just its first address
+    let fresh = HdWallet::generate();
+    let restored = HdWallet::restore(&fresh.mnemonic(), |_| false).unwrap();
+    assert_eq!(restored.all_addresses(), [fresh.derive_wallet(0).get_address()]);
+}
+
+#[test]
+fn saved_wallets_unlock_with_their_addresses() {
+    let dir = std::env::temp_dir().join(format!("common-hd-wallet-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("wallet.dat");
+
+    let mut wallet = HdWallet::from_mnemonic(MNEMONIC).unwrap();
+    wallet.new_address();
+    let second = wallet.new_address();
+    wallet.mark_used(&second);
+    wallet.save_encrypted(&path, "passphrase").unwrap();
+
+    let unlocked = HdWallet::unlock(&path, "passphrase").unwrap();
+    assert_eq!(unlocked.mnemonic(), MNEMONIC);
+    assert_eq!(unlocked.addresses, wallet.addresses);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn mnemonics_with_a_bad_checksum_are_refused() {
+    // The last word carries the checksum; "abandon" in its place does not match
+    let bad_checksum = MNEMONIC.replace("about", "abandon");
+    let unknown_word = MNEMONIC.replace("about", "aboutt");
+    let too_short = MNEMONIC.replacen("abandon ", "", 1);
+    for phrase in [bad_checksum.as_str(), unknown_word.as_str(), too_short.as_str()] {
+        assert!(matches!(HdWallet::from_mnemonic(phrase), Err(WalletError::Corrupt(_))), "{}", phrase);
+        assert!(HdWallet::restore(phrase, |_| false).is_err(), "{}", phrase);
+    }
+}
+
+#[test]
+fn debug_output_hides_the_secrets() {
+    let mut wallet = HdWallet::from_mnemonic(MNEMONIC).unwrap();
+    wallet.new_address();
+    let imported = HdWallet::generate().derive_wallet(0);
+    let imported_key = hex(&imported.private_key.to_bytes());
+    wallet.import_legacy(imported);
+
+    let debug = format!("{:?}", wallet);
+    assert!(debug.contains(&wallet.addresses[0].address), "{}", debug);
+    assert!(!debug.contains("abandon"), "{}", debug);
+    assert!(!debug.contains("94, 176, 11, 189"), "{}", debug); // The seed, 5eb00bbd...
+    assert!(!debug.contains(&imported_key), "{}", debug);
+}
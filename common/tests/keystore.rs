@@ -0,0 +1,95 @@
+// Encrypted wallet files: only the right passphrase opens them, any edit to the file is
+// detected, and changing the passphrase keeps the key.
+
+use common::keystore::{seal, unseal, KeystoreKind, WalletError, KEYSTORE_VERSION};
+use common::wallet::{Wallet, WalletFile};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use std::fs;
+use std::path::PathBuf;
+
+// Magic, version, kind, three KDF costs, salt and nonce
+const HEADER_LEN: usize = 4 + 1 + 1 + 12 + 16 + 12;
+const SECRET: &[u8] = b"thirty-two bytes of wallet key..";
+
+fn wallet_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("common-keystore-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("wallet.dat")
+}
+
+fn wallet() -> Wallet {
+    let private_key = SigningKey::from_slice(&[1; 32]).unwrap();
+    Wallet { public_key: VerifyingKey::from(&private_key), private_key }
+}
+
+#[test]
+fn sealed_secrets_open_with_their_passphrase() {
+    let sealed = seal(KeystoreKind::SingleKey, SECRET, "correct horse").unwrap();
+    assert_eq!(&sealed[..4], b"IMCW");
+    assert_eq!(sealed[4], KEYSTORE_VERSION);
+    assert!(!sealed.windows(SECRET.len()).any(|window| window == SECRET));
+    assert_eq!(unseal(&sealed, KeystoreKind::SingleKey, "correct horse").unwrap(), SECRET);
+
+    // A fresh salt and nonce every time
+    assert_ne!(seal(KeystoreKind::SingleKey, SECRET, "correct horse").unwrap(), sealed);
+}
+
+#[test]
+fn a_wrong_passphrase_is_refused() {
+    let sealed = seal(KeystoreKind::SingleKey, SECRET, "correct horse").unwrap();
+    for passphrase in ["correct horsf", "Correct horse", ""] {
+        let result = unseal(&sealed, KeystoreKind::SingleKey, passphrase);
+        assert!(matches!(result, Err(WalletError::WrongPassphrase)), "{:?}", result);
+    }
+}
+
+#[test]
+fn tampered_files_are_refused() {
+    let sealed = seal(KeystoreKind::SingleKey, SECRET, "correct horse").unwrap();
+
+    // The KDF parameters, salt and nonce are authenticated as associated data. Each edit here
+    // still derives a key quickly; costs that would not are refused before deriving.
+    for (at, what) in [(9, "m_cost"), (13, "t_cost"), (20, "salt"), (40, "nonce"), (HEADER_LEN, "ciphertext")] {
+        let mut tampered = sealed.clone();
+        tampered[at] ^= 0x01;
+        let result = unseal(&tampered, KeystoreKind::SingleKey, "correct horse");
+        assert!(matches!(result, Err(WalletError::WrongPassphrase)), "{}: {:?}", what, result);
+    }
+    let mut expensive = sealed.clone();
+    expensive[6] = 0xff;
+    assert!(matches!(unseal(&expensive, KeystoreKind::SingleKey, "correct horse"), Err(WalletError::Corrupt(_))));
+
+    let mut ciphertext = sealed.clone();
+    *ciphertext.last_mut().unwrap() ^= 0x80;
+    assert!(matches!(unseal(&ciphertext, KeystoreKind::SingleKey, "correct horse"), Err(WalletError::WrongPassphrase)));
+
+    let mut version = sealed.clone();
+    version[4] = KEYSTORE_VERSION + 1;
+    assert!(matches!(unseal(&version, KeystoreKind::SingleKey, "correct horse"), Err(WalletError::UnsupportedVersion(v)) if v == KEYSTORE_VERSION + 1));
+
+    assert!(matches!(unseal(&sealed, KeystoreKind::HdWallet, "correct horse"), Err(WalletError::UnexpectedKind(1))));
+    assert!(matches!(unseal(&sealed[..20], KeystoreKind::SingleKey, "correct horse"), Err(WalletError::Corrupt(_))));
+    assert!(matches!(unseal(b"{\"private_key\":\"..\"}", KeystoreKind::SingleKey, "correct horse"), Err(WalletError::NotEncrypted)));
+}
+
+#[test]
+fn changing_the_passphrase_keeps_the_key() {
+    let path = wallet_path("change");
+    let wallet = wallet();
+    wallet.save_encrypted(&path, "old passphrase").unwrap();
+
+    // The wrong current passphrase leaves the file as it was
+    let before = fs::read(&path).unwrap();
+    let result = WalletFile::change_passphrase(&path, "not it", "new passphrase");
+    assert!(matches!(result, Err(WalletError::WrongPassphrase)), "{:?}", result);
+    assert_eq!(fs::read(&path).unwrap(), before);
+
+    WalletFile::change_passphrase(&path, "old passphrase", "new passphrase").unwrap();
+    assert!(matches!(Wallet::unlock(&path, "old passphrase"), Err(WalletError::WrongPassphrase)));
+    let unlocked = Wallet::unlock(&path, "new passphrase").unwrap();
+    assert_eq!(unlocked.get_address(), wallet.get_address());
+    assert_eq!(unlocked.private_key.to_bytes(), wallet.private_key.to_bytes());
+
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
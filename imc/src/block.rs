@@ -0,0 +1,151 @@
+use crate::coinbase::Coinbase;
+use crate::difficulty::{hash_meets_target, work_for};
+use crate::merkle::{merkle_root, MerkleProof};
+use bigdecimal::num_bigint::BigUint;
+use crate::transaction::Transaction;
+use log::info;
+use common::encoding::{encode, Decode, DecodeError, Decoder, Encode, Encoder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The part of a block that is hashed and mined. The coinbase and transactions are committed
+/// to through `merkle_root`, so a header alone is enough to check a [`MerkleProof`] against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: u128,
+    pub previous_hash: String,
+    pub merkle_root: String,
+    pub miner: String, // Address credited with the block reward
+    pub bits: u32, // Compact target the block hash must not exceed, see `difficulty`
+    pub nonce: u64,
+}
+
+impl BlockHeader {
+    pub fn calculate_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(encode(self));
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Whether `hash` is at or below this header's target.
+    pub fn meets_target(&self, hash: &str) -> bool {
+        hash_meets_target(hash, self.bits)
+    }
+
+    /// Expected number of hashes needed to find a block at this header's target.
+    pub fn work(&self) -> BigUint {
+        work_for(self.bits)
+    }
+
+    /// Checks that `tx_hash` is one of the transactions committed to by this header.
+    pub fn verify_inclusion(&self, tx_hash: &str, proof: &MerkleProof) -> bool {
+        proof.verify(tx_hash, &self.merkle_root)
+    }
+}
+
+impl Encode for BlockHeader {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_u64(self.index);
+        encoder.put_u128(self.timestamp);
+        encoder.put_str(&self.previous_hash);
+        encoder.put_str(&self.merkle_root);
+        encoder.put_str(&self.miner);
+        encoder.put_u32(self.bits);
+        encoder.put_u64(self.nonce);
+    }
+}
+
+impl Decode for BlockHeader {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(BlockHeader {
+            index: decoder.get_u64()?,
+            timestamp: decoder.get_u128()?,
+            previous_hash: decoder.get_string()?,
+            merkle_root: decoder.get_string()?,
+            miner: decoder.get_string()?,
+            bits: decoder.get_u32()?,
+            nonce: decoder.get_u64()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub hash: String,
+    pub coinbase: Coinbase,
+    pub transactions: Vec<Transaction>,
+}
+
+impl Block {
+    pub fn new(index: u64, timestamp: u128, previous_hash: String, miner: String, coinbase: Coinbase, transactions: Vec<Transaction>, bits: u32) -> Self {
+        let header = BlockHeader {
+            index,
+            timestamp,
+            previous_hash,
+            merkle_root: merkle_root(&Self::leaf_hashes(&coinbase, &transactions)),
+            miner,
+            bits,
+            nonce: 0,
+        };
+        let hash = header.calculate_hash();
+        Block { header, hash, coinbase, transactions }
+    }
+
+    // The coinbase hash followed by the transaction hashes
+    fn leaf_hashes(coinbase: &Coinbase, transactions: &[Transaction]) -> Vec<String> {
+        std::iter::once(coinbase.hash()).chain(transactions.iter().map(Transaction::hash)).collect()
+    }
+
+    pub fn calculate_hash(&self) -> String {
+        self.header.calculate_hash()
+    }
+
+    /// Recomputes the Merkle root from the block body.
+    pub fn calculate_merkle_root(&self) -> String {
+        merkle_root(&Self::leaf_hashes(&self.coinbase, &self.transactions))
+    }
+
+    /// Builds an inclusion proof for the transaction, or coinbase, with hash `tx_hash`, if it
+    /// is in this block.
+    pub fn merkle_proof(&self, tx_hash: &str) -> Option<MerkleProof> {
+        let hashes = Self::leaf_hashes(&self.coinbase, &self.transactions);
+        let index = hashes.iter().position(|hash| hash == tx_hash)?;
+        MerkleProof::generate(&hashes, index)
+    }
+
+    pub fn mine_block(&mut self) {
+        while !self.header.meets_target(&self.hash) {
+            self.header.nonce += 1;
+            self.hash = self.calculate_hash();
+        }
+        info!("Block {} mined with hash: {}", self.header.index, self.hash);
+    }
+}
+
+// The stored hash is kept rather than recomputed on decode so that loading a block can
+// detect that its contents no longer match it.
+impl Encode for Block {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put(&self.header);
+        encoder.put_str(&self.hash);
+        encoder.put(&self.coinbase);
+        encoder.put_seq(&self.transactions);
+    }
+}
+
+impl Decode for Block {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Block {
+            header: decoder.get()?,
+            hash: decoder.get_string()?,
+            coinbase: decoder.get()?,
+            transactions: decoder.get_seq()?,
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use crate::block::{Block, BlockHeader};
+use crate::consensus::HeaderSource;
+use crate::error::ChainError;
+use bigdecimal::num_bigint::BigUint;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A block in the tree together with where it sits.
+#[derive(Debug, Clone)]
+pub struct BlockNode {
+    pub block: Block,
+    pub height: u64,
+    pub chain_work: BigUint, // Total work of this block and all of its ancestors
+}
+
+/// What [`Blockchain::submit_block`](crate::blockchain::Blockchain::submit_block) did with a
+/// valid block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// The block was built on the tip and is the new tip.
+    ExtendedTip,
+    /// The block completed a branch with more work than the active chain, which replaced the
+    /// last `disconnected` blocks of the old chain with `connected` blocks from the branch.
+    Reorganized { disconnected: usize, connected: usize },
+    /// The block was stored but its branch does not have the most work.
+    SideBranch,
+}
+
+/// Every known block that links back to genesis, indexed by hash, whether or not it is on
+/// the active chain. Blocks that failed to connect are remembered so that their descendants
+/// can be refused straight away.
+#[derive(Debug, Clone)]
+pub struct BlockTree {
+    nodes: HashMap<String, BlockNode>,
+    invalid: HashSet<String>,
+}
+
+impl BlockTree {
+    pub fn new(genesis: Block) -> Self {
+        let node = BlockNode { chain_work: genesis.header.work(), block: genesis, height: 0 };
+        let mut nodes = HashMap::new();
+        nodes.insert(node.block.hash.clone(), node);
+        BlockTree { nodes, invalid: HashSet::new() }
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&BlockNode> {
+        self.nodes.get(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    pub fn is_invalid(&self, hash: &str) -> bool {
+        self.invalid.contains(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The ancestor of `hash` (or `hash` itself) at `height`.
+    pub fn ancestor(&self, hash: &str, height: u64) -> Option<&BlockNode> {
+        let mut node = self.nodes.get(hash)?;
+        while node.height > height {
+            node = self.nodes.get(&node.block.header.previous_hash)?;
+        }
+        (node.height == height).then_some(node)
+    }
+
+    /// Blocks nothing has been built on yet, one per branch.
+    pub fn tips(&self) -> Vec<&BlockNode> {
+        let parents: HashSet<&str> = self.nodes.values().map(|node| node.block.header.previous_hash.as_str()).collect();
+        self.nodes.values().filter(|node| !parents.contains(node.block.hash.as_str())).collect()
+    }
+
+    /// Adds a block whose parent is already in the tree.
+    pub(crate) fn insert(&mut self, block: Block) -> Result<&BlockNode, ChainError> {
+        let hash = block.hash.clone();
+        let parent_hash = &block.header.previous_hash;
+        if self.nodes.contains_key(&hash) {
+            return Err(ChainError::DuplicateBlock { hash });
+        }
+        if self.invalid.contains(parent_hash) {
+            return Err(ChainError::InvalidAncestor { hash, ancestor: parent_hash.clone() });
+        }
+        let parent = self.nodes.get(parent_hash).ok_or_else(|| ChainError::UnknownParent { hash: hash.clone(), parent: parent_hash.clone() })?;
+
+        let node = BlockNode {
+            height: parent.height + 1,
+            chain_work: &parent.chain_work + block.header.work(),
+            block,
+        };
+        Ok(self.nodes.entry(hash).or_insert(node))
+    }
+
+    /// Forgets `hash` and every block built on it, and refuses them from now on.
+    pub(crate) fn mark_invalid(&mut self, hash: &str) {
+        let mut pending = vec![hash.to_string()];
+        while let Some(hash) = pending.pop() {
+            pending.extend(
+                self.nodes.values()
+                    .filter(|node| node.block.header.previous_hash == hash)
+                    .map(|node| node.block.hash.clone()),
+            );
+            self.nodes.remove(&hash);
+            self.invalid.insert(hash);
+        }
+    }
+}
+
+impl HeaderSource for BlockTree {
+    fn header(&self, hash: &str) -> Option<&BlockHeader> {
+        self.get(hash).map(|node| &node.block.header)
+    }
+}
+
+/// What connecting a block changed, so that it can be disconnected again during a reorg.
+///
+/// Maps hold the value each touched entry had before the block, `None` if it did not exist.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockUndo {
+    pub balances: HashMap<String, Option<BigDecimal>>,
+    pub nonces: HashMap<String, Option<u64>>,
+    pub miner_contributions: HashMap<String, u64>,
+}
@@ -1,301 +1,647 @@
-use sha3::{Sha3_256, Digest as Sha3Digest};
-use sha2::Sha256;
-use p256::ecdsa::{SigningKey, VerifyingKey};
-use p256::ecdsa::signature::{Signer, Verifier};
-use p256::FieldBytes; 
-use common::wallet::{OptionalSerializableSignature, SerializableSignature};
+use crate::block::Block;
+use crate::block_tree::{BlockStatus, BlockTree, BlockUndo};
+use crate::coinbase::{Coinbase, CoinbaseOutput};
+use crate::consensus::ConsensusRules;
+use crate::emission::COIN_DECIMALS;
+use crate::genesis::GenesisSpec;
+use crate::contract::SmartContract;
+use crate::error::{ChainError, TxError};
+use crate::mempool::Mempool;
+use crate::storage::{invalid_data, ChainState, ChainStore};
+use crate::transaction::{ReplayError, Transaction, TxId};
+use log::{debug, info, warn};
+use p256::FieldBytes;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH, Duration};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::str::FromStr;
 use std::fs::File;
 use std::io::{self, Write, Read};
+use std::path::Path;
+use bigdecimal::num_bigint::BigUint;
+use common::encoding::encode;
 use bigdecimal::{BigDecimal, Zero};
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Transaction {
-    pub sender: String,
-    pub receiver: String,
-    pub amount: BigDecimal,
-    pub fee: BigDecimal,
-    pub signature: OptionalSerializableSignature,
+
+/// Chain identifier signed into every transaction so it cannot be replayed on another network.
+pub const DEFAULT_CHAIN_ID: u32 = 1;
+
+#[derive(Debug)]
+pub struct Blockchain {
+    pub blocks: Vec<Block>,
+    pub mempool: Mempool, // Transactions waiting to be mined
+    pub balances: HashMap<String, BigDecimal>,
+    pub miner_contributions: HashMap<String, u64>,
+    pub liquidity_wallet: String,
+    pub rewards_wallet: String,
+    pub chain_id: u32,
+    pub nonces: HashMap<String, u64>, // Next expected nonce per sender, counting confirmed transactions only
+    pub consensus: ConsensusRules,
+    pub smart_contracts: HashMap<String, SmartContract>,
+    tree: BlockTree, // Every known block, including side branches
+    undo: HashMap<String, BlockUndo>, // How to disconnect each block of `blocks` but genesis
+    store: Option<ChainStore>,
+    unsaved: Vec<String>, // Blocks whose block or undo file has not been written to `store` yet
+    genesis: GenesisSpec, // The spec this chain was created from
 }
 
-impl Transaction {
-    pub fn is_well_formed(&self) -> bool {
-        !self.sender.is_empty() && !self.receiver.is_empty() && self.amount > BigDecimal::zero() && self.fee >= BigDecimal::zero()
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn sign(&mut self, private_key: &SigningKey) {
-        let message = self.hash();
-        self.signature = OptionalSerializableSignature(Some(SerializableSignature(private_key.sign(message.as_bytes()))));
+impl Blockchain {
+    /// Creates a mainnet chain.
+    pub fn new() -> Self {
+        Self::from_genesis(&GenesisSpec::mainnet())
     }
 
-    pub fn verify(&self, public_key: &VerifyingKey) -> Result<bool, p256::ecdsa::Error> {
-        if let Some(signature) = &self.signature.0 {
-            let message = self.hash();
-            public_key.verify(message.as_bytes(), &signature.0).map(|_| true)
-        } else {
-            Ok(false)
-        }
+    /// Creates a mainnet chain whose blocks must follow `consensus` instead.
+    pub fn with_rules(consensus: ConsensusRules) -> Self {
+        Self::from_genesis(&GenesisSpec { consensus, ..GenesisSpec::mainnet() })
     }
 
-    pub fn hash(&self) -> String {
-        let mut hasher = Sha3_256::new();
-        hasher.update(&self.sender);
-        hasher.update(&self.receiver);
-        hasher.update(&self.amount.to_string());
-        hasher.update(&self.fee.to_string());
-        format!("{:x}", hasher.finalize())
+    /// Creates the chain of the network `genesis` describes, holding only its genesis block.
+    /// The genesis block has the initial target of the spec's consensus rules and pays out
+    /// its genesis allocation, so chains with different specs do not share blocks.
+    pub fn from_genesis(genesis: &GenesisSpec) -> Self {
+        let consensus = genesis.consensus.clone();
+        let genesis_coinbase = consensus.emission.genesis_coinbase();
+        let balances = genesis_coinbase.outputs.iter().map(|output| (output.address.clone(), output.amount.clone())).collect();
+
+        let genesis_block = Block::new(0, genesis.timestamp, "0".to_string(), String::new(), genesis_coinbase, vec![], consensus.retarget.initial_bits); // Genesis block is not mined
+
+        let mut blockchain = Blockchain {
+            blocks: vec![],
+            mempool: Mempool::default(),
+            balances,
+            miner_contributions: HashMap::new(),
+            liquidity_wallet: genesis.liquidity_wallet.clone(),
+            rewards_wallet: genesis.rewards_wallet.clone(),
+            chain_id: genesis.chain_id,
+            nonces: HashMap::new(),
+            consensus,
+            smart_contracts: HashMap::new(), // Initialize smart contracts storage
+            tree: BlockTree::new(genesis_block.clone()),
+            undo: HashMap::new(),
+            store: None,
+            unsaved: vec![genesis_block.hash.clone()],
+            genesis: genesis.clone(),
+        };
+        blockchain.blocks.push(genesis_block);
+
+        blockchain
     }
 
-    pub fn validate(&self, blockchain: &Blockchain) -> bool {
-        let sender_balance = blockchain.get_balance(&self.sender);
+    /// Opens the chain of the network `genesis` describes stored in the data directory
+    /// `path`, creating a new chain with a genesis block there if the directory holds none
+    /// yet. Fails if the directory holds a chain with a different genesis block.
+    pub fn open<P: AsRef<Path>>(path: P, genesis: &GenesisSpec) -> io::Result<Self> {
+        let store = ChainStore::open(path)?;
+        let mut blockchain = match store.read_state()? {
+            Some(state) => Self::from_state(&store, state, genesis)?,
+            None => Self::from_genesis(genesis),
+        };
+        blockchain.store = Some(store);
+        blockchain.flush()?;
+        Ok(blockchain)
+    }
 
-        if sender_balance < self.amount.clone() + self.fee.clone() {
-            println!("Transaction from {} to {} is invalid: insufficient balance.", self.sender, self.receiver);
-            return false;
+    fn from_state(store: &ChainStore, state: ChainState, genesis: &GenesisSpec) -> io::Result<Self> {
+        let mut blockchain = Self::from_genesis(genesis);
+        let genesis_hash = blockchain.blocks[0].hash.clone();
+
+        // Parents have lower indices, so inserting in index order always finds them
+        let mut stored = store.read_blocks()?;
+        stored.sort_by_key(|block| block.header.index);
+        for block in stored {
+            if block.hash == genesis_hash {
+                continue;
+            }
+            if block.header.index == 0 {
+                return Err(invalid_data(format!("{} holds a different genesis block", store.root().display())));
+            }
+            let hash = block.hash.clone();
+            if let Err(e) = blockchain.tree.insert(block) {
+                warn!("Ignoring stored block {}: {}", hash, e);
+            }
         }
 
-        self.is_well_formed()
+        let mut chain = Vec::new();
+        let mut hash = state.tip_hash.clone();
+        loop {
+            let node = blockchain.tree.get(&hash).ok_or_else(|| invalid_data(format!("stored chain is missing block {}", hash)))?;
+            chain.push(node.block.clone());
+            if node.height == 0 {
+                break;
+            }
+            hash = node.block.header.previous_hash.clone();
+        }
+        chain.reverse();
+
+        for block in &chain[1..] {
+            let undo = store.read_undo(&block.hash)?.ok_or_else(|| invalid_data(format!("undo record for block {} is missing", block.hash)))?;
+            blockchain.undo.insert(block.hash.clone(), undo);
+        }
+
+        blockchain.blocks = chain;
+        blockchain.unsaved.clear();
+        blockchain.balances = state.balances;
+        blockchain.miner_contributions = state.miner_contributions;
+        blockchain.nonces = state.nonces;
+        blockchain.smart_contracts = state.smart_contracts;
+        blockchain.return_to_pending(state.pending_transactions);
+        Ok(blockchain)
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SmartContract {
-    pub id: String,
-    pub creator: String,
-    pub code: String, // The code of the smart contract
-    pub state: HashMap<String, String>, // State variables of the contract
-}
+    /// Writes new blocks and the current state to the data directory this chain was opened
+    /// from. Does nothing for a chain created with [`Blockchain::new`].
+    pub fn flush(&mut self) -> io::Result<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
 
-impl SmartContract {
-    pub fn new(id: String, creator: String, code: String) -> Self {
-        SmartContract {
-            id,
-            creator,
-            code,
-            state: HashMap::new(),
-        }
-    }
-
-    pub fn execute(&mut self, function: &str, params: HashMap<String, String>) -> Result<String, String> {
-        // Placeholder for actual execution logic
-        match function {
-            "set" => {
-                if let Some(key) = params.get("key") {
-                    if let Some(value) = params.get("value") {
-                        self.state.insert(key.clone(), value.clone());
-                        return Ok(format!("Set state '{}' to '{}'", key, value));
-                    }
-                }
-                Err("Invalid parameters for 'set' function".to_string())
+        for hash in &self.unsaved {
+            // Blocks that turned out to be invalid have been dropped from the tree since
+            if let Some(node) = self.tree.get(hash) {
+                store.write_block(&node.block)?;
             }
-            "get" => {
-                if let Some(key) = params.get("key") {
-                    if let Some(value) = self.state.get(key) {
-                        return Ok(format!("State value for key '{}': '{}'", key, value));
-                    } else {
-                        return Err(format!("Key '{}' not found in state", key));
-                    }
-                }
-                Err("Invalid parameters for 'get' function".to_string())
+            if let Some(undo) = self.undo.get(hash) {
+                store.write_undo(hash, undo)?;
             }
-            _ => Err(format!("Function '{}' not recognized", function)),
         }
+        store.write_state(&self.to_state())?;
+
+        self.unsaved.clear();
+        Ok(())
     }
-}
 
-#[derive(Debug)]
-pub struct Blockchain {
-    pub blocks: Vec<Block>,
-    pub pending_transactions: Vec<Transaction>,
-    pub mining_reward: BigDecimal,
-    pub balances: HashMap<String, BigDecimal>,
-    pub miner_contributions: HashMap<String, u64>,
-    pub liquidity_wallet: String,
-    pub rewards_wallet: String,
-    pub public_keys: HashMap<String, VerifyingKey>,
-    pub target_block_time: Duration,
-    pub difficulty: usize,
-    pub smart_contracts: HashMap<String, SmartContract>,
-}
+    fn to_state(&self) -> ChainState {
+        let tip = self.blocks.last().unwrap();
+        ChainState {
+            height: tip.header.index,
+            tip_hash: tip.hash.clone(),
+            balances: self.balances.clone(),
+            miner_contributions: self.miner_contributions.clone(),
+            nonces: self.nonces.clone(),
+            smart_contracts: self.smart_contracts.clone(),
+            pending_transactions: self.mempool.transactions(),
+        }
+    }
 
-impl Blockchain {
-    pub fn new() -> Self {
-        let target_block_time = Duration::from_secs(450); // 7.5 minutes
-        let initial_difficulty = 5; // Set a higher initial difficulty
+    /// The nonce the sender's next transaction must use, counting pending transactions.
+    pub fn next_nonce(&self, sender: &str) -> u64 {
+        let confirmed = self.nonces.get(sender).copied().unwrap_or(0);
+        self.mempool.next_nonce(sender).map_or(confirmed, |pending| pending.max(confirmed))
+    }
 
-        let mut balances = HashMap::new();
-        balances.insert("System".to_string(), BigDecimal::from_str("1000000000000").unwrap());
-        balances.insert("Alice".to_string(), BigDecimal::from_str("1000").unwrap());
+    /// Checks that a new transaction is for this chain and carries the sender's next nonce,
+    /// or the nonce of a different pending transaction it is meant to replace.
+    pub fn check_replay(&self, transaction: &Transaction) -> Result<(), ReplayError> {
+        if transaction.chain_id != self.chain_id {
+            return Err(ReplayError::WrongChain { expected: self.chain_id, found: transaction.chain_id });
+        }
 
-        let mut blockchain = Blockchain {
-            blocks: vec![],
-            pending_transactions: vec![],
-            mining_reward: BigDecimal::from_str("40.0").unwrap(),
-            balances,
-            miner_contributions: HashMap::new(),
-            liquidity_wallet: "LiquidityWallet".to_string(),
-            rewards_wallet: "RewardsWallet".to_string(),
-            public_keys: HashMap::new(),
-            target_block_time,
-            difficulty: initial_difficulty,
-            smart_contracts: HashMap::new(), // Initialize smart contracts storage
-        };
+        let confirmed = self.nonces.get(&transaction.sender).copied().unwrap_or(0);
+        if transaction.nonce < confirmed {
+            return Err(ReplayError::StaleNonce { expected: confirmed, found: transaction.nonce });
+        }
+        if let Some(pending) = self.mempool.get_by_nonce(&transaction.sender, transaction.nonce) {
+            if pending.tx_id == transaction.hash() {
+                return Err(ReplayError::DuplicateNonce { nonce: transaction.nonce });
+            }
+            // A replacement, which the mempool accepts only for a higher fee
+            return Ok(());
+        }
+        let expected = self.next_nonce(&transaction.sender);
+        if transaction.nonce != expected {
+            return Err(ReplayError::NonceGap { expected, found: transaction.nonce });
+        }
+        Ok(())
+    }
 
-        let mut genesis_block = Block::new(0, 0, "0".to_string(), "Genesis Block".to_string(), 0);
-        genesis_block.hash = genesis_block.calculate_hash(); // Calculate hash for genesis block without mining
-        blockchain.blocks.push(genesis_block);
+    /// Checks a new transaction against the chain and adds it to the mempool.
+    pub fn create_transaction(&mut self, transaction: Transaction) -> Result<TxId, TxError> {
+        self.expire_pending();
+        let tx_id = transaction.hash();
+        let (sender, receiver) = (transaction.sender.clone(), transaction.receiver.clone());
+        let result = self.check_new_transaction(&transaction).and_then(|()| self.mempool.insert(transaction, now()));
+        match result {
+            Ok(replaced) => {
+                if let Some(replaced) = replaced {
+                    info!("Transaction {} replaces pending transaction {}", tx_id, replaced.hash());
+                }
+                info!("Transaction {} from {} to {} added to the mempool", tx_id, sender, receiver);
+                Ok(tx_id)
+            }
+            Err(e) => {
+                warn!("Transaction from {} to {} is rejected: {}", sender, receiver, e);
+                Err(e)
+            }
+        }
+    }
 
-        blockchain
+    // Pending transactions are checked against the balance left after the sender's other
+    // pending transactions, so a sender cannot queue more than they have. A replacement
+    // frees what the transaction it replaces would have spent.
+    fn check_new_transaction(&self, transaction: &Transaction) -> Result<(), TxError> {
+        transaction.check_addresses()?;
+        transaction.verify_sender()?;
+        self.check_replay(transaction)?;
+        let replaced = self.mempool.get_by_nonce(&transaction.sender, transaction.nonce)
+            .map_or_else(BigDecimal::zero, |entry| &entry.transaction.amount + &entry.transaction.fee);
+        transaction.check_funds(self.spendable_balance(&transaction.sender) + replaced)
     }
 
-    pub fn create_transaction(&mut self, transaction: Transaction) {
-        if transaction.validate(self) {
-            println!("Transaction from {} to {} is valid and added to pending transactions.", transaction.sender, transaction.receiver);
-            self.pending_transactions.push(transaction);
-        } else {
-            println!("Transaction from {} to {} is invalid and not added to pending transactions.", transaction.sender, transaction.receiver);
+    /// Confirmed balance of `address` less what its pending transactions spend.
+    pub fn spendable_balance(&self, address: &str) -> BigDecimal {
+        self.get_balance(address) - self.mempool.pending_spend(address)
+    }
+
+    fn expire_pending(&mut self) {
+        for transaction in self.mempool.expire(now()) {
+            debug!("Transaction {} expired from the mempool", transaction.hash());
         }
     }
 
-    pub fn mine_pending_transactions(&mut self, miner_address: String) {
-        println!("Mining transactions by {}", miner_address);
+    /// Mines a block on the tip, filled from the mempool by fee rate, and returns its hash.
+    pub fn mine_pending_transactions(&mut self, miner_address: String) -> Result<String, ChainError> {
+        info!("Mining transactions by {}", miner_address);
+        self.expire_pending();
+        let mut block = self.block_template(miner_address);
+        block.mine_block();
+        let hash = block.hash.clone();
+        self.submit_block(block)?;
+        Ok(hash)
+    }
+
+    /// An unmined block on the tip paying the block reward with `miner_address` as the
+    /// winner, holding as many pending transactions as fit within the block size limit.
+    pub fn block_template(&self, miner_address: String) -> Block {
         let previous_block = self.blocks.last().unwrap();
-        let start_time = SystemTime::now();
-        
-        let mut block = Block::new(
-            previous_block.index + 1,
-            start_time.duration_since(UNIX_EPOCH).unwrap().as_millis(),
-            previous_block.hash.clone(),
-            serde_json::to_string(&self.pending_transactions).unwrap(),
-            0,
-        );
-
-        block.mine_block(self.difficulty);
-        let end_time = SystemTime::now();
-        self.blocks.push(block);
+        let (index, timestamp, bits) = (previous_block.header.index + 1, self.next_timestamp(), self.next_bits());
+        let coinbase = self.coinbase_for(&miner_address);
+        let empty = Block::new(index, timestamp, previous_block.hash.clone(), miner_address.clone(), coinbase.clone(), vec![], bits);
+        let room = self.consensus.max_block_size.saturating_sub(encode(&empty).len());
+        let transactions = self.mempool.block_template(self, room);
+        Block::new(index, timestamp, previous_block.hash.clone(), miner_address, coinbase, transactions, bits)
+    }
 
-        self.adjust_difficulty(start_time, end_time);
+    /// The coinbase the next block on the tip must carry if `winner` mines it.
+    ///
+    /// The block reward comes from the emission schedule. The winner is paid 30% of it. The
+    /// other 70% is shared by every miner, the winner included, in proportion to their
+    /// contributions: blocks mined since rewards were last paid, counting this one. Shares
+    /// are rounded down to whole units and the winner gets what rounding leaves over.
+    pub fn coinbase_for(&self, winner: &str) -> Coinbase {
+        let height = self.blocks.len() as u64;
+        let reward = self.consensus.emission.reward_at(height);
+
+        let mut contributions: Vec<(&str, u64)> = self.miner_contributions.iter().map(|(miner, count)| (miner.as_str(), *count)).collect();
+        match contributions.iter_mut().find(|(miner, _)| *miner == winner) {
+            Some((_, count)) => *count += 1,
+            None => contributions.push((winner, 1)),
+        }
+        contributions.sort();
+        let total_contributions: u64 = contributions.iter().map(|(_, count)| count).sum();
+
+        let remaining_reward = &reward * BigDecimal::from_str("0.7").unwrap();
+        let shares: Vec<CoinbaseOutput> = contributions.into_iter()
+            .map(|(miner, contribution)| {
+                let amount = &remaining_reward * BigDecimal::from(contribution as i64) / BigDecimal::from(total_contributions as i64);
+                CoinbaseOutput { address: miner.to_string(), amount: amount.with_scale(COIN_DECIMALS) }
+            })
+            .collect();
+        let winner_reward = reward - shares.iter().map(|share| &share.amount).sum::<BigDecimal>();
+
+        let mut outputs = vec![CoinbaseOutput { address: winner.to_string(), amount: winner_reward }];
+        outputs.extend(shares);
+        Coinbase { outputs }
+    }
 
-        for transaction in &self.pending_transactions {
-            let sender_balance = self.balances.entry(transaction.sender.clone()).or_insert(BigDecimal::zero());
-            *sender_balance -= &transaction.amount + &transaction.fee;
+    /// Coins in existence on the active chain.
+    pub fn total_supply(&self) -> BigDecimal {
+        self.consensus.emission.total_supply_at(self.blocks.len() as u64 - 1)
+    }
 
-            let receiver_balance = self.balances.entry(transaction.receiver.clone()).or_insert(BigDecimal::zero());
-            *receiver_balance += &transaction.amount;
+    /// Accepts a block from any source, such as a peer or a local miner.
+    ///
+    /// The block is added to the block tree if its parent is known and its header is valid.
+    /// If that gives its branch more cumulative work than the active chain, the chain switches
+    /// to it: blocks past the fork point are disconnected and the branch is connected in
+    /// their place, with every transaction checked on the way. Transactions from disconnected
+    /// blocks that the new chain does not include go back to the pending transactions.
+    pub fn submit_block(&mut self, block: Block) -> Result<BlockStatus, ChainError> {
+        self.check_block(&block)?;
+        let hash = block.hash.clone();
+        let chain_work = self.tree.insert(block)?.chain_work.clone();
+        self.unsaved.push(hash.clone());
+
+        if chain_work <= self.chain_work() {
+            info!("Block {} stored on a side branch", hash);
+            return Ok(BlockStatus::SideBranch);
+        }
 
-            let liquidity_fee = &transaction.fee / BigDecimal::from(2);
-            let rewards_fee = &transaction.fee / BigDecimal::from(2);
+        let node = self.tree.get(&hash).unwrap();
+        let (status, orphaned) = if node.block.header.previous_hash == self.blocks.last().unwrap().hash {
+            let block = node.block.clone();
+            if let Err(e) = self.connect_tip(block) {
+                self.tree.mark_invalid(&hash);
+                return Err(e);
+            }
+            (BlockStatus::ExtendedTip, vec![])
+        } else {
+            self.reorganize(&hash)?
+        };
 
-            let liquidity_balance = self.balances.entry(self.liquidity_wallet.clone()).or_insert(BigDecimal::zero());
-            *liquidity_balance += liquidity_fee;
+        self.return_to_pending(orphaned);
+        Ok(status)
+    }
 
-            let rewards_balance = self.balances.entry(self.rewards_wallet.clone()).or_insert(BigDecimal::zero());
-            *rewards_balance += rewards_fee;
-        }
+    /// Total work of the active chain.
+    pub fn chain_work(&self) -> BigUint {
+        self.tree.get(&self.blocks.last().unwrap().hash).map_or_else(BigUint::default, |node| node.chain_work.clone())
+    }
 
-        let miner_contribution = self.miner_contributions.entry(miner_address.clone()).or_insert(0);
-        *miner_contribution += 1;
+    /// Target the next block on the tip must meet.
+    pub fn next_bits(&self) -> u32 {
+        self.expected_bits(&self.blocks.last().unwrap().hash).expect("the tip is in the block tree")
+    }
+
+    /// Target a child of `parent_hash` must meet, or `None` if that block is unknown. See
+    /// [`ConsensusRules::expected_bits`].
+    pub fn expected_bits(&self, parent_hash: &str) -> Option<u32> {
+        let parent = self.tree.get(parent_hash)?;
+        self.consensus.expected_bits(&parent.block.header, &self.tree)
+    }
 
-        self.distribute_rewards(miner_address);
+    /// Median timestamp of `parent_hash` and its ancestors, up to
+    /// [`ConsensusRules::median_time_span`] blocks, or `None` if that block is unknown.
+    pub fn median_time_past(&self, parent_hash: &str) -> Option<u128> {
+        let parent = self.tree.get(parent_hash)?;
+        self.consensus.median_time_past(&parent.block.header, &self.tree)
+    }
+
+    /// Timestamp for the next block on the tip: the current time, or the earliest time the
+    /// timestamp rule allows if the clock is behind the chain.
+    pub fn next_timestamp(&self) -> u128 {
+        let median_time_past = self.median_time_past(&self.blocks.last().unwrap().hash).expect("the tip is in the block tree");
+        now().max(median_time_past + 1)
+    }
 
-        self.pending_transactions.clear();
+    /// The spec this chain was created from.
+    pub fn genesis(&self) -> &GenesisSpec {
+        &self.genesis
     }
 
-    fn adjust_difficulty(&mut self, start_time: SystemTime, end_time: SystemTime) {
-        let block_time = end_time.duration_since(start_time).unwrap();
-        let adjustment_factor = 0.015; // Adjust this factor to 1.5%
-    
-        if block_time < self.target_block_time {
-            // Increase difficulty if block time is less than target
-            self.difficulty = (self.difficulty as f64 * (1.0 + adjustment_factor)).ceil() as usize;
-        } else if block_time > self.target_block_time {
-            // Decrease difficulty if block time is greater than target
-            self.difficulty = (self.difficulty as f64 * (1.0 - adjustment_factor)).max(1.0) as usize;
+    pub fn block_tree(&self) -> &BlockTree {
+        &self.tree
+    }
+
+    // Checks the consensus rules for a block, everything that can be checked without
+    // connecting it
+    fn check_block(&self, block: &Block) -> Result<(), ChainError> {
+        self.consensus.check_header(&block.header, &block.hash, &self.tree, now())?;
+        if block.header.merkle_root != block.calculate_merkle_root() {
+            return Err(ChainError::InvalidMerkleRoot { index: block.header.index });
         }
-    
-        println!("Adjusted difficulty to: {}", self.difficulty);
+        self.consensus.check_size(block)
     }
 
-    pub fn distribute_rewards(&mut self, winner: String) {
-        let total_contributions: u64 = self.miner_contributions.values().sum();
-        if total_contributions == 0 {
-            return;
+    fn is_active(&self, hash: &str) -> bool {
+        self.tree.get(hash).is_some_and(|node| self.blocks.get(node.height as usize).is_some_and(|block| block.hash == hash))
+    }
+
+    // Switches the active chain to the branch ending in `new_tip`. If a block of the branch
+    // fails to connect, it is marked invalid and the old chain is restored.
+    fn reorganize(&mut self, new_tip: &str) -> Result<(BlockStatus, Vec<Transaction>), ChainError> {
+        let mut branch = Vec::new();
+        let mut cursor = new_tip.to_string();
+        while !self.is_active(&cursor) {
+            let node = self.tree.get(&cursor).expect("every block in the tree links back to genesis");
+            branch.push(node.block.clone());
+            cursor = node.block.header.previous_hash.clone();
         }
+        branch.reverse();
+        let fork_height = self.tree.get(&cursor).unwrap().height as usize;
 
-        // Winner gets 30% of the mining reward
-        let winner_reward = &self.mining_reward * BigDecimal::from_str("0.3").unwrap();
-        let winner_balance = self.balances.entry(winner.clone()).or_insert(BigDecimal::zero());
-        *winner_balance += winner_reward;
+        let mut disconnected = Vec::new();
+        while self.blocks.len() > fork_height + 1 {
+            disconnected.push(self.disconnect_tip());
+        }
+        disconnected.reverse();
+
+        for (connected, block) in branch.iter().enumerate() {
+            if let Err(e) = self.connect_tip(block.clone()) {
+                warn!("Reorganization to {} failed at block {}: {}", new_tip, block.hash, e);
+                self.tree.mark_invalid(&block.hash);
+                for _ in 0..connected {
+                    self.disconnect_tip();
+                }
+                for block in disconnected {
+                    self.connect_tip(block).expect("blocks of the previous chain connect again");
+                }
+                return Err(e);
+            }
+        }
+
+        info!("Reorganized at height {}: {} blocks disconnected, {} connected", fork_height, disconnected.len(), branch.len());
+        let status = BlockStatus::Reorganized { disconnected: disconnected.len(), connected: branch.len() };
+        let orphaned = disconnected.into_iter().flat_map(|block| block.transactions).collect();
+        Ok((status, orphaned))
+    }
 
-        // Everyone, including the winner, gets a share of the remaining 70% based on their contributions
-        let remaining_reward = &self.mining_reward * BigDecimal::from_str("0.7").unwrap();
-        for (miner, contribution) in &self.miner_contributions {
-            let reward = &remaining_reward * BigDecimal::from(*contribution as i64) / BigDecimal::from(total_contributions as i64);
-            let miner_balance = self.balances.entry(miner.clone()).or_insert(BigDecimal::zero());
-            *miner_balance += reward;
+    // Applies a block on top of the active chain, checking its coinbase against the reward
+    // schedule and each transaction against the state left by the ones before it
+    fn connect_tip(&mut self, block: Block) -> Result<(), ChainError> {
+        let allowed = self.consensus.emission.reward_at(block.header.index);
+        let found = block.coinbase.total();
+        if found > allowed {
+            return Err(ChainError::ExcessiveMint { index: block.header.index, allowed, found });
         }
+        if block.coinbase != self.coinbase_for(&block.header.miner) {
+            return Err(ChainError::InvalidCoinbase { index: block.header.index });
+        }
+        let mut undo = BlockUndo { miner_contributions: self.miner_contributions.clone(), ..BlockUndo::default() };
 
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let expected = self.nonces.get(&transaction.sender).copied().unwrap_or(0);
+            if let Err(error) = self.check_confirmed_transaction(transaction, expected, self.get_balance(&transaction.sender)) {
+                self.roll_back(undo);
+                return Err(ChainError::InvalidTransaction { index: block.header.index, position, tx_id: transaction.hash(), error: Box::new(error) });
+            }
+            self.apply_transaction(transaction, &mut undo);
+        }
+
+        // Paying the reward settles every contribution so far
+        for output in &block.coinbase.outputs {
+            self.adjust_balance(&mut undo, &output.address, output.amount.clone());
+        }
         self.miner_contributions.clear();
+
+        self.undo.insert(block.hash.clone(), undo);
+        self.unsaved.push(block.hash.clone());
+        self.blocks.push(block);
+        Ok(())
+    }
+
+    fn disconnect_tip(&mut self) -> Block {
+        let block = self.blocks.pop().expect("genesis is never disconnected");
+        let undo = self.undo.remove(&block.hash).expect("every connected block has an undo record");
+        self.roll_back(undo);
+        block
+    }
+
+    fn roll_back(&mut self, undo: BlockUndo) {
+        for (address, balance) in undo.balances {
+            match balance {
+                Some(balance) => self.balances.insert(address, balance),
+                None => self.balances.remove(&address),
+            };
+        }
+        for (address, nonce) in undo.nonces {
+            match nonce {
+                Some(nonce) => self.nonces.insert(address, nonce),
+                None => self.nonces.remove(&address),
+            };
+        }
+        self.miner_contributions = undo.miner_contributions;
+    }
+
+    fn apply_transaction(&mut self, transaction: &Transaction, undo: &mut BlockUndo) {
+        self.adjust_balance(undo, &transaction.sender, -(&transaction.amount + &transaction.fee));
+        self.adjust_balance(undo, &transaction.receiver, transaction.amount.clone());
+
+        let liquidity_fee = &transaction.fee / BigDecimal::from(2);
+        let rewards_fee = &transaction.fee / BigDecimal::from(2);
+        let (liquidity_wallet, rewards_wallet) = (self.liquidity_wallet.clone(), self.rewards_wallet.clone());
+        self.adjust_balance(undo, &liquidity_wallet, liquidity_fee);
+        self.adjust_balance(undo, &rewards_wallet, rewards_fee);
+
+        undo.nonces.entry(transaction.sender.clone()).or_insert_with(|| self.nonces.get(&transaction.sender).copied());
+        self.nonces.insert(transaction.sender.clone(), transaction.nonce + 1);
+    }
+
+    // Changes a balance, remembering in `undo` what it was before the block
+    fn adjust_balance(&mut self, undo: &mut BlockUndo, address: &str, amount: BigDecimal) {
+        undo.balances.entry(address.to_string()).or_insert_with(|| self.balances.get(address).cloned());
+        *self.balances.entry(address.to_string()).or_insert_with(BigDecimal::zero) += amount;
+    }
+
+    // Puts transactions back into the mempool and drops any pending transaction that is no
+    // longer valid on the current chain or has expired
+    fn return_to_pending(&mut self, orphaned: Vec<Transaction>) {
+        self.expire_pending();
+        let now = now();
+        let candidates: Vec<(Transaction, u128)> = orphaned.into_iter()
+            .map(|transaction| (transaction, now))
+            .chain(self.mempool.drain().into_iter().map(|entry| (entry.transaction, entry.added)))
+            .collect();
+        for (transaction, added) in candidates {
+            let result = self.check_new_transaction(&transaction).and_then(|()| self.mempool.insert(transaction.clone(), added));
+            if let Err(e) = result {
+                debug!("Dropping transaction {} from the mempool: {}", transaction.hash(), e);
+            }
+        }
     }
 
     pub fn get_balance(&self, address: &str) -> BigDecimal {
         self.balances.get(address).cloned().unwrap_or(BigDecimal::zero())
     }
 
-    pub fn is_valid(&self) -> bool {
+    /// Whether `address` has ever held funds or sent or received a transaction.
+    pub fn is_address_used(&self, address: &str) -> bool {
+        self.balances.contains_key(address)
+            || self.nonces.contains_key(address)
+            || self.blocks.iter()
+                .flat_map(|block| &block.transactions)
+                .chain(self.mempool.for_address(address))
+                .any(|tx| tx.sender == address || tx.receiver == address)
+    }
+
+    /// Checks every block against the consensus rules and its link to the block before it,
+    /// then replays the chain from the genesis state, checking every transaction against the
+    /// balances and nonces at that point and paying fees and rewards as it goes. The replayed
+    /// state must match the live one. Returns the first problem found.
+    pub fn is_valid(&self) -> Result<(), ChainError> {
+        let mut replay = self.genesis_replica();
         for i in 1..self.blocks.len() {
             let current_block = &self.blocks[i];
             let previous_block = &self.blocks[i - 1];
+            let index = current_block.header.index;
 
-            if current_block.hash != current_block.calculate_hash() {
-                return false;
+            if index != i as u64 {
+                return Err(ChainError::InvalidIndex { index, expected: i as u64 });
             }
 
-            if current_block.previous_hash != previous_block.hash {
-                return false;
-            }
+            self.check_block(current_block)?;
 
-            let transactions: Vec<Transaction> = match serde_json::from_str(&current_block.data) {
-                Ok(txs) => txs,
-                Err(_) => return false,
-            };
-            for transaction in &transactions {
-                let public_key = match self.get_public_key(&transaction.sender) {
-                    Ok(key) => key,
-                    Err(e) => {
-                        println!("Failed to get public key for {}: {:?}", transaction.sender, e);
-                        return false;
-                    }
-                };
-                match transaction.verify(&public_key) {
-                    Ok(true) => (),
-                    Ok(false) => {
-                        println!("Transaction verification failed for transaction from {} to {}", transaction.sender, transaction.receiver);
-                        return false;
-                    }
-                    Err(e) => {
-                        println!("Transaction verification error for transaction from {} to {}: {:?}", transaction.sender, transaction.receiver, e);
-                        return false;
-                    }
-                }
-                if !transaction.validate(self) {
-                    println!("Transaction validation failed for transaction from {} to {}", transaction.sender, transaction.receiver);
-                    return false;
-                }
+            if current_block.header.previous_hash != previous_block.hash {
+                return Err(ChainError::BrokenLink { index });
             }
+
+            replay.connect_tip(current_block.clone())?;
         }
-        true
+        replay.check_same_state(self)
+    }
+
+    // A chain with the same parameters as this one, holding only the genesis block and state
+    fn genesis_replica(&self) -> Blockchain {
+        Blockchain::from_genesis(&GenesisSpec {
+            chain_id: self.chain_id,
+            liquidity_wallet: self.liquidity_wallet.clone(),
+            rewards_wallet: self.rewards_wallet.clone(),
+            consensus: self.consensus.clone(),
+            ..self.genesis.clone()
+        })
     }
 
-    fn get_public_key(&self, address: &str) -> Result<VerifyingKey, p256::ecdsa::Error> {
-        self.public_keys.get(address).cloned().ok_or_else(|| p256::ecdsa::Error::new())
+    // Compares this chain's balances, nonces and miner contributions with `live`'s. Missing
+    // entries count as zero.
+    fn check_same_state(&self, live: &Blockchain) -> Result<(), ChainError> {
+        let mismatch = |field, key: &String, replayed: &dyn ToString, live: &dyn ToString| ChainError::StateMismatch {
+            field,
+            key: key.clone(),
+            replayed: replayed.to_string(),
+            live: live.to_string(),
+        };
+
+        for address in self.balances.keys().chain(live.balances.keys()) {
+            let (replayed, live) = (self.get_balance(address), live.get_balance(address));
+            if replayed != live {
+                return Err(mismatch("balance", address, &replayed, &live));
+            }
+        }
+        for address in self.nonces.keys().chain(live.nonces.keys()) {
+            let (replayed, live) = (self.nonces.get(address).copied().unwrap_or(0), live.nonces.get(address).copied().unwrap_or(0));
+            if replayed != live {
+                return Err(mismatch("nonce", address, &replayed, &live));
+            }
+        }
+        for miner in self.miner_contributions.keys().chain(live.miner_contributions.keys()) {
+            let replayed = self.miner_contributions.get(miner).copied().unwrap_or(0);
+            let live = live.miner_contributions.get(miner).copied().unwrap_or(0);
+            if replayed != live {
+                return Err(mismatch("miner contribution", miner, &replayed, &live));
+            }
+        }
+        Ok(())
     }
 
-    pub fn store_public_key(&mut self, address: &str, public_key: VerifyingKey) {
-        self.public_keys.insert(address.to_string(), public_key);
+    // Checks a transaction in a block, which must carry the nonce `expected` and be covered
+    // by `balance`
+    pub(crate) fn check_confirmed_transaction(&self, transaction: &Transaction, expected: u64, balance: BigDecimal) -> Result<(), TxError> {
+        if transaction.chain_id != self.chain_id {
+            return Err(ReplayError::WrongChain { expected: self.chain_id, found: transaction.chain_id }.into());
+        }
+        if transaction.nonce < expected {
+            return Err(ReplayError::StaleNonce { expected, found: transaction.nonce }.into());
+        }
+        if transaction.nonce > expected {
+            return Err(ReplayError::NonceGap { expected, found: transaction.nonce }.into());
+        }
+        transaction.verify_sender()?;
+        transaction.check_funds(balance)
     }
 
     pub fn save_key_to_file(key_path: &str, key_data: &[u8]) -> io::Result<()> {
@@ -330,47 +676,7 @@ impl Blockchain {
     }
 }
 
-// Dummy Block struct for demonstration purposes
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Block {
-    pub index: u64,
-    pub timestamp: u128,
-    pub previous_hash: String,
-    pub hash: String,
-    pub data: String,
-    pub nonce: u64,
+// Milliseconds since the Unix epoch
+pub(crate) fn now() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
 }
-
-impl Block {
-    pub fn new(index: u64, timestamp: u128, previous_hash: String, data: String, nonce: u64) -> Self {
-        let mut block = Block {
-            index,
-            timestamp,
-            previous_hash,
-            hash: String::new(),
-            data,
-            nonce,
-        };
-        block.hash = block.calculate_hash();
-        block
-    }
-
-    pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(self.index.to_string());
-        hasher.update(self.timestamp.to_string());
-        hasher.update(&self.previous_hash);
-        hasher.update(&self.data);
-        hasher.update(self.nonce.to_string());
-        format!("{:x}", hasher.finalize())
-    }
-
-    pub fn mine_block(&mut self, difficulty: usize) {
-        let target = "0".repeat(difficulty);
-        while &self.hash[..difficulty] != target {
-            self.nonce += 1;
-            self.hash = self.calculate_hash();
-        }
-        println!("Block mined with hash: {}", self.hash);
-    }
-}
\ No newline at end of file
@@ -0,0 +1,63 @@
+use bigdecimal::BigDecimal;
+use common::encoding::{encode, Decode, DecodeError, Decoder, Encode, Encoder};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+/// A payment of newly created coins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinbaseOutput {
+    pub address: String,
+    pub amount: BigDecimal,
+}
+
+/// The block reward as paid out by a block: the winner's share first, then each
+/// contributor's share ordered by address. Committed to as the first leaf of the block's
+/// Merkle tree, so the payout is part of what is mined.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coinbase {
+    pub outputs: Vec<CoinbaseOutput>,
+}
+
+impl Coinbase {
+    /// Hex SHA3-256 hash of the canonical encoding, used as the coinbase's Merkle leaf.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha3_256::new();
+        hasher.update(encode(self));
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Sum of every output.
+    pub fn total(&self) -> BigDecimal {
+        self.outputs.iter().map(|output| &output.amount).sum()
+    }
+}
+
+impl Encode for CoinbaseOutput {
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.address);
+        encoder.put_decimal(&self.amount);
+    }
+}
+
+impl Decode for CoinbaseOutput {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(CoinbaseOutput {
+            address: decoder.get_string()?,
+            amount: decoder.get_decimal()?,
+        })
+    }
+}
+
+impl Encode for Coinbase {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_seq(&self.outputs);
+    }
+}
+
+impl Decode for Coinbase {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Coinbase { outputs: decoder.get_seq()? })
+    }
+}
@@ -0,0 +1,148 @@
+//! Rules every block must follow to be part of the chain.
+//!
+//! [`Blockchain`](crate::blockchain::Blockchain) checks them when a block is submitted and
+//! again for every block in [`Blockchain::is_valid`](crate::blockchain::Blockchain::is_valid),
+//! so nodes that disagree on any of these parameters are on different networks.
+
+use crate::block::{Block, BlockHeader};
+use crate::difficulty::{retarget, RetargetParams};
+use crate::emission::EmissionSchedule;
+use crate::error::ChainError;
+use common::encoding::encode;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusRules {
+    pub retarget: RetargetParams,
+    pub emission: EmissionSchedule,
+    pub median_time_span: usize, // A block's timestamp must be later than the median of this many ancestors
+    #[serde(with = "duration_secs")]
+    pub max_future_drift: Duration, // How far past the local clock a block's timestamp may be
+    pub max_block_size: usize, // Largest allowed canonical encoding of a block, in bytes
+}
+
+impl Default for ConsensusRules {
+    fn default() -> Self {
+        ConsensusRules {
+            retarget: RetargetParams::default(),
+            emission: EmissionSchedule::default(),
+            median_time_span: 11,
+            max_future_drift: Duration::from_secs(2 * 60 * 60),
+            max_block_size: 1_000_000,
+        }
+    }
+}
+
+// Durations are written as whole seconds, which reads better in a genesis spec file than
+// serde's default `{ "secs", "nanos" }`
+pub(crate) mod duration_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// Headers of known blocks by hash, for the rules that look at a block's ancestors.
+pub trait HeaderSource {
+    fn header(&self, hash: &str) -> Option<&BlockHeader>;
+}
+
+impl ConsensusRules {
+    /// Checks everything about a block that its header and ancestors decide: that it follows
+    /// its parent, that `hash` is its hash, that it uses the target the retarget rule gives and
+    /// meets it, and that its timestamp is in range. The body is not needed, so this is also
+    /// how headers are checked before their blocks are downloaded.
+    pub fn check_header<H: HeaderSource + ?Sized>(&self, header: &BlockHeader, hash: &str, headers: &H, now: u128) -> Result<(), ChainError> {
+        let index = header.index;
+        let parent = headers.header(&header.previous_hash)
+            .ok_or_else(|| ChainError::UnknownParent { hash: hash.to_string(), parent: header.previous_hash.clone() })?;
+        if index != parent.index + 1 {
+            return Err(ChainError::InvalidIndex { index, expected: parent.index + 1 });
+        }
+        if hash != header.calculate_hash() {
+            return Err(ChainError::InvalidHash { index });
+        }
+        if let Some(expected) = self.expected_bits(parent, headers) {
+            if header.bits != expected {
+                return Err(ChainError::WrongTarget { index, expected, found: header.bits });
+            }
+        }
+        if !header.meets_target(hash) {
+            return Err(ChainError::InsufficientProofOfWork { index });
+        }
+        if let Some(median_time_past) = self.median_time_past(parent, headers) {
+            self.check_timestamp(header, median_time_past, now)?;
+        }
+        Ok(())
+    }
+
+    /// Target a child of `parent` must meet, or `None` if an ancestor it depends on is unknown.
+    ///
+    /// Blocks keep their parent's target except at multiples of the retarget window. There,
+    /// the target is scaled by the time the last `window` blocks took according to their
+    /// timestamps. Windows that would reach back to genesis, whose timestamp is not a
+    /// mining time, are skipped.
+    pub fn expected_bits<'a, H: HeaderSource + ?Sized>(&self, parent: &'a BlockHeader, headers: &'a H) -> Option<u32> {
+        let height = parent.index + 1;
+        let window = self.retarget.window;
+        if self.retarget.fixed_target || !height.is_multiple_of(window) || height <= window {
+            return Some(parent.bits);
+        }
+
+        let mut first = parent;
+        for _ in 0..window {
+            first = headers.header(&first.previous_hash)?;
+        }
+        let timespan = parent.timestamp.saturating_sub(first.timestamp);
+        let bits = retarget(&self.retarget, parent.bits, timespan);
+        debug!("Retargeting at height {}: window took {} ms, target {:08x} -> {:08x}", height, timespan, parent.bits, bits);
+        Some(bits)
+    }
+
+    /// Median timestamp of `parent` and its ancestors, up to `median_time_span` blocks, or
+    /// `None` if one of them is unknown.
+    pub fn median_time_past<'a, H: HeaderSource + ?Sized>(&self, parent: &'a BlockHeader, headers: &'a H) -> Option<u128> {
+        let mut timestamps = Vec::with_capacity(self.median_time_span);
+        let mut header = parent;
+        loop {
+            timestamps.push(header.timestamp);
+            if timestamps.len() == self.median_time_span || header.index == 0 {
+                break;
+            }
+            header = headers.header(&header.previous_hash)?;
+        }
+        timestamps.sort_unstable();
+        Some(timestamps[timestamps.len() / 2])
+    }
+
+    /// Checks that `header`'s timestamp is later than `median_time_past` and no more than
+    /// `max_future_drift` past `now`, both in milliseconds since the Unix epoch.
+    pub fn check_timestamp(&self, header: &BlockHeader, median_time_past: u128, now: u128) -> Result<(), ChainError> {
+        let index = header.index;
+        let timestamp = header.timestamp;
+        if timestamp <= median_time_past {
+            return Err(ChainError::TimestampTooOld { index, timestamp, median_time_past });
+        }
+        let max = now + self.max_future_drift.as_millis();
+        if timestamp > max {
+            return Err(ChainError::TimestampTooNew { index, timestamp, max });
+        }
+        Ok(())
+    }
+
+    pub fn check_size(&self, block: &Block) -> Result<(), ChainError> {
+        let size = encode(block).len();
+        if size > self.max_block_size {
+            return Err(ChainError::BlockTooLarge { index: block.header.index, size, max: self.max_block_size });
+        }
+        Ok(())
+    }
+}
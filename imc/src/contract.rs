@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartContract {
+    pub id: String,
+    pub creator: String,
+    pub code: String, // The code of the smart contract
+    pub state: HashMap<String, String>, // State variables of the contract
+}
+
+impl SmartContract {
+    pub fn new(id: String, creator: String, code: String) -> Self {
+        SmartContract {
+            id,
+            creator,
+            code,
+            state: HashMap::new(),
+        }
+    }
+
+    pub fn execute(&mut self, function: &str, params: HashMap<String, String>) -> Result<String, String> {
+        // Placeholder for actual execution logic
+        match function {
+            "set" => {
+                if let Some(key) = params.get("key") {
+                    if let Some(value) = params.get("value") {
+                        self.state.insert(key.clone(), value.clone());
+                        return Ok(format!("Set state '{}' to '{}'", key, value));
+                    }
+                }
+                Err("Invalid parameters for 'set' function".to_string())
+            }
+            "get" => {
+                if let Some(key) = params.get("key") {
+                    if let Some(value) = self.state.get(key) {
+                        return Ok(format!("State value for key '{}': '{}'", key, value));
+                    } else {
+                        return Err(format!("Key '{}' not found in state", key));
+                    }
+                }
+                Err("Invalid parameters for 'get' function".to_string())
+            }
+            _ => Err(format!("Function '{}' not recognized", function)),
+        }
+    }
+}
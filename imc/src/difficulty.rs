@@ -0,0 +1,97 @@
+//! Proof-of-work targets and retargeting.
+//!
+//! A block's hash, read as a 256-bit big-endian number, must not exceed its target. Targets
+//! are stored in headers in the compact form Bitcoin calls `nBits`: the top byte is the
+//! target's length in bytes and the low three bytes are its most significant bytes. Compact
+//! targets lose precision, which is fine as long as every node rounds the same way, so every
+//! computed target goes through [`target_to_compact`] before it is used.
+//!
+//! Every [`RetargetParams::window`] blocks the target is scaled by how long the previous
+//! window actually took compared to how long it should have taken, going only by header
+//! timestamps. The change is clamped to [`RetargetParams::max_adjustment`] in either
+//! direction and never makes the target easier than [`RetargetParams::pow_limit`].
+
+use crate::consensus::duration_secs;
+use bigdecimal::num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetargetParams {
+    #[serde(with = "duration_secs")]
+    pub target_block_time: Duration,
+    pub window: u64,         // Number of blocks between retargets
+    pub max_adjustment: u32, // A retarget changes the target by at most this factor
+    pub pow_limit: u32,      // Easiest allowed target, compact
+    pub initial_bits: u32,   // Target of genesis and of every block until the first retarget
+    pub fixed_target: bool,  // Never retarget, so every block has `initial_bits`
+}
+
+impl Default for RetargetParams {
+    fn default() -> Self {
+        RetargetParams {
+            target_block_time: Duration::from_secs(450), // 7.5 minutes
+            window: 24,
+            max_adjustment: 4,
+            pow_limit: 0x207f_ffff,
+            initial_bits: 0x1e10_0000, // 2^236, about five leading zero hex digits
+            fixed_target: false,
+        }
+    }
+}
+
+/// Expands a compact target. Targets with the sign bit set are negative in Bitcoin's
+/// encoding and expand to zero, which no hash can meet.
+pub fn compact_to_target(bits: u32) -> BigUint {
+    let size = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 {
+        return BigUint::from(0u32);
+    }
+    if size <= 3 {
+        BigUint::from(mantissa >> (8 * (3 - size)))
+    } else {
+        BigUint::from(mantissa) << (8 * (size - 3))
+    }
+}
+
+/// Rounds `target` down to compact form.
+pub fn target_to_compact(target: &BigUint) -> u32 {
+    let mut size = target.bits().div_ceil(8) as u32;
+    let mut mantissa = if size <= 3 {
+        (target.iter_u32_digits().next().unwrap_or(0)) << (8 * (3 - size))
+    } else {
+        (target >> (8 * (size - 3))).iter_u32_digits().next().unwrap_or(0)
+    };
+    // The mantissa's top bit is the sign, so shift positive targets that would set it
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    mantissa | (size << 24)
+}
+
+/// Whether the hex block hash `hash` is at or below the target `bits`.
+pub fn hash_meets_target(hash: &str, bits: u32) -> bool {
+    match BigUint::parse_bytes(hash.as_bytes(), 16) {
+        Some(value) => hash.len() == 64 && value <= compact_to_target(bits),
+        None => false,
+    }
+}
+
+/// Expected number of hashes needed to meet `bits`, `2^256 / (target + 1)`.
+pub fn work_for(bits: u32) -> BigUint {
+    (BigUint::from(1u32) << 256u32) / (compact_to_target(bits) + 1u32)
+}
+
+/// Target after a window of blocks that took `actual_timespan` milliseconds, where the
+/// window's blocks had target `bits`.
+pub fn retarget(params: &RetargetParams, bits: u32, actual_timespan: u128) -> u32 {
+    let expected = params.target_block_time.as_millis() * params.window as u128;
+    let max_adjustment = params.max_adjustment as u128;
+    let actual = actual_timespan.clamp(expected / max_adjustment, expected * max_adjustment);
+
+    let target = compact_to_target(bits) * BigUint::from(actual) / BigUint::from(expected);
+    let limit = compact_to_target(params.pow_limit);
+    target_to_compact(if target > limit { &limit } else { &target })
+}
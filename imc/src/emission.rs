@@ -0,0 +1,97 @@
+//! Monetary policy: how many coins exist at each height.
+//!
+//! The genesis block creates [`EmissionSchedule::genesis_allocation`]. Every later block may
+//! mint the block reward, which starts at [`EmissionSchedule::initial_reward`] and halves
+//! every [`EmissionSchedule::halving_interval`] blocks. Rewards are rounded down to
+//! [`COIN_DECIMALS`] places, so they reach zero after a finite number of halvings, and
+//! minting stops early if it would take the supply past [`EmissionSchedule::max_supply`].
+
+use crate::coinbase::{Coinbase, CoinbaseOutput};
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Decimal places of the smallest amount a reward is paid in.
+pub const COIN_DECIMALS: i64 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmissionSchedule {
+    pub genesis_allocation: BTreeMap<String, BigDecimal>, // Balances the genesis block creates
+    pub initial_reward: BigDecimal,
+    pub halving_interval: u64, // Blocks between halvings of the reward
+    pub max_supply: BigDecimal, // No block may take the total supply past this
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        EmissionSchedule {
+            genesis_allocation: BTreeMap::new(), // Set by a network's genesis spec; nothing without one
+            initial_reward: BigDecimal::from(40),
+            halving_interval: 280_320, // About four years of 7.5 minute blocks
+            max_supply: BigDecimal::from(100_000_000), // 10^8 in total, genesis allocation included
+        }
+    }
+}
+
+impl EmissionSchedule {
+    /// Coins created by the genesis block.
+    pub fn genesis_supply(&self) -> BigDecimal {
+        self.genesis_allocation.values().sum()
+    }
+
+    /// The genesis block's coinbase, paying out the genesis allocation by address.
+    pub fn genesis_coinbase(&self) -> Coinbase {
+        let outputs = self.genesis_allocation.iter()
+            .map(|(address, amount)| CoinbaseOutput { address: address.clone(), amount: amount.clone() })
+            .collect();
+        Coinbase { outputs }
+    }
+
+    /// Total coins in existence once the block at `height` is connected.
+    pub fn total_supply_at(&self, height: u64) -> BigDecimal {
+        let supply = self.genesis_supply() + self.mined_through(height);
+        if supply > self.max_supply {
+            self.max_supply.clone()
+        } else {
+            supply
+        }
+    }
+
+    /// Coins the block at `height` may mint. Zero for genesis, whose coins are the genesis
+    /// allocation.
+    pub fn reward_at(&self, height: u64) -> BigDecimal {
+        if height == 0 {
+            return BigDecimal::zero();
+        }
+        let reward = self.total_supply_at(height) - self.total_supply_at(height - 1);
+        if reward > BigDecimal::zero() {
+            reward
+        } else {
+            BigDecimal::zero()
+        }
+    }
+
+    // Reward of a block in halving epoch `epoch`, ignoring the supply cap
+    fn epoch_reward(&self, epoch: u64) -> BigDecimal {
+        if epoch >= 64 {
+            return BigDecimal::zero();
+        }
+        (&self.initial_reward / BigDecimal::from(1u64 << epoch)).with_scale(COIN_DECIMALS)
+    }
+
+    // Sum of the rewards of blocks 1 to `height`, ignoring the supply cap
+    fn mined_through(&self, height: u64) -> BigDecimal {
+        let interval = self.halving_interval.max(1);
+        let mut mined = BigDecimal::zero();
+        for epoch in 0u64.. {
+            let first = epoch.saturating_mul(interval).max(1);
+            let reward = self.epoch_reward(epoch);
+            if first > height || reward.is_zero() {
+                break;
+            }
+            let last = (epoch + 1).saturating_mul(interval).saturating_sub(1).min(height);
+            mined += reward * BigDecimal::from(last - first + 1);
+        }
+        mined
+    }
+}
@@ -0,0 +1,159 @@
+use crate::mempool::FeeRate;
+use crate::transaction::{ReplayError, SignatureError};
+use bigdecimal::BigDecimal;
+use common::address::AddressError;
+use std::fmt;
+
+/// Why a transaction was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxError {
+    InvalidAddress(AddressError),
+    NonPositiveAmount,
+    NegativeFee,
+    Signature(SignatureError),
+    Replay(ReplayError),
+    InsufficientBalance { balance: BigDecimal, required: BigDecimal },
+    ReplacementFeeTooLow { fee: BigDecimal, required: BigDecimal },
+    MempoolFull { min_fee_rate: FeeRate },
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxError::InvalidAddress(e) => write!(f, "invalid address: {}", e),
+            TxError::NonPositiveAmount => write!(f, "amount must be greater than zero"),
+            TxError::NegativeFee => write!(f, "fee must not be negative"),
+            TxError::Signature(e) => write!(f, "{}", e),
+            TxError::Replay(e) => write!(f, "{}", e),
+            TxError::InsufficientBalance { balance, required } => {
+                write!(f, "insufficient balance: sender has {}, needs {}", balance, required)
+            }
+            TxError::ReplacementFeeTooLow { fee, required } => {
+                write!(f, "fee {} is too low to replace the pending transaction with this nonce, needs {}", fee, required)
+            }
+            TxError::MempoolFull { min_fee_rate } => write!(f, "mempool is full, fee rate must be above {}", min_fee_rate),
+        }
+    }
+}
+
+impl std::error::Error for TxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TxError::InvalidAddress(e) => Some(e),
+            TxError::Signature(e) => Some(e),
+            TxError::Replay(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AddressError> for TxError {
+    fn from(e: AddressError) -> Self {
+        TxError::InvalidAddress(e)
+    }
+}
+
+impl From<SignatureError> for TxError {
+    fn from(e: SignatureError) -> Self {
+        TxError::Signature(e)
+    }
+}
+
+impl From<ReplayError> for TxError {
+    fn from(e: ReplayError) -> Self {
+        TxError::Replay(e)
+    }
+}
+
+/// Why a block, or a chain, was rejected. Names the block (and transaction) at fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    InvalidHash { index: u64 },
+    BrokenLink { index: u64 },
+    InvalidMerkleRoot { index: u64 },
+    InvalidCoinbase { index: u64 },
+    ExcessiveMint { index: u64, allowed: BigDecimal, found: BigDecimal },
+    InvalidTransaction { index: u64, position: usize, tx_id: String, error: Box<TxError> },
+    InsufficientProofOfWork { index: u64 },
+    WrongTarget { index: u64, expected: u32, found: u32 },
+    InvalidIndex { index: u64, expected: u64 },
+    TimestampTooOld { index: u64, timestamp: u128, median_time_past: u128 },
+    TimestampTooNew { index: u64, timestamp: u128, max: u128 },
+    BlockTooLarge { index: u64, size: usize, max: usize },
+    DuplicateBlock { hash: String },
+    UnknownParent { hash: String, parent: String },
+    InvalidAncestor { hash: String, ancestor: String },
+    StateMismatch { field: &'static str, key: String, replayed: String, live: String },
+}
+
+impl ChainError {
+    /// Index of the block that failed validation, when the block could be placed in the chain.
+    pub fn block_index(&self) -> Option<u64> {
+        match self {
+            ChainError::InvalidHash { index }
+            | ChainError::BrokenLink { index }
+            | ChainError::InvalidMerkleRoot { index }
+            | ChainError::InvalidCoinbase { index }
+            | ChainError::ExcessiveMint { index, .. }
+            | ChainError::InvalidTransaction { index, .. }
+            | ChainError::InsufficientProofOfWork { index }
+            | ChainError::WrongTarget { index, .. }
+            | ChainError::InvalidIndex { index, .. }
+            | ChainError::TimestampTooOld { index, .. }
+            | ChainError::TimestampTooNew { index, .. }
+            | ChainError::BlockTooLarge { index, .. } => Some(*index),
+            ChainError::DuplicateBlock { .. }
+            | ChainError::UnknownParent { .. }
+            | ChainError::InvalidAncestor { .. }
+            | ChainError::StateMismatch { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::InvalidHash { index } => write!(f, "block {} hash does not match its header", index),
+            ChainError::BrokenLink { index } => {
+                write!(f, "block {} previous_hash does not match block {}", index, index.saturating_sub(1))
+            }
+            ChainError::InvalidMerkleRoot { index } => write!(f, "block {} merkle root does not match its transactions", index),
+            ChainError::InvalidCoinbase { index } => {
+                write!(f, "block {} coinbase does not match the block reward owed to its miner and contributors", index)
+            }
+            ChainError::ExcessiveMint { index, allowed, found } => {
+                write!(f, "block {} mints {}, but the emission schedule allows {}", index, found, allowed)
+            }
+            ChainError::InvalidTransaction { index, position, tx_id, error } => {
+                write!(f, "block {} transaction {} ({}) is invalid: {}", index, position, tx_id, error)
+            }
+            ChainError::InsufficientProofOfWork { index } => write!(f, "block {} hash does not meet its target", index),
+            ChainError::WrongTarget { index, expected, found } => {
+                write!(f, "block {} has target {:08x}, the retarget rule gives {:08x}", index, found, expected)
+            }
+            ChainError::InvalidIndex { index, expected } => write!(f, "block has index {}, expected {}", index, expected),
+            ChainError::TimestampTooOld { index, timestamp, median_time_past } => {
+                write!(f, "block {} timestamp {} is not after the median time past {}", index, timestamp, median_time_past)
+            }
+            ChainError::TimestampTooNew { index, timestamp, max } => {
+                write!(f, "block {} timestamp {} is too far in the future, latest allowed is {}", index, timestamp, max)
+            }
+            ChainError::BlockTooLarge { index, size, max } => write!(f, "block {} is {} bytes, limit is {}", index, size, max),
+            ChainError::DuplicateBlock { hash } => write!(f, "block {} is already known", hash),
+            ChainError::UnknownParent { hash, parent } => write!(f, "block {} builds on unknown block {}", hash, parent),
+            ChainError::InvalidAncestor { hash, ancestor } => write!(f, "block {} builds on invalid block {}", hash, ancestor),
+            ChainError::StateMismatch { field, key, replayed, live } => {
+                write!(f, "{} of {} is {}, but replaying the chain gives {}", field, key, live, replayed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChainError::InvalidTransaction { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Everything that defines a network: its id, genesis block and consensus rules.
+//!
+//! A [`GenesisSpec`] is read from a JSON file or taken from one of the built-in [`Network`]
+//! presets. Two nodes are on the same network only if their specs are identical; a chain
+//! stored under one spec will not open under another, since the genesis blocks differ.
+
+use crate::blockchain::DEFAULT_CHAIN_ID;
+use crate::consensus::ConsensusRules;
+use crate::difficulty::compact_to_target;
+use crate::emission::EmissionSchedule;
+use bigdecimal::{BigDecimal, Zero};
+use bigdecimal::num_bigint::BigUint;
+use common::address::validate_address;
+use common::storage::{read_json, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Private key of the testnet faucet, published so anyone can spend testnet coins. Never
+/// use it for anything else.
+pub const TESTNET_FAUCET_KEY: &str = "478dd440b634abfecd276a9f56c72363d39a707e8ea8c1a31e376ac141d25968";
+/// Address of [`TESTNET_FAUCET_KEY`], which the testnet genesis block funds.
+pub const TESTNET_FAUCET_ADDRESS: &str = "RApV8JPXESsKYY1DtfZkVi2LXavh4R2cFS";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    pub network: String,
+    pub chain_id: u32, // Signed into every transaction so it cannot be replayed on another network
+    pub timestamp: u128, // Of the genesis block, in milliseconds since the Unix epoch
+    pub liquidity_wallet: String, // Receives half of every transaction fee
+    pub rewards_wallet: String, // Receives the other half
+    pub consensus: ConsensusRules, // Includes the genesis allocation, see `EmissionSchedule`
+}
+
+impl GenesisSpec {
+    pub fn mainnet() -> Self {
+        GenesisSpec {
+            network: "mainnet".to_string(),
+            chain_id: DEFAULT_CHAIN_ID,
+            timestamp: 0,
+            liquidity_wallet: "LiquidityWallet".to_string(),
+            rewards_wallet: "RewardsWallet".to_string(),
+            consensus: ConsensusRules::default(),
+        }
+    }
+
+    /// Like mainnet but with coins allocated to a faucet whose key is published, see
+    /// [`TESTNET_FAUCET_KEY`], and a target a CPU can meet.
+    pub fn testnet() -> Self {
+        let mut consensus = ConsensusRules::default();
+        consensus.retarget.initial_bits = 0x1f0f_ffff;
+        consensus.emission.genesis_allocation = allocation(&[(TESTNET_FAUCET_ADDRESS, "1000000000")]);
+        consensus.emission.max_supply = BigDecimal::from_str("1100000000").unwrap();
+        GenesisSpec {
+            network: "testnet".to_string(),
+            chain_id: 2,
+            timestamp: 1_704_067_200_000, // 2024-01-01
+            consensus,
+            ..Self::mainnet()
+        }
+    }
+
+    /// A private network for local tests: any hash has about even odds of meeting the
+    /// target, which never changes, and nothing is allocated at genesis.
+    pub fn regtest() -> Self {
+        let mut consensus = ConsensusRules::default();
+        consensus.retarget.initial_bits = 0x207f_ffff;
+        consensus.retarget.pow_limit = 0x207f_ffff;
+        consensus.retarget.fixed_target = true;
+        consensus.retarget.target_block_time = Duration::from_secs(1);
+        consensus.emission = EmissionSchedule {
+            genesis_allocation: BTreeMap::new(),
+            halving_interval: 150,
+            max_supply: BigDecimal::from(12_000),
+            ..EmissionSchedule::default()
+        };
+        GenesisSpec {
+            network: "regtest".to_string(),
+            chain_id: 3,
+            timestamp: 1_704_067_200_000,
+            consensus,
+            ..Self::mainnet()
+        }
+    }
+
+    /// Reads a spec written by [`GenesisSpec::save`], or by hand. A spec that fails
+    /// [`GenesisSpec::validate`] is reported as [`io::ErrorKind::InvalidData`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let spec: GenesisSpec = read_json(path)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())))?;
+        spec.validate().map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Ok(spec)
+    }
+
+    /// Checks that a chain can run under these rules: that retargeting never divides by
+    /// zero, that targets are ones a hash can meet, and that the genesis allocation pays
+    /// spendable addresses and fits under the supply cap.
+    pub fn validate(&self) -> io::Result<()> {
+        let retarget = &self.consensus.retarget;
+        let emission = &self.consensus.emission;
+        if retarget.target_block_time.as_millis() == 0 {
+            return Err(invalid_spec("target_block_time must be at least a millisecond".to_string()));
+        }
+        if retarget.window == 0 {
+            return Err(invalid_spec("window must be at least one block".to_string()));
+        }
+        if retarget.max_adjustment == 0 {
+            return Err(invalid_spec("max_adjustment must be at least 1".to_string()));
+        }
+        let pow_limit = decode_target("pow_limit", retarget.pow_limit)?;
+        if decode_target("initial_bits", retarget.initial_bits)? > pow_limit {
+            return Err(invalid_spec(format!("initial_bits {:#010x} is easier than pow_limit {:#010x}", retarget.initial_bits, retarget.pow_limit)));
+        }
+        if emission.halving_interval == 0 {
+            return Err(invalid_spec("halving_interval must be at least one block".to_string()));
+        }
+        for (address, amount) in &emission.genesis_allocation {
+            validate_address(address).map_err(|e| invalid_spec(format!("genesis allocation to {:?}: {}", address, e)))?;
+            if *amount <= BigDecimal::zero() {
+                return Err(invalid_spec(format!("genesis allocation of {} to {} is not positive", amount, address)));
+            }
+        }
+        if emission.genesis_supply() > emission.max_supply {
+            return Err(invalid_spec(format!("genesis allocation of {} exceeds max_supply {}", emission.genesis_supply(), emission.max_supply)));
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_json_atomic(path, self)
+    }
+}
+
+impl Default for GenesisSpec {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+fn invalid_spec(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// The target `bits` expands to, if a hash can meet it
+fn decode_target(name: &str, bits: u32) -> io::Result<BigUint> {
+    let target = compact_to_target(bits);
+    if target.is_zero() || target.bits() > 256 {
+        return Err(invalid_spec(format!("{} {:#010x} is not a valid compact target", name, bits)));
+    }
+    Ok(target)
+}
+
+fn allocation(entries: &[(&str, &str)]) -> BTreeMap<String, BigDecimal> {
+    entries.iter().map(|(address, amount)| (address.to_string(), BigDecimal::from_str(amount).unwrap())).collect()
+}
+
+/// The built-in networks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    pub fn genesis(&self) -> GenesisSpec {
+        match self {
+            Network::Mainnet => GenesisSpec::mainnet(),
+            Network::Testnet => GenesisSpec::testnet(),
+            Network::Regtest => GenesisSpec::regtest(),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(UnknownNetwork(name.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownNetwork(pub String);
+
+impl fmt::Display for UnknownNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown network {}, expected mainnet, testnet or regtest", self.0)
+    }
+}
+
+impl std::error::Error for UnknownNetwork {}
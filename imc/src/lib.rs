@@ -1 +1,46 @@
-pub mod blockchain;
\ No newline at end of file
+//! The InfiniMath main chain: blocks, transactions, smart contracts, and the
+//! [`Blockchain`](blockchain::Blockchain) that validates and stores them.
+//!
+//! Diagnostics go through the [`log`] facade; binaries choose where they end up by
+//! installing a logger.
+//!
+//! Only the key and signature types of `common` are needed here, so this crate builds without
+//! its `wallet` feature. Networking lives in `p2p` behind the `p2p` feature, on by
+//! default, so a crate that only validates or stores blocks can leave it out.
+
+pub mod block;
+pub mod block_tree;
+pub mod blockchain;
+pub mod coinbase;
+pub mod consensus;
+pub mod contract;
+pub mod difficulty;
+pub mod emission;
+pub mod error;
+pub mod genesis;
+pub mod mempool;
+pub mod merkle;
+#[cfg(feature = "p2p")]
+pub mod p2p;
+pub mod storage;
+pub mod transaction;
+pub mod work;
+
+pub mod prelude {
+    pub use crate::block::{Block, BlockHeader};
+    pub use crate::block_tree::BlockStatus;
+    pub use crate::blockchain::{Blockchain, DEFAULT_CHAIN_ID};
+    pub use crate::coinbase::{Coinbase, CoinbaseOutput};
+    pub use crate::consensus::ConsensusRules;
+    pub use crate::contract::SmartContract;
+    pub use crate::error::{ChainError, TxError};
+    pub use crate::genesis::{GenesisSpec, Network};
+    pub use crate::mempool::{Mempool, MempoolConfig};
+    pub use crate::merkle::{merkle_root, MerkleProof};
+    #[cfg(feature = "p2p")]
+    pub use crate::p2p::message::Inventory;
+    #[cfg(feature = "p2p")]
+    pub use crate::p2p::node::{Node, NodeConfig};
+    pub use crate::transaction::{ReplayError, SignatureError, Transaction, TxId};
+    pub use crate::work::{BlockTemplate, WorkQueue};
+}
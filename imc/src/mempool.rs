@@ -0,0 +1,342 @@
+//! Transactions waiting to be mined.
+//!
+//! [`Blockchain`] checks a transaction's signature, nonce and funds before it reaches the
+//! [`Mempool`]; the mempool decides what it keeps and in which order it is mined. Each
+//! sender's transactions form a chain of consecutive nonces, so removing one also removes
+//! the ones after it, which could no longer be mined.
+//!
+//! Blocks are filled by fee rate, the fee per byte of canonical encoding. A sender's
+//! transactions still go in nonce order: one becomes a candidate once the one before it is
+//! in the block.
+
+use crate::blockchain::Blockchain;
+use crate::error::TxError;
+use crate::transaction::{Transaction, TxId};
+use bigdecimal::{BigDecimal, Zero};
+use common::encoding::encode;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolConfig {
+    pub max_bytes: usize,        // Total encoded size of the transactions the pool may hold
+    pub expiry: Duration,        // Transactions pending for longer than this are dropped
+    pub replacement_bump: u32,   // A replacement must pay at least this many percent more fee
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_bytes: 5_000_000,
+            expiry: Duration::from_secs(72 * 60 * 60),
+            replacement_bump: 10,
+        }
+    }
+}
+
+/// Fee per byte. Compared exactly, without dividing.
+#[derive(Debug, Clone)]
+pub struct FeeRate {
+    pub fee: BigDecimal,
+    pub size: usize,
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.fee * BigDecimal::from(other.size as u64)).cmp(&(&other.fee * BigDecimal::from(self.size as u64)))
+    }
+}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FeeRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FeeRate {}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} per {} bytes", self.fee, self.size)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub transaction: Transaction,
+    pub tx_id: TxId,
+    pub size: usize,  // Bytes of canonical encoding
+    pub added: u128,  // When the transaction entered the pool, in milliseconds since the Unix epoch
+    seq: u64,         // Arrival order, which breaks fee rate ties
+}
+
+impl MempoolEntry {
+    pub fn fee_rate(&self) -> FeeRate {
+        FeeRate { fee: self.transaction.fee.clone(), size: self.size }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    pub config: MempoolConfig,
+    entries: HashMap<TxId, MempoolEntry>,
+    by_sender: HashMap<String, BTreeMap<u64, TxId>>, // Each sender's transactions by nonce
+    bytes: usize,
+    next_seq: u64,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool { config, ..Mempool::default() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total encoded size of the pending transactions.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn contains(&self, tx_id: &str) -> bool {
+        self.entries.contains_key(tx_id)
+    }
+
+    pub fn get(&self, tx_id: &str) -> Option<&MempoolEntry> {
+        self.entries.get(tx_id)
+    }
+
+    /// The pending transaction from `sender` with `nonce`.
+    pub fn get_by_nonce(&self, sender: &str, nonce: u64) -> Option<&MempoolEntry> {
+        self.by_sender.get(sender)?.get(&nonce).map(|tx_id| &self.entries[tx_id])
+    }
+
+    /// The nonce after the last pending transaction from `sender`, if it has any.
+    pub fn next_nonce(&self, sender: &str) -> Option<u64> {
+        self.by_sender.get(sender)?.keys().next_back().map(|nonce| nonce + 1)
+    }
+
+    /// Amount plus fee of every pending transaction from `sender`.
+    pub fn pending_spend(&self, sender: &str) -> BigDecimal {
+        self.sent_by(sender).iter().map(|tx| &tx.amount + &tx.fee).sum()
+    }
+
+    /// Pending transactions from `sender`, in nonce order.
+    pub fn sent_by(&self, sender: &str) -> Vec<&Transaction> {
+        self.by_sender.get(sender)
+            .map(|chain| chain.values().map(|tx_id| &self.entries[tx_id].transaction).collect())
+            .unwrap_or_default()
+    }
+
+    /// Pending transactions to `receiver`, in arrival order.
+    pub fn received_by(&self, receiver: &str) -> Vec<&Transaction> {
+        self.in_arrival_order().into_iter().map(|entry| &entry.transaction).filter(|tx| tx.receiver == receiver).collect()
+    }
+
+    /// Pending transactions from or to `address`, in arrival order.
+    pub fn for_address(&self, address: &str) -> Vec<&Transaction> {
+        self.in_arrival_order().into_iter()
+            .map(|entry| &entry.transaction)
+            .filter(|tx| tx.sender == address || tx.receiver == address)
+            .collect()
+    }
+
+    /// Every pending transaction, in arrival order, which keeps each sender's in nonce order.
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.in_arrival_order().into_iter().map(|entry| entry.transaction.clone()).collect()
+    }
+
+    fn in_arrival_order(&self) -> Vec<&MempoolEntry> {
+        let mut entries: Vec<&MempoolEntry> = self.entries.values().collect();
+        entries.sort_by_key(|entry| entry.seq);
+        entries
+    }
+
+    /// Adds a transaction that has already been checked against the chain, at time `added`.
+    ///
+    /// A transaction with the nonce of a pending one from the same sender replaces it if it
+    /// pays at least [`MempoolConfig::replacement_bump`] percent more fee; the replaced
+    /// transaction is returned. If the pool grows past [`MempoolConfig::max_bytes`], the
+    /// transactions with the lowest fee rate are evicted, and the new one is refused if its
+    /// fee rate is too low to stay.
+    pub fn insert(&mut self, transaction: Transaction, added: u128) -> Result<Option<Transaction>, TxError> {
+        let tx_id = transaction.hash();
+        let size = encode(&transaction).len();
+
+        let replaced = match self.get_by_nonce(&transaction.sender, transaction.nonce) {
+            Some(old) => {
+                let old_fee = &old.transaction.fee;
+                let required = old_fee * BigDecimal::from(100 + self.config.replacement_bump) / BigDecimal::from(100);
+                if transaction.fee <= *old_fee || transaction.fee < required {
+                    return Err(TxError::ReplacementFeeTooLow { fee: transaction.fee.clone(), required });
+                }
+                Some(old.tx_id.clone())
+            }
+            None => {
+                if self.bytes + size > self.config.max_bytes {
+                    let rate = FeeRate { fee: transaction.fee.clone(), size };
+                    if let Some(lowest) = self.lowest_fee_rate() {
+                        if rate <= lowest.fee_rate() {
+                            return Err(TxError::MempoolFull { min_fee_rate: lowest.fee_rate() });
+                        }
+                    }
+                }
+                None
+            }
+        };
+
+        let (seq, replaced) = match replaced.and_then(|tx_id| self.entries.remove(&tx_id)) {
+            Some(old) => {
+                self.bytes -= old.size;
+                (old.seq, Some(old.transaction))
+            }
+            None => {
+                self.next_seq += 1;
+                (self.next_seq, None)
+            }
+        };
+        self.by_sender.entry(transaction.sender.clone()).or_default().insert(transaction.nonce, tx_id.clone());
+        self.bytes += size;
+        self.entries.insert(tx_id.clone(), MempoolEntry { transaction, tx_id: tx_id.clone(), size, added, seq });
+
+        while self.bytes > self.config.max_bytes {
+            let lowest = self.lowest_fee_rate().expect("a pool over its size limit is not empty").tx_id.clone();
+            for evicted in self.remove(&lowest) {
+                debug!("Evicted transaction {} from the mempool", evicted.hash());
+            }
+        }
+        if !self.entries.contains_key(&tx_id) {
+            let min_fee_rate = self.lowest_fee_rate().map_or(FeeRate { fee: BigDecimal::zero(), size }, MempoolEntry::fee_rate);
+            return Err(TxError::MempoolFull { min_fee_rate });
+        }
+        Ok(replaced)
+    }
+
+    fn lowest_fee_rate(&self) -> Option<&MempoolEntry> {
+        self.entries.values().min_by(|a, b| a.fee_rate().cmp(&b.fee_rate()).then(b.seq.cmp(&a.seq)))
+    }
+
+    /// Removes a transaction and every later one from the same sender, returning them in
+    /// nonce order.
+    pub fn remove(&mut self, tx_id: &str) -> Vec<Transaction> {
+        let (sender, nonce) = match self.entries.get(tx_id) {
+            Some(entry) => (entry.transaction.sender.clone(), entry.transaction.nonce),
+            None => return vec![],
+        };
+        let chain = self.by_sender.get_mut(&sender).unwrap();
+        let removed: Vec<TxId> = chain.split_off(&nonce).into_values().collect();
+        if chain.is_empty() {
+            self.by_sender.remove(&sender);
+        }
+
+        removed.into_iter()
+            .filter_map(|tx_id| self.entries.remove(&tx_id))
+            .map(|entry| {
+                self.bytes -= entry.size;
+                entry.transaction
+            })
+            .collect()
+    }
+
+    /// Removes the transactions added before `now` less [`MempoolConfig::expiry`], with the
+    /// ones after them from the same sender.
+    pub fn expire(&mut self, now: u128) -> Vec<Transaction> {
+        let cutoff = now.saturating_sub(self.config.expiry.as_millis());
+        let mut stale: Vec<&MempoolEntry> = self.entries.values().filter(|entry| entry.added < cutoff).collect();
+        stale.sort_by_key(|entry| entry.seq);
+        let stale: Vec<TxId> = stale.into_iter().map(|entry| entry.tx_id.clone()).collect();
+        stale.iter().flat_map(|tx_id| self.remove(tx_id)).collect()
+    }
+
+    /// Empties the pool, returning its entries in arrival order.
+    pub fn drain(&mut self) -> Vec<MempoolEntry> {
+        let mut entries: Vec<MempoolEntry> = self.entries.drain().map(|(_, entry)| entry).collect();
+        entries.sort_by_key(|entry| entry.seq);
+        self.by_sender.clear();
+        self.bytes = 0;
+        entries
+    }
+
+    /// Picks the transactions for the next block on `chain`'s tip, highest fee rate first,
+    /// whose encodings add up to at most `max_bytes`. Each is applied to a working copy of
+    /// the balances and nonces, so none of them overdraws its sender.
+    pub fn block_template(&self, chain: &Blockchain, max_bytes: usize) -> Vec<Transaction> {
+        let mut candidates = BinaryHeap::new();
+        for (sender, nonces) in &self.by_sender {
+            let confirmed = chain.nonces.get(sender).copied().unwrap_or(0);
+            if let Some(tx_id) = nonces.get(&confirmed) {
+                candidates.push(self.candidate(tx_id));
+            }
+        }
+
+        let mut balances: HashMap<&str, BigDecimal> = HashMap::new();
+        let mut bytes = 0;
+        let mut selected = Vec::new();
+        while let Some(Candidate { tx_id, .. }) = candidates.pop() {
+            let entry = &self.entries[&tx_id];
+            let transaction = &entry.transaction;
+            // Skipping a transaction also leaves the rest of its sender's chain for later
+            if bytes + entry.size > max_bytes {
+                continue;
+            }
+            let sender = transaction.sender.as_str();
+            let balance = balances.get(sender).cloned().unwrap_or_else(|| chain.get_balance(sender));
+            if let Err(e) = chain.check_confirmed_transaction(transaction, transaction.nonce, balance.clone()) {
+                debug!("Leaving transaction {} out of the block: {}", tx_id, e);
+                continue;
+            }
+
+            balances.insert(sender, balance - &transaction.amount - &transaction.fee);
+            let receiver = transaction.receiver.as_str();
+            let received = balances.get(receiver).cloned().unwrap_or_else(|| chain.get_balance(receiver)) + &transaction.amount;
+            balances.insert(receiver, received);
+            bytes += entry.size;
+            selected.push(transaction.clone());
+
+            if let Some(next) = self.by_sender[sender].get(&(transaction.nonce + 1)) {
+                candidates.push(self.candidate(next));
+            }
+        }
+        selected
+    }
+
+    fn candidate(&self, tx_id: &str) -> Candidate {
+        let entry = &self.entries[tx_id];
+        Candidate { fee_rate: entry.fee_rate(), seq: entry.seq, tx_id: entry.tx_id.clone() }
+    }
+}
+
+// Orders block template candidates by fee rate, then earliest arrival
+#[derive(PartialEq, Eq)]
+struct Candidate {
+    fee_rate: FeeRate,
+    seq: u64,
+    tx_id: TxId,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.fee_rate.cmp(&other.fee_rate).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
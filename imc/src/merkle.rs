@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Root of a block with no transactions.
+pub const EMPTY_MERKLE_ROOT: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Leaves and inner nodes are hashed with different prefixes so that an inner node can never
+// be passed off as a transaction hash.
+const LEAF_PREFIX: &[u8] = &[0x00];
+const NODE_PREFIX: &[u8] = &[0x01];
+
+fn hash_leaf(tx_hash: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(LEAF_PREFIX);
+    hasher.update(tx_hash);
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_node(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(NODE_PREFIX);
+    hasher.update(left);
+    hasher.update(right);
+    format!("{:x}", hasher.finalize())
+}
+
+// Hashes one level of the tree into the next. A node without a partner is carried up
+// unchanged rather than paired with itself, so a list with a duplicated trailing
+// transaction does not produce the same root as the list without it.
+fn next_level(level: &[String]) -> Vec<String> {
+    level.chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => single.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Computes the Merkle root over transaction hashes, in block order.
+pub fn merkle_root(tx_hashes: &[String]) -> String {
+    if tx_hashes.is_empty() {
+        return EMPTY_MERKLE_ROOT.to_string();
+    }
+
+    let mut level: Vec<String> = tx_hashes.iter().map(|hash| hash_leaf(hash)).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One sibling on the path from a leaf to the root, and which side of the path it sits on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleStep {
+    pub hash: String,
+    pub side: Side,
+}
+
+/// Proof that a transaction hash is included under a Merkle root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub steps: Vec<MerkleStep>,
+}
+
+impl MerkleProof {
+    /// Builds the proof for the transaction at `index`, or `None` if it is out of range.
+    pub fn generate(tx_hashes: &[String], index: usize) -> Option<Self> {
+        if index >= tx_hashes.len() {
+            return None;
+        }
+
+        let mut steps = Vec::new();
+        let mut level: Vec<String> = tx_hashes.iter().map(|hash| hash_leaf(hash)).collect();
+        let mut position = index;
+        while level.len() > 1 {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < position { Side::Left } else { Side::Right };
+                steps.push(MerkleStep { hash: level[sibling].clone(), side });
+            }
+            level = next_level(&level);
+            position /= 2;
+        }
+
+        Some(MerkleProof { index, steps })
+    }
+
+    /// Checks that `tx_hash` hashes up to `merkle_root` along this proof.
+    pub fn verify(&self, tx_hash: &str, merkle_root: &str) -> bool {
+        let computed = self.steps.iter().fold(hash_leaf(tx_hash), |acc, step| match step.side {
+            Side::Left => hash_node(&step.hash, &acc),
+            Side::Right => hash_node(&acc, &step.hash),
+        });
+        computed == merkle_root
+    }
+}
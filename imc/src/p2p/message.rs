@@ -0,0 +1,263 @@
+//! Messages exchanged between peers and how they are framed on the wire.
+//!
+//! Each message is sent as a `u32` big-endian length followed by its canonical encoding
+//! (see [`common::encoding`]), so peers built with a different message layout fail the
+//! handshake instead of misreading blocks.
+
+use crate::block::{Block, BlockHeader};
+use crate::transaction::{Transaction, TxId};
+use common::encoding::{decode, encode, Decode, DecodeError, Decoder, Encode, Encoder};
+use std::io::{self, Read, Write};
+
+/// Bumped whenever messages are added or change meaning.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Largest message a peer may send, well above the largest block the default rules allow.
+pub const MAX_MESSAGE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Most addresses sent in one [`Message::Addr`].
+pub const MAX_ADDRESSES: usize = 1_000;
+
+/// Most headers sent in one [`Message::Headers`]; a full batch means more may follow.
+pub const MAX_HEADERS: usize = 2_000;
+
+/// Most items in one [`Message::Inv`], [`Message::GetData`] or [`Message::NotFound`]. A
+/// longer list does not decode, so the peer that sent it is disconnected.
+pub const MAX_INVENTORY: usize = 1_000;
+
+/// The first message on every connection, in both directions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub protocol: u32,
+    pub chain_id: u32,
+    pub genesis_hash: String, // Together with `chain_id`, identifies the network
+    pub best_height: u64,
+    pub best_hash: String,
+    pub listen_port: u32, // Where the sender accepts connections, 0 if it does not
+    pub nonce: u64, // Random per node, so a node can tell when it has connected to itself
+}
+
+/// A block or transaction, named by its hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Inventory {
+    Block(String),
+    Tx(TxId),
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Version(Version),
+    /// Accepts the other side's [`Version`]; the handshake is done once both sides have sent one.
+    Verack,
+    GetAddr,
+    /// Listening addresses of other peers, as `ip:port`.
+    Addr(Vec<String>),
+    /// Announces items the sender has.
+    Inv(Vec<Inventory>),
+    /// Asks for the announced items the receiver lacks.
+    GetData(Vec<Inventory>),
+    /// The requested items the sender does not have.
+    NotFound(Vec<Inventory>),
+    Block(Box<Block>),
+    Tx(Box<Transaction>),
+    /// Asks for the headers that follow the first of these hashes on the receiver's active
+    /// chain. The hashes run from the sender's best header back to genesis, see
+    /// [`HeaderChain::locator`](crate::p2p::sync::HeaderChain::locator).
+    GetHeaders(Vec<String>),
+    /// Consecutive headers, oldest first.
+    Headers(Vec<BlockHeader>),
+}
+
+impl Message {
+    /// Name used in log messages.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Message::Version(_) => "version",
+            Message::Verack => "verack",
+            Message::GetAddr => "getaddr",
+            Message::Addr(_) => "addr",
+            Message::Inv(_) => "inv",
+            Message::GetData(_) => "getdata",
+            Message::NotFound(_) => "notfound",
+            Message::Block(_) => "block",
+            Message::Tx(_) => "tx",
+            Message::GetHeaders(_) => "getheaders",
+            Message::Headers(_) => "headers",
+        }
+    }
+}
+
+/// Writes `message` with its length prefix.
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    let bytes = encode(message);
+    let len = u32::try_from(bytes.len()).ok().filter(|&len| len as usize <= MAX_MESSAGE_SIZE)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} message of {} bytes is too large", message.command(), bytes.len())))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+/// Reads one message written by [`write_message`]. Oversized or undecodable messages are
+/// reported as [`io::ErrorKind::InvalidData`].
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Message> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes is too large", len)));
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl Encode for Version {
+    fn encode_to(&self, encoder: &mut Encoder) {
+        encoder.put_u32(self.protocol);
+        encoder.put_u32(self.chain_id);
+        encoder.put_str(&self.genesis_hash);
+        encoder.put_u64(self.best_height);
+        encoder.put_str(&self.best_hash);
+        encoder.put_u32(self.listen_port);
+        encoder.put_u64(self.nonce);
+    }
+}
+
+impl Decode for Version {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Version {
+            protocol: decoder.get_u32()?,
+            chain_id: decoder.get_u32()?,
+            genesis_hash: decoder.get_string()?,
+            best_height: decoder.get_u64()?,
+            best_hash: decoder.get_string()?,
+            listen_port: decoder.get_u32()?,
+            nonce: decoder.get_u64()?,
+        })
+    }
+}
+
+impl Encode for Inventory {
+    fn encode_to(&self, encoder: &mut Encoder) {
+        match self {
+            Inventory::Block(hash) => {
+                encoder.put_u8(0);
+                encoder.put_str(hash);
+            }
+            Inventory::Tx(tx_id) => {
+                encoder.put_u8(1);
+                encoder.put_str(tx_id);
+            }
+        }
+    }
+}
+
+impl Decode for Inventory {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        match decoder.get_u8()? {
+            0 => Ok(Inventory::Block(decoder.get_string()?)),
+            1 => Ok(Inventory::Tx(decoder.get_string()?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+// A tag byte per variant, then its fields. The version is bumped along with that of any
+// block, header or transaction carried.
+impl Encode for Message {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        match self {
+            Message::Version(version) => {
+                encoder.put_u8(0);
+                encoder.put(version);
+            }
+            Message::Verack => encoder.put_u8(1),
+            Message::GetAddr => encoder.put_u8(2),
+            Message::Addr(addresses) => {
+                encoder.put_u8(3);
+                put_strings(encoder, addresses);
+            }
+            Message::Inv(items) => {
+                encoder.put_u8(4);
+                encoder.put_seq(items);
+            }
+            Message::GetData(items) => {
+                encoder.put_u8(5);
+                encoder.put_seq(items);
+            }
+            Message::NotFound(items) => {
+                encoder.put_u8(6);
+                encoder.put_seq(items);
+            }
+            Message::Block(block) => {
+                encoder.put_u8(7);
+                encoder.put(block.as_ref());
+            }
+            Message::Tx(transaction) => {
+                encoder.put_u8(8);
+                encoder.put(transaction.as_ref());
+            }
+            Message::GetHeaders(locator) => {
+                encoder.put_u8(9);
+                put_strings(encoder, locator);
+            }
+            Message::Headers(headers) => {
+                encoder.put_u8(10);
+                encoder.put_seq(headers);
+            }
+        }
+    }
+}
+
+impl Decode for Message {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        match decoder.get_u8()? {
+            0 => Ok(Message::Version(decoder.get()?)),
+            1 => Ok(Message::Verack),
+            2 => Ok(Message::GetAddr),
+            3 => Ok(Message::Addr(get_strings(decoder, MAX_ADDRESSES)?)),
+            4 => Ok(Message::Inv(get_inventory(decoder)?)),
+            5 => Ok(Message::GetData(get_inventory(decoder)?)),
+            6 => Ok(Message::NotFound(get_inventory(decoder)?)),
+            7 => Ok(Message::Block(Box::new(decoder.get()?))),
+            8 => Ok(Message::Tx(Box::new(decoder.get()?))),
+            9 => Ok(Message::GetHeaders(get_strings(decoder, MAX_LOCATOR)?)),
+            10 => {
+                let headers: Vec<BlockHeader> = decoder.get_seq()?;
+                if headers.len() > MAX_HEADERS {
+                    return Err(DecodeError::Invalid(format!("{} headers, at most {} allowed", headers.len(), MAX_HEADERS)));
+                }
+                Ok(Message::Headers(headers))
+            }
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+// Most hashes in a `GetHeaders` locator, which grows logarithmically with the chain
+const MAX_LOCATOR: usize = 100;
+
+fn put_strings(encoder: &mut Encoder, values: &[String]) {
+    encoder.put_u32(values.len() as u32);
+    for value in values {
+        encoder.put_str(value);
+    }
+}
+
+fn get_inventory(decoder: &mut Decoder<'_>) -> Result<Vec<Inventory>, DecodeError> {
+    let items: Vec<Inventory> = decoder.get_seq()?;
+    if items.len() > MAX_INVENTORY {
+        return Err(DecodeError::Invalid(format!("{} inventory items, at most {} allowed", items.len(), MAX_INVENTORY)));
+    }
+    Ok(items)
+}
+
+fn get_strings(decoder: &mut Decoder<'_>, max: usize) -> Result<Vec<String>, DecodeError> {
+    let len = decoder.get_u32()? as usize;
+    if len > max {
+        return Err(DecodeError::Invalid(format!("{} strings, at most {} allowed", len, max)));
+    }
+    (0..len).map(|_| decoder.get_string()).collect()
+}
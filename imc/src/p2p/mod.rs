@@ -0,0 +1,6 @@
+//! Peer-to-peer networking: the wire [`message`]s, the [`node`] that keeps connections to
+//! peers, and the header-first block download in [`sync`].
+
+pub mod message;
+pub mod node;
+pub mod sync;
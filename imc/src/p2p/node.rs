@@ -0,0 +1,671 @@
+//! A node that gossips blocks and transactions with its peers over TCP.
+//!
+//! Every connection starts with a handshake: both sides send a [`Version`] and answer the
+//! other's with [`Message::Verack`]. Peers on another network, that is with a different
+//! chain id or genesis block, or speaking another protocol version are disconnected. A node
+//! asks its outbound peers for the addresses of their peers and connects to those until it
+//! has [`NodeConfig::max_peers`] peers. Connections still in the handshake count towards
+//! that limit, and inbound connections past it are refused.
+//!
+//! New blocks and transactions are announced with [`Message::Inv`]. A peer asks for the ones
+//! it lacks with [`Message::GetData`], checks them against its own chain and announces the
+//! ones it accepts to its other peers. Each connection remembers what the other side is known
+//! to have, so nothing is announced twice over it.
+//!
+//! A node that is behind its peers catches up through [`sync`](crate::p2p::sync). Peers
+//! that send invalid headers or blocks are disconnected and banned for
+//! [`NodeConfig::ban_duration`], by the address they connected from and the one they listen
+//! on. The chain is flushed as blocks are added and when the node shuts down.
+
+use crate::block::Block;
+use crate::block_tree::BlockStatus;
+use crate::blockchain::Blockchain;
+use crate::error::{ChainError, TxError};
+use crate::p2p::message::{read_message, write_message, Inventory, Message, Version, MAX_ADDRESSES, MAX_HEADERS, PROTOCOL_VERSION};
+use crate::p2p::sync::{headers_after, is_peer_fault, HeaderSync, Outcome, SyncStatus};
+use crate::transaction::{Transaction, TxId};
+use log::{debug, info, warn};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PORT: u16 = 9333;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const SYNC_INTERVAL: Duration = Duration::from_millis(100); // Between checks for requests that timed out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1); // Least time between writes of the chain
+const MAX_KNOWN_INVENTORY: usize = 50_000; // Per peer; the set is cleared when it grows past this
+
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub listen_addr: SocketAddr, // Port 0 picks a free port, see `Node::local_addr`
+    pub seeds: Vec<SocketAddr>, // Connected to on start
+    pub max_peers: usize,
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration, // How long a peer has to answer a request for headers or blocks
+    pub ban_duration: Duration,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            listen_addr: SocketAddr::from(([0, 0, 0, 0], DEFAULT_PORT)),
+            seeds: vec![],
+            max_peers: 16,
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(20),
+            ban_duration: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// A connected peer, as returned by [`Node::peers`].
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub addr: SocketAddr,
+    pub outbound: bool, // Whether this node opened the connection
+    pub version: Version, // What the peer sent in the handshake
+}
+
+pub struct Node {
+    shared: Arc<Shared>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+struct Shared {
+    chain: Arc<Mutex<Blockchain>>,
+    config: NodeConfig,
+    local_addr: SocketAddr,
+    nonce: u64,
+    peers: Mutex<HashMap<u64, Arc<Peer>>>, // By the nonce of the peer's `Version`
+    // Connections still in the handshake, with their socket once there is one so that
+    // shutdown can close it
+    connecting: Mutex<HashMap<SocketAddr, Option<TcpStream>>>, // Outbound
+    accepting: Mutex<HashMap<SocketAddr, Option<TcpStream>>>, // Inbound
+    peer_threads: Mutex<Vec<JoinHandle<()>>>, // One per connection, handshaking or not
+    addresses: Mutex<HashSet<SocketAddr>>, // Listening addresses of every peer heard of
+    banned: Mutex<HashMap<SocketAddr, Instant>>, // Until when
+    sync: Mutex<HeaderSync>, // Locked before `chain` when both are needed
+    unflushed: AtomicBool, // Whether blocks were added since the chain was last flushed
+    shutdown: AtomicBool,
+}
+
+struct Peer {
+    addr: SocketAddr,
+    listen_addr: Option<SocketAddr>,
+    outbound: bool,
+    version: Version,
+    stream: Mutex<TcpStream>, // For writing; the connection's thread reads from a clone
+    known: Mutex<HashSet<Inventory>>, // Items the peer has sent or been sent
+}
+
+impl Node {
+    /// Starts listening on `config.listen_addr` and connects to the seeds. Blocks and
+    /// transactions received from peers are submitted to `chain`.
+    pub fn start(config: NodeConfig, chain: Arc<Mutex<Blockchain>>) -> io::Result<Node> {
+        let listener = TcpListener::bind(config.listen_addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let seeds = config.seeds.clone();
+        let sync = HeaderSync::new(config.request_timeout);
+        let shared = Arc::new(Shared {
+            chain,
+            config,
+            local_addr,
+            nonce: RandomState::new().build_hasher().finish(),
+            peers: Mutex::new(HashMap::new()),
+            connecting: Mutex::new(HashMap::new()),
+            accepting: Mutex::new(HashMap::new()),
+            peer_threads: Mutex::new(Vec::new()),
+            addresses: Mutex::new(HashSet::new()),
+            banned: Mutex::new(HashMap::new()),
+            sync: Mutex::new(sync),
+            unflushed: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+        });
+        info!("Listening for peers on {}", local_addr);
+
+        let accepting = Arc::clone(&shared);
+        let syncing = Arc::clone(&shared);
+        let threads = vec![
+            thread::spawn(move || accepting.accept_loop(listener)),
+            thread::spawn(move || syncing.sync_loop()),
+        ];
+        for seed in seeds {
+            shared.connect(seed);
+        }
+        Ok(Node { shared, threads: Mutex::new(threads) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.shared.local_addr
+    }
+
+    pub fn chain(&self) -> &Arc<Mutex<Blockchain>> {
+        &self.shared.chain
+    }
+
+    /// Opens a connection to `addr` in the background.
+    pub fn connect(&self, addr: SocketAddr) {
+        self.shared.connect(addr);
+    }
+
+    /// Peers that completed the handshake.
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        self.shared.peers.lock().unwrap().values()
+            .map(|peer| PeerInfo { addr: peer.addr, outbound: peer.outbound, version: peer.version.clone() })
+            .collect()
+    }
+
+    pub fn peer_count(&self) -> usize {
+        self.shared.peers.lock().unwrap().len()
+    }
+
+    pub fn is_banned(&self, addr: SocketAddr) -> bool {
+        self.shared.is_banned(addr)
+    }
+
+    pub fn sync_status(&self) -> SyncStatus {
+        let sync = self.shared.sync.lock().unwrap();
+        sync.status(&self.shared.chain.lock().unwrap())
+    }
+
+    /// Submits a block, typically one mined locally, and announces it if the chain accepts it.
+    pub fn submit_block(&self, block: Block) -> Result<BlockStatus, ChainError> {
+        self.shared.accept_block(block)
+    }
+
+    /// Adds a transaction to the mempool and announces it if the chain accepts it.
+    pub fn submit_transaction(&self, transaction: Transaction) -> Result<TxId, TxError> {
+        self.shared.accept_transaction(transaction)
+    }
+
+    /// Announces an item already in the chain or mempool to every peer not known to have it.
+    pub fn announce(&self, item: Inventory) {
+        self.shared.relay(item);
+    }
+
+    /// Stops accepting connections, disconnects every peer, waits for their threads and
+    /// flushes the chain.
+    pub fn shutdown(&self) {
+        if self.shared.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for peer in self.shared.peers.lock().unwrap().values() {
+            let _ = peer.stream.lock().unwrap().shutdown(Shutdown::Both);
+        }
+        for pending in [&self.shared.connecting, &self.shared.accepting] {
+            for stream in pending.lock().unwrap().values().flatten() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+        for thread in self.threads.lock().unwrap().drain(..) {
+            let _ = thread.join();
+        }
+
+        // The accept loop is stopped, but a peer thread may still have been spawning another
+        loop {
+            let threads = std::mem::take(&mut *self.shared.peer_threads.lock().unwrap());
+            if threads.is_empty() {
+                break;
+            }
+            for thread in threads {
+                let _ = thread.join();
+            }
+        }
+        self.shared.flush();
+        info!("Stopped listening on {}", self.shared.local_addr);
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Shared {
+    fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn accept_loop(self: Arc<Self>, listener: TcpListener) {
+        while !self.is_shutting_down() {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    // Connections still in the handshake count too, or peers that never
+                    // finish it could open any number of them
+                    if self.connection_count() >= self.config.max_peers {
+                        debug!("Refusing connection from {}: too many peers", addr);
+                        continue;
+                    }
+                    self.accepting.lock().unwrap().insert(addr, None);
+                    let shared = Arc::clone(&self);
+                    self.spawn_peer_thread(move || shared.run_peer(stream, addr, false));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+                Err(e) => {
+                    warn!("Failed to accept a connection: {}", e);
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn sync_loop(self: Arc<Self>) {
+        let mut last_flush = Instant::now();
+        while !self.is_shutting_down() {
+            thread::sleep(SYNC_INTERVAL);
+            self.with_sync(|sync, chain, out| sync.tick(chain, out));
+            if last_flush.elapsed() >= FLUSH_INTERVAL {
+                self.flush();
+                last_flush = Instant::now();
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if self.unflushed.swap(false, Ordering::SeqCst) {
+            if let Err(e) = self.chain.lock().unwrap().flush() {
+                warn!("Failed to save chain data: {}", e);
+            }
+        }
+    }
+
+    // Runs a sync step, then does what it asks for once the locks are released
+    fn with_sync<F: FnOnce(&mut HeaderSync, &mut Blockchain, &mut Outcome)>(&self, step: F) {
+        let mut outcome = Outcome::default();
+        {
+            let mut sync = self.sync.lock().unwrap();
+            let mut chain = self.chain.lock().unwrap();
+            step(&mut sync, &mut chain, &mut outcome);
+        }
+
+        let peers = self.peers.lock().unwrap().clone();
+        for (id, message) in outcome.messages {
+            if let Some(peer) = peers.get(&id) {
+                if let Err(e) = peer.send(&message) {
+                    debug!("Failed to send {} to {}: {}", message.command(), peer.addr, e);
+                }
+            }
+        }
+        for (id, reason) in outcome.misbehaving {
+            if let Some(peer) = peers.get(&id) {
+                self.ban(peer, &reason);
+            }
+        }
+        if outcome.connected > 0 {
+            self.unflushed.store(true, Ordering::SeqCst);
+        }
+        if outcome.caught_up {
+            let tip = self.chain.lock().unwrap().blocks.last().unwrap().hash.clone();
+            self.relay(Inventory::Block(tip));
+        }
+    }
+
+    fn ban(&self, peer: &Peer, reason: &str) {
+        warn!("Banning peer {}: {}", peer.addr, reason);
+        let until = Instant::now() + self.config.ban_duration;
+        {
+            let mut banned = self.banned.lock().unwrap();
+            banned.insert(peer.addr, until);
+            if let Some(listen_addr) = peer.listen_addr {
+                banned.insert(listen_addr, until);
+                self.addresses.lock().unwrap().remove(&listen_addr);
+            }
+        }
+        let _ = peer.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+
+    fn is_banned(&self, addr: SocketAddr) -> bool {
+        self.banned.lock().unwrap().get(&addr).is_some_and(|&until| until > Instant::now())
+    }
+
+    // Connects unless already connected or connecting to `addr`
+    fn connect(self: &Arc<Self>, addr: SocketAddr) {
+        if self.is_shutting_down() || addr == self.local_addr || self.is_banned(addr) || self.is_connected_to(addr) {
+            return;
+        }
+        {
+            let mut connecting = self.connecting.lock().unwrap();
+            if connecting.contains_key(&addr) {
+                return;
+            }
+            connecting.insert(addr, None);
+        }
+        let shared = Arc::clone(self);
+        self.spawn_peer_thread(move || {
+            match TcpStream::connect_timeout(&addr, shared.config.connect_timeout) {
+                Ok(stream) => shared.run_peer(stream, addr, true),
+                Err(e) => {
+                    debug!("Failed to connect to {}: {}", addr, e);
+                    shared.connecting.lock().unwrap().remove(&addr);
+                }
+            }
+        });
+    }
+
+    // Runs a connection's thread, keeping its handle for `Node::shutdown` to join
+    fn spawn_peer_thread<F: FnOnce() + Send + 'static>(&self, run: F) {
+        let mut threads = self.peer_threads.lock().unwrap();
+        threads.retain(|thread| !thread.is_finished());
+        threads.push(thread::spawn(run));
+    }
+
+    // Peers plus connections in either direction that are still in the handshake
+    fn connection_count(&self) -> usize {
+        self.peers.lock().unwrap().len() + self.connecting.lock().unwrap().len() + self.accepting.lock().unwrap().len()
+    }
+
+    fn is_connected_to(&self, addr: SocketAddr) -> bool {
+        self.peers.lock().unwrap().values().any(|peer| peer.addr == addr || peer.listen_addr == Some(addr))
+    }
+
+    fn run_peer(self: Arc<Self>, stream: TcpStream, addr: SocketAddr, outbound: bool) {
+        let handshake = self.handshake(stream, addr, outbound);
+        if outbound {
+            self.connecting.lock().unwrap().remove(&addr);
+        } else {
+            self.accepting.lock().unwrap().remove(&addr);
+        }
+        let (peer, mut reader) = match handshake {
+            Ok(Some(connected)) => connected,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Handshake with {} failed: {}", addr, e);
+                return;
+            }
+        };
+        info!("Connected to peer {} at height {}", addr, peer.version.best_height);
+        if outbound {
+            let _ = peer.send(&Message::GetAddr);
+        }
+        let (id, best_height) = (peer.version.nonce, peer.version.best_height);
+        self.with_sync(|sync, chain, out| sync.peer_connected(id, best_height, chain, out));
+
+        while !self.is_shutting_down() {
+            let result = read_message(&mut reader).and_then(|message| self.handle(&peer, message));
+            if let Err(e) = result {
+                if e.kind() == io::ErrorKind::InvalidData {
+                    warn!("Disconnecting peer {}: {}", addr, e);
+                }
+                break;
+            }
+        }
+
+        let _ = peer.stream.lock().unwrap().shutdown(Shutdown::Both);
+        {
+            let mut peers = self.peers.lock().unwrap();
+            if peers.get(&id).is_some_and(|registered| Arc::ptr_eq(registered, &peer)) {
+                peers.remove(&id);
+            }
+        }
+        self.with_sync(|sync, chain, out| sync.peer_disconnected(id, chain, out));
+        info!("Disconnected from peer {}", addr);
+    }
+
+    // Exchanges versions and registers the peer. `None` means the connection was dropped
+    // without error: it leads back to this node or duplicates an existing one.
+    fn handshake(&self, mut stream: TcpStream, addr: SocketAddr, outbound: bool) -> io::Result<Option<(Arc<Peer>, BufReader<TcpStream>)>> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        // Shutdown closes the sockets it finds registered after setting its flag, so one
+        // registered too late sees the flag here instead
+        let pending = if outbound { &self.connecting } else { &self.accepting };
+        pending.lock().unwrap().insert(addr, Some(stream.try_clone()?));
+        if self.is_shutting_down() {
+            return Ok(None);
+        }
+
+        write_message(&mut stream, &Message::Version(self.version()))?;
+        let version = match read_message(&mut reader)? {
+            Message::Version(version) => version,
+            message => return Err(invalid_data(format!("expected version, got {}", message.command()))),
+        };
+        if version.nonce == self.nonce {
+            debug!("Dropping connection to {}, which is this node", addr);
+            return Ok(None);
+        }
+        self.check_version(&version)?;
+        write_message(&mut stream, &Message::Verack)?;
+        match read_message(&mut reader)? {
+            Message::Verack => {}
+            message => return Err(invalid_data(format!("expected verack, got {}", message.command()))),
+        }
+        stream.set_read_timeout(None)?;
+
+        let listen_addr = u16::try_from(version.listen_port).ok()
+            .filter(|&port| port != 0)
+            .map(|port| SocketAddr::new(addr.ip(), port));
+        if self.is_banned(addr) || listen_addr.is_some_and(|listen_addr| self.is_banned(listen_addr)) {
+            debug!("Dropping connection to banned peer {}", addr);
+            return Ok(None);
+        }
+        let peer = Arc::new(Peer {
+            addr,
+            listen_addr,
+            outbound,
+            version,
+            stream: Mutex::new(stream),
+            known: Mutex::new(HashSet::new()),
+        });
+        {
+            let mut peers = self.peers.lock().unwrap();
+            if peers.contains_key(&peer.version.nonce) {
+                debug!("Dropping duplicate connection to {}", addr);
+                return Ok(None);
+            }
+            if peers.len() >= self.config.max_peers {
+                debug!("Dropping connection to {}: too many peers", addr);
+                return Ok(None);
+            }
+            peers.insert(peer.version.nonce, Arc::clone(&peer));
+        }
+        if let Some(listen_addr) = listen_addr {
+            self.addresses.lock().unwrap().insert(listen_addr);
+        }
+        Ok(Some((peer, reader)))
+    }
+
+    fn version(&self) -> Version {
+        let chain = self.chain.lock().unwrap();
+        let tip = chain.blocks.last().unwrap();
+        Version {
+            protocol: PROTOCOL_VERSION,
+            chain_id: chain.chain_id,
+            genesis_hash: chain.blocks[0].hash.clone(),
+            best_height: tip.header.index,
+            best_hash: tip.hash.clone(),
+            listen_port: self.local_addr.port() as u32,
+            nonce: self.nonce,
+        }
+    }
+
+    fn check_version(&self, version: &Version) -> io::Result<()> {
+        if version.protocol != PROTOCOL_VERSION {
+            return Err(invalid_data(format!("peer speaks protocol {}, expected {}", version.protocol, PROTOCOL_VERSION)));
+        }
+        let chain = self.chain.lock().unwrap();
+        if version.chain_id != chain.chain_id || version.genesis_hash != chain.blocks[0].hash {
+            return Err(invalid_data(format!("peer is on chain {} with genesis {}, expected chain {} with genesis {}",
+                version.chain_id, version.genesis_hash, chain.chain_id, chain.blocks[0].hash)));
+        }
+        Ok(())
+    }
+
+    // Errors of kind `InvalidData` mean the peer broke the protocol and is disconnected
+    fn handle(self: &Arc<Self>, peer: &Arc<Peer>, message: Message) -> io::Result<()> {
+        match message {
+            Message::Version(_) | Message::Verack => Err(invalid_data("handshake repeated".to_string())),
+            Message::GetAddr => peer.send(&Message::Addr(self.addresses_for(peer))),
+            Message::Addr(addresses) => {
+                self.learn_addresses(&addresses);
+                Ok(())
+            }
+            Message::Inv(items) => {
+                peer.add_known(items.iter().cloned());
+                let wanted: Vec<Inventory> = {
+                    let chain = self.chain.lock().unwrap();
+                    items.into_iter().filter(|item| !has_item(&chain, item)).collect()
+                };
+                if wanted.is_empty() {
+                    Ok(())
+                } else {
+                    peer.send(&Message::GetData(wanted))
+                }
+            }
+            Message::GetData(items) => {
+                // Each item is looked up and sent on its own, so neither the chain lock is
+                // held nor the requested blocks are buffered while writing to the peer
+                let mut missing = vec![];
+                for item in items {
+                    let found = get_item(&self.chain.lock().unwrap(), &item);
+                    match found {
+                        Some(message) => peer.send(&message)?,
+                        None => missing.push(item),
+                    }
+                }
+                if missing.is_empty() {
+                    Ok(())
+                } else {
+                    peer.send(&Message::NotFound(missing))
+                }
+            }
+            Message::NotFound(items) => {
+                let id = peer.version.nonce;
+                self.with_sync(|sync, chain, out| sync.not_found(id, &items, chain, out));
+                Ok(())
+            }
+            Message::Block(block) => {
+                peer.add_known([Inventory::Block(block.hash.clone())]);
+                let id = peer.version.nonce;
+                if self.sync.lock().unwrap().wants(&block.hash) {
+                    self.with_sync(|sync, chain, out| sync.block_received(id, *block, chain, out));
+                    return Ok(());
+                }
+                match self.accept_block(*block) {
+                    // Blocks were missed; sync finds out which
+                    Err(ChainError::UnknownParent { .. }) => self.with_sync(|sync, chain, out| sync.request_headers(id, chain, out)),
+                    Err(e) if is_peer_fault(&e) => self.ban(peer, &format!("sent an invalid block: {}", e)),
+                    _ => {}
+                }
+                Ok(())
+            }
+            Message::Tx(transaction) => {
+                peer.add_known([Inventory::Tx(transaction.hash())]);
+                let _ = self.accept_transaction(*transaction);
+                Ok(())
+            }
+            Message::GetHeaders(locator) => {
+                let headers = headers_after(&self.chain.lock().unwrap(), &locator, MAX_HEADERS);
+                peer.send(&Message::Headers(headers))
+            }
+            Message::Headers(headers) => {
+                let id = peer.version.nonce;
+                self.with_sync(|sync, chain, out| sync.headers_received(id, headers, chain, out));
+                Ok(())
+            }
+        }
+    }
+
+    fn accept_block(&self, block: Block) -> Result<BlockStatus, ChainError> {
+        let hash = block.hash.clone();
+        let result = self.chain.lock().unwrap().submit_block(block);
+        match &result {
+            Ok(_) => {
+                self.unflushed.store(true, Ordering::SeqCst);
+                self.relay(Inventory::Block(hash));
+            }
+            Err(ChainError::DuplicateBlock { .. }) => {}
+            Err(e) => debug!("Not relaying block {}: {}", hash, e),
+        }
+        result
+    }
+
+    fn accept_transaction(&self, transaction: Transaction) -> Result<TxId, TxError> {
+        let result = self.chain.lock().unwrap().create_transaction(transaction);
+        if let Ok(tx_id) = &result {
+            self.relay(Inventory::Tx(tx_id.clone()));
+        }
+        result
+    }
+
+    fn relay(&self, item: Inventory) {
+        let peers: Vec<Arc<Peer>> = self.peers.lock().unwrap().values().cloned().collect();
+        for peer in peers {
+            if peer.add_known([item.clone()]) {
+                if let Err(e) = peer.send(&Message::Inv(vec![item.clone()])) {
+                    debug!("Failed to announce to {}: {}", peer.addr, e);
+                }
+            }
+        }
+    }
+
+    // Every listening address heard of except the requester's own
+    fn addresses_for(&self, peer: &Peer) -> Vec<String> {
+        self.addresses.lock().unwrap().iter()
+            .filter(|&&addr| Some(addr) != peer.listen_addr)
+            .take(MAX_ADDRESSES)
+            .map(|addr| addr.to_string())
+            .collect()
+    }
+
+    fn learn_addresses(self: &Arc<Self>, addresses: &[String]) {
+        for addr in addresses.iter().filter_map(|addr| addr.parse::<SocketAddr>().ok()) {
+            let new = self.addresses.lock().unwrap().insert(addr);
+            let room = self.connection_count() < self.config.max_peers;
+            if new && room {
+                self.connect(addr);
+            }
+        }
+    }
+}
+
+impl Peer {
+    fn send(&self, message: &Message) -> io::Result<()> {
+        write_message(&mut *self.stream.lock().unwrap(), message)
+    }
+
+    // Returns whether any of `items` was not known yet
+    fn add_known<I: IntoIterator<Item = Inventory>>(&self, items: I) -> bool {
+        let mut known = self.known.lock().unwrap();
+        if known.len() > MAX_KNOWN_INVENTORY {
+            known.clear();
+        }
+        let mut added = false;
+        for item in items {
+            added |= known.insert(item);
+        }
+        added
+    }
+}
+
+fn has_item(chain: &Blockchain, item: &Inventory) -> bool {
+    match item {
+        Inventory::Block(hash) => chain.block_tree().contains(hash) || chain.block_tree().is_invalid(hash),
+        Inventory::Tx(tx_id) => chain.mempool.contains(tx_id),
+    }
+}
+
+fn get_item(chain: &Blockchain, item: &Inventory) -> Option<Message> {
+    match item {
+        Inventory::Block(hash) => chain.block_tree().get(hash).map(|node| Message::Block(Box::new(node.block.clone()))),
+        Inventory::Tx(tx_id) => chain.mempool.get(tx_id).map(|entry| Message::Tx(Box::new(entry.transaction.clone()))),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
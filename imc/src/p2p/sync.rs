@@ -0,0 +1,454 @@
+//! Initial block download: bringing a node that is behind up to its peers' chain.
+//!
+//! Sync is headers first. A node asks one peer at a time for the headers past its best
+//! known header and checks each one against the rules a header alone decides, proof of
+//! work and target included, before fetching anything else. The blocks of the best header
+//! chain are then requested from every peer that has them, at most
+//! [`MAX_BLOCKS_PER_PEER`] at a time each and no more than [`DOWNLOAD_WINDOW`] past the
+//! tip, and submitted to the chain in order as they arrive. A request that is not answered
+//! in time is given to another peer. Peers that send invalid headers or blocks are reported
+//! so that the node can ban them.
+//!
+//! None of this is stored. A node that restarts resumes from the chain it flushed and asks
+//! again for the headers past it.
+
+use crate::block::{Block, BlockHeader};
+use crate::block_tree::BlockTree;
+use crate::blockchain::{now, Blockchain};
+use crate::consensus::HeaderSource;
+use crate::error::ChainError;
+use crate::p2p::message::{Inventory, Message, MAX_HEADERS};
+use bigdecimal::num_bigint::BigUint;
+use log::{debug, info};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How far past the tip blocks are requested.
+pub const DOWNLOAD_WINDOW: usize = 512;
+
+/// Most blocks requested from one peer at a time.
+pub const MAX_BLOCKS_PER_PEER: usize = 16;
+
+// Hashes at the start of a locator that step back one block at a time; later ones double
+// the step
+const DENSE_LOCATOR: usize = 10;
+
+/// Progress of the sync, as returned by [`Node::sync_status`](crate::p2p::node::Node::sync_status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub height: u64, // Of the chain's tip
+    pub best_header_height: u64, // Of the best checked header, at least `height`
+    pub blocks_in_flight: usize,
+    pub blocks_downloaded: usize, // Received but waiting for an earlier block
+}
+
+struct HeaderNode {
+    header: BlockHeader,
+    chain_work: BigUint,
+}
+
+/// Checked headers of blocks that are not in the block tree yet.
+#[derive(Default)]
+pub struct HeaderChain {
+    nodes: HashMap<String, HeaderNode>,
+    invalid: HashSet<String>, // Headers whose block turned out to be invalid, and their descendants
+    best: VecDeque<String>, // The branch with the most work, from its first header without a block
+}
+
+// Looks headers up among the checked headers, then in the block tree
+struct Lookup<'a> {
+    nodes: &'a HashMap<String, HeaderNode>,
+    tree: &'a BlockTree,
+}
+
+impl HeaderSource for Lookup<'_> {
+    fn header(&self, hash: &str) -> Option<&BlockHeader> {
+        self.nodes.get(hash).map(|node| &node.header).or_else(|| self.tree.header(hash))
+    }
+}
+
+impl HeaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    /// Hash of the header with the most work, which is the chain's tip if no header has more.
+    pub fn best_hash(&self, chain: &Blockchain) -> String {
+        self.best.back().cloned().unwrap_or_else(|| chain.blocks.last().unwrap().hash.clone())
+    }
+
+    pub fn best_height(&self, chain: &Blockchain) -> u64 {
+        self.best.back().map_or_else(|| chain.blocks.last().unwrap().header.index, |hash| self.nodes[hash].header.index)
+    }
+
+    /// Hashes of the blocks to download, in the order they connect.
+    pub fn missing(&self) -> impl Iterator<Item = &String> {
+        self.best.iter()
+    }
+
+    /// Checks `headers` in order and keeps the valid ones, stopping at the first invalid one.
+    /// Returns how many were new.
+    pub fn add(&mut self, headers: &[BlockHeader], chain: &Blockchain) -> Result<usize, ChainError> {
+        let tree = chain.block_tree();
+        let mut best_work = self.best_work(chain);
+        let mut new_best = None;
+        let mut added = 0;
+        let mut result = Ok(());
+        for header in headers {
+            let hash = header.calculate_hash();
+            if self.nodes.contains_key(&hash) || tree.contains(&hash) {
+                continue;
+            }
+            if let Some(ancestor) = [&hash, &header.previous_hash].into_iter().find(|hash| self.invalid.contains(*hash) || tree.is_invalid(hash)) {
+                self.invalid.insert(hash.clone());
+                result = Err(ChainError::InvalidAncestor { hash: hash.clone(), ancestor: ancestor.clone() });
+                break;
+            }
+            let lookup = Lookup { nodes: &self.nodes, tree };
+            if let Err(e) = chain.consensus.check_header(header, &hash, &lookup, now()) {
+                result = Err(e);
+                break;
+            }
+
+            let parent_work = match self.nodes.get(&header.previous_hash) {
+                Some(parent) => parent.chain_work.clone(),
+                None => tree.get(&header.previous_hash).unwrap().chain_work.clone(),
+            };
+            let chain_work = parent_work + header.work();
+            if chain_work > best_work {
+                best_work = chain_work.clone();
+                new_best = Some(hash.clone());
+            }
+            self.nodes.insert(hash, HeaderNode { header: header.clone(), chain_work });
+            added += 1;
+        }
+        if let Some(hash) = new_best {
+            self.set_best(&hash);
+        }
+        result.map(|()| added)
+    }
+
+    /// Hashes from the best header back to genesis, one block apart at first and then
+    /// exponentially further apart, so a peer can find where its chain leaves this one.
+    pub fn locator(&self, chain: &Blockchain) -> Vec<String> {
+        let lookup = Lookup { nodes: &self.nodes, tree: chain.block_tree() };
+        let mut locator = vec![];
+        let mut hash = self.best_hash(chain);
+        let mut step = 1;
+        while let Some(mut header) = lookup.header(&hash) {
+            locator.push(hash.clone());
+            if locator.len() >= DENSE_LOCATOR {
+                step *= 2;
+            }
+            for _ in 0..step {
+                if header.index == 0 {
+                    break;
+                }
+                hash = header.previous_hash.clone();
+                header = match lookup.header(&hash) {
+                    Some(header) => header,
+                    None => break,
+                };
+            }
+            if header.index == 0 {
+                break;
+            }
+        }
+        let genesis = &chain.blocks[0].hash;
+        if locator.last() != Some(genesis) {
+            locator.push(genesis.clone());
+        }
+        locator
+    }
+
+    /// Forgets `hash` and every header built on it, and never accepts them again.
+    pub fn mark_invalid(&mut self, hash: &str, chain: &Blockchain) {
+        let mut pending = vec![hash.to_string()];
+        while let Some(hash) = pending.pop() {
+            pending.extend(
+                self.nodes.values()
+                    .filter(|node| node.header.previous_hash == hash)
+                    .map(|node| node.header.calculate_hash()),
+            );
+            self.nodes.remove(&hash);
+            self.invalid.insert(hash);
+        }
+
+        let best = self.nodes.iter().max_by(|(_, a), (_, b)| a.chain_work.cmp(&b.chain_work)).map(|(hash, node)| (hash.clone(), node.chain_work.clone()));
+        match best {
+            Some((hash, chain_work)) if chain_work > chain.chain_work() => self.set_best(&hash),
+            _ => self.best.clear(),
+        }
+    }
+
+    /// Drops headers whose blocks have reached the block tree. Once the chain has caught up
+    /// with the best header, headers of branches with less work are dropped too.
+    pub fn prune(&mut self, chain: &Blockchain) {
+        while let Some(hash) = self.best.front() {
+            if !chain.block_tree().contains(hash) {
+                break;
+            }
+            self.nodes.remove(hash);
+            self.best.pop_front();
+        }
+        if self.best.back().is_some_and(|hash| self.nodes[hash].chain_work <= chain.chain_work()) {
+            self.best.clear();
+        }
+        if self.best.is_empty() {
+            self.nodes.clear();
+        }
+    }
+
+    fn best_work(&self, chain: &Blockchain) -> BigUint {
+        self.best.back().map_or_else(|| chain.chain_work(), |hash| self.nodes[hash].chain_work.clone())
+    }
+
+    fn set_best(&mut self, hash: &str) {
+        self.best.clear();
+        let mut hash = hash.to_string();
+        while let Some(node) = self.nodes.get(&hash) {
+            self.best.push_front(hash);
+            hash = node.header.previous_hash.clone();
+        }
+    }
+}
+
+/// What a sync step wants the node to do.
+#[derive(Debug, Default)]
+pub(crate) struct Outcome {
+    pub messages: Vec<(u64, Message)>, // By peer
+    pub misbehaving: Vec<(u64, String)>, // Peers to ban, and why
+    pub connected: usize, // Blocks submitted to the chain
+    pub caught_up: bool, // Blocks were submitted and none are missing any more
+}
+
+/// Download state, shared by every connection of a node. Peers are identified by the nonce
+/// of their handshake.
+pub(crate) struct HeaderSync {
+    headers: HeaderChain,
+    timeout: Duration, // How long a peer has to answer a request
+    peers: HashMap<u64, u64>, // Best height each peer is known to have
+    header_request: Option<(u64, Instant)>,
+    in_flight: HashMap<String, (u64, Instant)>, // Requested blocks, by whom and when
+    downloaded: HashMap<String, (Block, u64)>, // Blocks waiting for their parent, and who sent them
+}
+
+impl HeaderSync {
+    pub fn new(timeout: Duration) -> Self {
+        HeaderSync {
+            headers: HeaderChain::new(),
+            timeout,
+            peers: HashMap::new(),
+            header_request: None,
+            in_flight: HashMap::new(),
+            downloaded: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self, chain: &Blockchain) -> SyncStatus {
+        SyncStatus {
+            height: chain.blocks.last().unwrap().header.index,
+            best_header_height: self.headers.best_height(chain),
+            blocks_in_flight: self.in_flight.len(),
+            blocks_downloaded: self.downloaded.len(),
+        }
+    }
+
+    /// Whether `hash` is a block sync is waiting for, which should be handed to
+    /// [`HeaderSync::block_received`] rather than submitted directly.
+    pub fn wants(&self, hash: &str) -> bool {
+        self.headers.contains(hash) && !self.downloaded.contains_key(hash)
+    }
+
+    pub fn peer_connected(&mut self, peer: u64, best_height: u64, chain: &Blockchain, out: &mut Outcome) {
+        self.peers.insert(peer, best_height);
+        self.request_headers_if_behind(chain, out);
+        self.schedule(chain, out);
+    }
+
+    pub fn peer_disconnected(&mut self, peer: u64, chain: &Blockchain, out: &mut Outcome) {
+        self.peers.remove(&peer);
+        self.in_flight.retain(|_, (from, _)| *from != peer);
+        if self.header_request.is_some_and(|(from, _)| from == peer) {
+            self.header_request = None;
+        }
+        self.request_headers_if_behind(chain, out);
+        self.schedule(chain, out);
+    }
+
+    /// Asks `peer` for headers past the best one, typically because it announced a block
+    /// that does not connect to anything known.
+    pub fn request_headers(&mut self, peer: u64, chain: &Blockchain, out: &mut Outcome) {
+        if self.header_request.is_none() {
+            self.header_request = Some((peer, Instant::now()));
+            out.messages.push((peer, Message::GetHeaders(self.headers.locator(chain))));
+        }
+    }
+
+    pub fn headers_received(&mut self, peer: u64, headers: Vec<BlockHeader>, chain: &mut Blockchain, out: &mut Outcome) {
+        if self.header_request.is_some_and(|(from, _)| from == peer) {
+            self.header_request = None;
+        }
+        if let Some(last) = headers.last() {
+            let height = self.peers.entry(peer).or_insert(0);
+            *height = (*height).max(last.index);
+        }
+
+        match self.headers.add(&headers, chain) {
+            Ok(added) => {
+                debug!("Received {} headers from peer {}, {} new", headers.len(), peer, added);
+                if headers.len() == MAX_HEADERS {
+                    self.request_headers(peer, chain, out);
+                }
+            }
+            Err(e) if is_peer_fault(&e) => out.misbehaving.push((peer, format!("sent an invalid header: {}", e))),
+            Err(e) => debug!("Ignoring headers from peer {}: {}", peer, e),
+        }
+        self.request_headers_if_behind(chain, out);
+        self.schedule(chain, out);
+    }
+
+    pub fn block_received(&mut self, peer: u64, block: Block, chain: &mut Blockchain, out: &mut Outcome) {
+        let hash = block.hash.clone();
+        if self.in_flight.get(&hash).is_some_and(|(from, _)| *from == peer) {
+            self.in_flight.remove(&hash);
+        }
+        if hash != block.calculate_hash() {
+            out.misbehaving.push((peer, format!("sent block {} whose header does not hash to it", hash)));
+            return;
+        }
+        if self.headers.contains(&hash) {
+            self.downloaded.insert(hash, (block, peer));
+            self.connect_downloaded(chain, out);
+        }
+        self.schedule(chain, out);
+    }
+
+    pub fn not_found(&mut self, peer: u64, items: &[Inventory], chain: &Blockchain, out: &mut Outcome) {
+        for item in items {
+            if let Inventory::Block(hash) = item {
+                if self.in_flight.get(hash).is_some_and(|(from, _)| *from == peer) {
+                    self.in_flight.remove(hash);
+                    // The peer's chain evidently ends before this block
+                    if let (Some(node), Some(height)) = (self.headers.nodes.get(hash), self.peers.get_mut(&peer)) {
+                        *height = (*height).min(node.header.index.saturating_sub(1));
+                    }
+                }
+            }
+        }
+        self.schedule(chain, out);
+    }
+
+    /// Gives requests that timed out to other peers.
+    pub fn tick(&mut self, chain: &mut Blockchain, out: &mut Outcome) {
+        let timeout = self.timeout;
+        if self.header_request.is_some_and(|(_, at)| at.elapsed() > timeout) {
+            debug!("Header request timed out");
+            self.header_request = None;
+        }
+        let before = self.in_flight.len();
+        self.in_flight.retain(|_, (_, at)| at.elapsed() <= timeout);
+        if self.in_flight.len() < before {
+            debug!("{} block requests timed out", before - self.in_flight.len());
+        }
+        self.connect_downloaded(chain, out);
+        self.request_headers_if_behind(chain, out);
+        self.schedule(chain, out);
+    }
+
+    fn request_headers_if_behind(&mut self, chain: &Blockchain, out: &mut Outcome) {
+        let best_height = self.headers.best_height(chain);
+        let ahead = self.peers.iter().filter(|(_, &height)| height > best_height).max_by_key(|(_, &height)| height).map(|(&peer, _)| peer);
+        if let Some(peer) = ahead {
+            self.request_headers(peer, chain, out);
+        }
+    }
+
+    // Requests the next blocks of the best header chain, spreading them over the peers
+    fn schedule(&mut self, chain: &Blockchain, out: &mut Outcome) {
+        self.headers.prune(chain);
+        let mut load: HashMap<u64, usize> = self.peers.keys().map(|&peer| (peer, 0)).collect();
+        for (peer, _) in self.in_flight.values() {
+            *load.entry(*peer).or_insert(0) += 1;
+        }
+
+        let mut requests: BTreeMap<u64, Vec<Inventory>> = BTreeMap::new();
+        for hash in self.headers.missing().take(DOWNLOAD_WINDOW) {
+            if self.in_flight.contains_key(hash) || self.downloaded.contains_key(hash) {
+                continue;
+            }
+            let height = self.headers.nodes[hash].header.index;
+            let peer = load.iter()
+                .filter(|(peer, &count)| count < MAX_BLOCKS_PER_PEER && self.peers.get(peer).is_some_and(|&best| best >= height))
+                .min_by_key(|(&peer, &count)| (count, peer))
+                .map(|(&peer, _)| peer);
+            let Some(peer) = peer else {
+                continue;
+            };
+            *load.get_mut(&peer).unwrap() += 1;
+            self.in_flight.insert(hash.clone(), (peer, Instant::now()));
+            requests.entry(peer).or_default().push(Inventory::Block(hash.clone()));
+        }
+        out.messages.extend(requests.into_iter().map(|(peer, items)| (peer, Message::GetData(items))));
+    }
+
+    // Submits downloaded blocks for as long as the next one on the best header chain is there
+    fn connect_downloaded(&mut self, chain: &mut Blockchain, out: &mut Outcome) {
+        let before = out.connected;
+        loop {
+            self.headers.prune(chain);
+            let Some(hash) = self.headers.missing().next().cloned() else {
+                break;
+            };
+            let Some((block, peer)) = self.downloaded.remove(&hash) else {
+                break;
+            };
+            match chain.submit_block(block) {
+                Ok(_) | Err(ChainError::DuplicateBlock { .. }) => out.connected += 1,
+                // The header is fine, so the block is fetched again, from someone else
+                Err(e @ ChainError::InvalidMerkleRoot { .. }) => out.misbehaving.push((peer, format!("sent a block that does not match its header: {}", e))),
+                Err(e) => {
+                    if is_peer_fault(&e) {
+                        out.misbehaving.push((peer, format!("sent an invalid block: {}", e)));
+                    }
+                    self.headers.mark_invalid(&hash, chain);
+                }
+            }
+        }
+        self.downloaded.retain(|hash, _| self.headers.contains(hash));
+
+        if out.connected > before {
+            let status = self.status(chain);
+            info!("Synced to height {} of {}", status.height, status.best_header_height);
+            out.caught_up = self.headers.missing().next().is_none();
+        }
+    }
+}
+
+/// Headers of the blocks that follow the first hash of `locator` found on the active chain,
+/// or genesis if there is none.
+pub(crate) fn headers_after(chain: &Blockchain, locator: &[String], max: usize) -> Vec<BlockHeader> {
+    let fork = locator.iter()
+        .filter_map(|hash| chain.block_tree().get(hash))
+        .find(|node| chain.blocks.get(node.height as usize).is_some_and(|block| block.hash == node.block.hash))
+        .map_or(0, |node| node.height as usize);
+    chain.blocks.iter().skip(fork + 1).take(max).map(|block| block.header.clone()).collect()
+}
+
+// Whether a peer that sent something failing with `e` broke the rules, as opposed to being
+// out of date or having a clock that runs ahead
+pub(crate) fn is_peer_fault(e: &ChainError) -> bool {
+    !matches!(e, ChainError::DuplicateBlock { .. } | ChainError::UnknownParent { .. } | ChainError::TimestampTooNew { .. })
+}
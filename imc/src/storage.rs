@@ -0,0 +1,123 @@
+use crate::block::Block;
+use crate::block_tree::BlockUndo;
+use crate::contract::SmartContract;
+use crate::transaction::Transaction;
+use bigdecimal::BigDecimal;
+use common::encoding::{decode, encode};
+use common::storage::{read_json, write_atomic, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "state.json";
+const BLOCKS_DIR: &str = "blocks";
+const UNDO_DIR: &str = "undo";
+
+/// Everything besides the blocks themselves that is needed to resume a chain.
+///
+/// `tip_hash` is the last block of the active chain this state was computed from. Block and
+/// undo files are written before the state, so the state file is the commit point: blocks
+/// from an interrupted flush are loaded as side branches and never as part of the chain.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChainState {
+    pub height: u64,
+    pub tip_hash: String,
+    pub balances: HashMap<String, BigDecimal>,
+    pub miner_contributions: HashMap<String, u64>,
+    pub nonces: HashMap<String, u64>,
+    pub smart_contracts: HashMap<String, SmartContract>,
+    pub pending_transactions: Vec<Transaction>,
+}
+
+/// On-disk layout of a chain data directory:
+///
+/// ```text
+/// <root>/state.json            balances, nonces, contracts, mempool, ...
+/// <root>/blocks/<hash>.bin     every known block, active or not, canonically encoded
+/// <root>/undo/<hash>.json      how to disconnect each block of the active chain
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChainStore {
+    root: PathBuf,
+}
+
+impl ChainStore {
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(BLOCKS_DIR))?;
+        fs::create_dir_all(root.join(UNDO_DIR))?;
+        Ok(ChainStore { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn block_path(&self, hash: &str) -> PathBuf {
+        self.root.join(BLOCKS_DIR).join(format!("{}.bin", hash))
+    }
+
+    fn undo_path(&self, hash: &str) -> PathBuf {
+        self.root.join(UNDO_DIR).join(format!("{}.json", hash))
+    }
+
+    pub fn write_block(&self, block: &Block) -> io::Result<()> {
+        write_atomic(self.block_path(&block.hash), &encode(block))
+    }
+
+    /// Reads the block stored under `hash`, failing if its contents do not match that hash or
+    /// its transactions do not match the merkle root.
+    pub fn read_block(&self, hash: &str) -> io::Result<Option<Block>> {
+        let bytes = match fs::read(self.block_path(hash)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let block: Block = decode(&bytes).map_err(|e| invalid_data(format!("block {}: {}", hash, e)))?;
+        if block.hash != hash || block.calculate_hash() != hash {
+            return Err(invalid_data(format!("block file {} does not match its hash", hash)));
+        }
+        // The hash covers only the header, so the transactions are checked against its merkle root
+        if block.calculate_merkle_root() != block.header.merkle_root {
+            return Err(invalid_data(format!("block file {} does not match its merkle root", hash)));
+        }
+        Ok(Some(block))
+    }
+
+    /// Loads every stored block, in no particular order.
+    pub fn read_blocks(&self) -> io::Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        for entry in fs::read_dir(self.root.join(BLOCKS_DIR))? {
+            let path = entry?.path();
+            // Skip temporary files left by an interrupted write
+            if path.extension().and_then(|ext| ext.to_str()) != Some("bin") {
+                continue;
+            }
+            let hash = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            blocks.extend(self.read_block(hash)?);
+        }
+        Ok(blocks)
+    }
+
+    pub fn write_undo(&self, hash: &str, undo: &BlockUndo) -> io::Result<()> {
+        write_json_atomic(self.undo_path(hash), undo)
+    }
+
+    pub fn read_undo(&self, hash: &str) -> io::Result<Option<BlockUndo>> {
+        read_json(self.undo_path(hash))
+    }
+
+    pub fn write_state(&self, state: &ChainState) -> io::Result<()> {
+        write_json_atomic(self.root.join(STATE_FILE), state)
+    }
+
+    pub fn read_state(&self) -> io::Result<Option<ChainState>> {
+        read_json(self.root.join(STATE_FILE))
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
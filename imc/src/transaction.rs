@@ -0,0 +1,186 @@
+use crate::blockchain::Blockchain;
+use crate::error::TxError;
+use bigdecimal::{BigDecimal, Zero};
+use common::address::{address_from_public_key, validate_address, AddressError};
+use common::encoding::{Decode, DecodeError, Decoder, Encode, Encoder};
+use common::signature::{OptionalSerializableSignature, SerializablePublicKey, SerializableSignature};
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::fmt;
+
+/// A transaction's id: the hex SHA3-256 hash of its unsigned encoding, see [`Transaction::hash`].
+pub type TxId = String;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Transaction {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: BigDecimal,
+    pub fee: BigDecimal,
+    pub nonce: u64, // Must equal the number of transactions the sender has sent before
+    pub chain_id: u32,
+    pub sender_public_key: Option<SerializablePublicKey>, // Filled in by `sign`; must map to `sender`
+    pub signature: OptionalSerializableSignature,
+}
+
+/// Why a transaction's signature does not authorize spending from its sender address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    MissingPublicKey,
+    AddressMismatch { address: String },
+    MissingSignature,
+    InvalidSignature,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::MissingPublicKey => write!(f, "transaction does not reveal the sender's public key"),
+            SignatureError::AddressMismatch { address } => write!(f, "public key belongs to {}, not the sender", address),
+            SignatureError::MissingSignature => write!(f, "transaction is not signed"),
+            SignatureError::InvalidSignature => write!(f, "signature does not match the transaction"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Why a transaction was rejected as a replay or as out of sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    WrongChain { expected: u32, found: u32 },
+    StaleNonce { expected: u64, found: u64 },
+    DuplicateNonce { nonce: u64 },
+    NonceGap { expected: u64, found: u64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::WrongChain { expected, found } => write!(f, "transaction is for chain {}, expected chain {}", found, expected),
+            ReplayError::StaleNonce { expected, found } => write!(f, "nonce {} has already been used, next nonce is {}", found, expected),
+            ReplayError::DuplicateNonce { nonce } => write!(f, "a pending transaction already uses nonce {}", nonce),
+            ReplayError::NonceGap { expected, found } => write!(f, "nonce {} is out of order, expected {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Transaction {
+    pub fn is_well_formed(&self) -> bool {
+        self.check_well_formed().is_ok()
+    }
+
+    /// Checks everything that does not depend on chain state: addresses, amount and fee.
+    pub fn check_well_formed(&self) -> Result<(), TxError> {
+        self.check_addresses()?;
+        if self.amount <= BigDecimal::zero() {
+            return Err(TxError::NonPositiveAmount);
+        }
+        if self.fee < BigDecimal::zero() {
+            return Err(TxError::NegativeFee);
+        }
+        Ok(())
+    }
+
+    /// Checks that sender and receiver are well-formed addresses with valid checksums.
+    pub fn check_addresses(&self) -> Result<(), AddressError> {
+        validate_address(&self.sender)?;
+        validate_address(&self.receiver)
+    }
+
+    /// Reveals the public key of `private_key` and signs the transaction with it. The key is
+    /// part of the signed message, so it cannot be swapped afterwards.
+    pub fn sign(&mut self, private_key: &SigningKey) {
+        self.sender_public_key = Some(SerializablePublicKey(VerifyingKey::from(private_key)));
+        let message = self.hash();
+        self.signature = OptionalSerializableSignature(Some(SerializableSignature(private_key.sign(message.as_bytes()))));
+    }
+
+    pub fn verify(&self, public_key: &VerifyingKey) -> Result<bool, p256::ecdsa::Error> {
+        if let Some(signature) = &self.signature.0 {
+            let message = self.hash();
+            public_key.verify(message.as_bytes(), &signature.0).map(|_| true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Checks that the revealed public key belongs to the sender address and signed this
+    /// transaction.
+    pub fn verify_sender(&self) -> Result<(), SignatureError> {
+        let public_key = &self.sender_public_key.as_ref().ok_or(SignatureError::MissingPublicKey)?.0;
+        let address = address_from_public_key(public_key);
+        if address != self.sender {
+            return Err(SignatureError::AddressMismatch { address });
+        }
+        if self.signature.0.is_none() {
+            return Err(SignatureError::MissingSignature);
+        }
+        match self.verify(public_key) {
+            Ok(true) => Ok(()),
+            _ => Err(SignatureError::InvalidSignature),
+        }
+    }
+
+    // Everything except the signature, which is what gets hashed and signed
+    fn encode_unsigned(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.sender);
+        encoder.put_str(&self.receiver);
+        encoder.put_decimal(&self.amount);
+        encoder.put_decimal(&self.fee);
+        encoder.put_u64(self.nonce);
+        encoder.put_u32(self.chain_id);
+        encoder.put_option(self.sender_public_key.as_ref());
+    }
+
+    pub fn hash(&self) -> TxId {
+        let mut encoder = Encoder::with_version(Self::VERSION);
+        self.encode_unsigned(&mut encoder);
+        let mut hasher = Sha3_256::new();
+        hasher.update(encoder.finish());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Checks that the transaction is well formed and the sender can afford it.
+    pub fn validate(&self, blockchain: &Blockchain) -> Result<(), TxError> {
+        self.check_funds(blockchain.get_balance(&self.sender))
+    }
+
+    /// Checks that the transaction is well formed and that `balance` covers its amount and fee.
+    pub fn check_funds(&self, balance: BigDecimal) -> Result<(), TxError> {
+        let required = &self.amount + &self.fee;
+        if balance < required {
+            return Err(TxError::InsufficientBalance { balance, required });
+        }
+
+        self.check_well_formed()
+    }
+}
+
+impl Encode for Transaction {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        self.encode_unsigned(encoder);
+        encoder.put_option(self.signature.0.as_ref());
+    }
+}
+
+impl Decode for Transaction {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Transaction {
+            sender: decoder.get_string()?,
+            receiver: decoder.get_string()?,
+            amount: decoder.get_decimal()?,
+            fee: decoder.get_decimal()?,
+            nonce: decoder.get_u64()?,
+            chain_id: decoder.get_u32()?,
+            sender_public_key: decoder.get_option()?,
+            signature: OptionalSerializableSignature(decoder.get_option()?),
+        })
+    }
+}
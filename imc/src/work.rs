@@ -0,0 +1,109 @@
+//! Work distribution for miners that do not hold the chain.
+//!
+//! A miner asks for a [`BlockTemplate`] paying its address, searches nonces on the header
+//! without touching the chain, and hands back only the solved header. The [`WorkQueue`]
+//! that issued the template keeps the block body, found again by the header's Merkle root,
+//! so the full block can be rebuilt and checked like any other.
+
+use crate::block::{Block, BlockHeader};
+use crate::blockchain::Blockchain;
+use crate::coinbase::Coinbase;
+use crate::difficulty::compact_to_target;
+use crate::transaction::{Transaction, TxId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+/// How many templates a [`WorkQueue`] remembers by default.
+pub const DEFAULT_MAX_TEMPLATES: usize = 64;
+
+/// A block to mine, less the transaction bodies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTemplate {
+    pub height: u64,
+    pub header: BlockHeader, // Miners search `nonce`; the rest is committed to by the node
+    pub target: String, // `header.bits` expanded, 64 hex digits
+    pub coinbase: Coinbase, // What the block pays out, committed in `header.merkle_root`
+    pub transactions: Vec<TxId>,
+}
+
+impl BlockTemplate {
+    /// Searches `nonces` for one that meets the target, and returns the solved header.
+    pub fn solve(&self, nonces: Range<u64>) -> Option<BlockHeader> {
+        let mut header = self.header.clone();
+        for nonce in nonces {
+            header.nonce = nonce;
+            if header.meets_target(&header.calculate_hash()) {
+                return Some(header);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkError {
+    /// No remembered template has this Merkle root: it was never issued, has been forgotten,
+    /// or the header was altered.
+    UnknownTemplate { merkle_root: String },
+}
+
+impl fmt::Display for WorkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkError::UnknownTemplate { merkle_root } => write!(f, "no block template has merkle root {}", merkle_root),
+        }
+    }
+}
+
+impl std::error::Error for WorkError {}
+
+/// The block bodies of the most recently issued templates.
+#[derive(Debug)]
+pub struct WorkQueue {
+    bodies: HashMap<String, (Coinbase, Vec<Transaction>)>, // By Merkle root
+    issued: VecDeque<String>, // Merkle roots, oldest first
+    capacity: usize,
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TEMPLATES)
+    }
+}
+
+impl WorkQueue {
+    pub fn new(capacity: usize) -> Self {
+        WorkQueue { bodies: HashMap::new(), issued: VecDeque::new(), capacity }
+    }
+
+    /// A template on the tip of `chain` paying `miner_address`, remembered until `capacity`
+    /// newer ones have been issued.
+    pub fn template(&mut self, chain: &Blockchain, miner_address: String) -> BlockTemplate {
+        let block = chain.block_template(miner_address);
+        let template = BlockTemplate {
+            height: block.header.index,
+            target: format!("{:064x}", compact_to_target(block.header.bits)),
+            transactions: block.transactions.iter().map(Transaction::hash).collect(),
+            coinbase: block.coinbase.clone(),
+            header: block.header,
+        };
+        let merkle_root = template.header.merkle_root.clone();
+        if self.bodies.insert(merkle_root.clone(), (block.coinbase, block.transactions)).is_none() {
+            self.issued.push_back(merkle_root);
+            while self.issued.len() > self.capacity {
+                let forgotten = self.issued.pop_front().unwrap();
+                self.bodies.remove(&forgotten);
+            }
+        }
+        template
+    }
+
+    /// Rebuilds the block a solved header was mined for. The block is not checked.
+    pub fn block_for(&self, header: BlockHeader) -> Result<Block, WorkError> {
+        let (coinbase, transactions) = self.bodies.get(&header.merkle_root)
+            .ok_or_else(|| WorkError::UnknownTemplate { merkle_root: header.merkle_root.clone() })?;
+        Ok(Block { hash: header.calculate_hash(), header, coinbase: coinbase.clone(), transactions: transactions.clone() })
+    }
+}
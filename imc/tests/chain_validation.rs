@@ -0,0 +1,90 @@
+// A stored chain that has been tampered with must fail to open, or fail `is_valid`, with the
+// error that names what was changed.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::encoding::{decode, encode};
+use ::common::signature::OptionalSerializableSignature;
+use crate::common::{address, data_dir, genesis, key};
+use imc::prelude::*;
+use std::fs;
+use std::path::Path;
+
+// A chain stored in `dir` whose block 1 pays 10 from key 1 to key 2
+fn stored_chain(dir: &Path) -> Blockchain {
+    let mut chain = Blockchain::open(dir, &genesis()).unwrap();
+    let mut transaction = Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(1),
+        nonce: 0,
+        chain_id: chain.chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key(1));
+    chain.create_transaction(transaction).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    chain.flush().unwrap();
+    chain.is_valid().unwrap();
+    chain
+}
+
+#[test]
+fn a_tampered_balance_is_a_state_mismatch() {
+    let dir = data_dir("balance");
+    let mut chain = stored_chain(&dir);
+    chain.balances.insert(address(2), BigDecimal::from(1_000));
+    chain.flush().unwrap();
+    drop(chain);
+
+    let chain = Blockchain::open(&dir, &genesis()).unwrap();
+    assert_eq!(chain.is_valid(), Err(ChainError::StateMismatch {
+        field: "balance",
+        key: address(2),
+        replayed: "10".to_string(),
+        live: "1000".to_string(),
+    }));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_tampered_nonce_is_a_state_mismatch() {
+    let dir = data_dir("nonce");
+    let mut chain = stored_chain(&dir);
+    chain.nonces.insert(address(1), 0);
+    chain.flush().unwrap();
+    drop(chain);
+
+    let chain = Blockchain::open(&dir, &genesis()).unwrap();
+    assert_eq!(chain.is_valid(), Err(ChainError::StateMismatch {
+        field: "nonce",
+        key: address(1),
+        replayed: "1".to_string(),
+        live: "0".to_string(),
+    }));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_tampered_block_body_breaks_its_merkle_root() {
+    let dir = data_dir("block");
+    let chain = stored_chain(&dir);
+    let hash = chain.blocks[1].hash.clone();
+    drop(chain);
+
+    // The header, and so the block's hash, is untouched; only the payment is changed
+    let path = dir.join("blocks").join(format!("{}.bin", hash));
+    let mut block: Block = decode(&fs::read(&path).unwrap()).unwrap();
+    block.transactions[0].amount = BigDecimal::from(1);
+    fs::write(&path, encode(&block)).unwrap();
+
+    // Caught as soon as the block file is read
+    let error = Blockchain::open(&dir, &genesis()).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("merkle root"), "{}", error);
+    let _ = fs::remove_dir_all(&dir);
+}
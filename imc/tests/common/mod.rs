@@ -0,0 +1,41 @@
+// Fixtures shared by the chain tests: keys and addresses picked by number, a regtest genesis
+// that funds some of them, and a scratch data directory per test. Each test file uses only
+// some of them.
+#![allow(dead_code)]
+
+use bigdecimal::BigDecimal;
+use ::common::address::address_from_public_key;
+use imc::prelude::*;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use std::fs;
+use std::path::PathBuf;
+
+pub fn key(n: u8) -> SigningKey {
+    SigningKey::from_slice(&[n; 32]).unwrap()
+}
+
+pub fn address(n: u8) -> String {
+    address_from_public_key(&VerifyingKey::from(&key(n)))
+}
+
+// Regtest with 1,000 coins for the address of each of `keys`
+pub fn funded_genesis(keys: &[u8]) -> GenesisSpec {
+    let mut genesis = GenesisSpec::regtest();
+    for &n in keys {
+        genesis.consensus.emission.genesis_allocation.insert(address(n), BigDecimal::from(1_000));
+        genesis.consensus.emission.max_supply += BigDecimal::from(1_000);
+    }
+    genesis
+}
+
+// Regtest with 1,000 coins for the address of key 1
+pub fn genesis() -> GenesisSpec {
+    funded_genesis(&[1])
+}
+
+// An empty directory named after the test file and `name`
+pub fn data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("imc-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
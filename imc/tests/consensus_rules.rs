@@ -0,0 +1,175 @@
+// Each consensus rule must reject an offending block both when it is submitted and when a
+// chain containing it is validated again.
+
+use bigdecimal::BigDecimal;
+use common::signature::OptionalSerializableSignature;
+use imc::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const EASY_BITS: u32 = 0x207f_ffff;
+
+fn rules() -> ConsensusRules {
+    let mut rules = ConsensusRules::default();
+    rules.retarget.initial_bits = EASY_BITS;
+    rules.retarget.pow_limit = EASY_BITS;
+    rules
+}
+
+fn chain_with(rules: ConsensusRules) -> Blockchain {
+    let mut chain = Blockchain::with_rules(rules);
+    for _ in 0..3 {
+        chain.mine_pending_transactions("Miner".to_string()).unwrap();
+    }
+    chain
+}
+
+fn chain() -> Blockchain {
+    chain_with(rules())
+}
+
+// A block that follows every rule on top of the tip
+fn next_block(chain: &Blockchain) -> Block {
+    chain.block_template("Miner".to_string())
+}
+
+fn seal(mut block: Block) -> Block {
+    block.hash = block.calculate_hash();
+    block.mine_block();
+    block
+}
+
+fn now() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
+}
+
+fn assert_rejected(mut chain: Blockchain, block: Block, expected: ChainError) {
+    assert_eq!(chain.submit_block(block.clone()), Err(expected.clone()));
+    assert!(chain.is_valid().is_ok());
+
+    chain.blocks.push(block);
+    assert_eq!(chain.is_valid(), Err(expected));
+}
+
+#[test]
+fn valid_block_is_accepted() {
+    let mut chain = chain();
+    let block = seal(next_block(&chain));
+    assert_eq!(chain.submit_block(block), Ok(BlockStatus::ExtendedTip));
+    assert!(chain.is_valid().is_ok());
+}
+
+#[test]
+fn hash_must_meet_target() {
+    let chain = chain();
+    let mut block = next_block(&chain);
+    while block.header.meets_target(&block.hash) {
+        block.header.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    let index = block.header.index;
+    assert_rejected(chain, block, ChainError::InsufficientProofOfWork { index });
+}
+
+#[test]
+fn target_must_follow_retarget_rule() {
+    let chain = chain();
+    let mut block = next_block(&chain);
+    block.header.bits = 0x2000_ffff;
+    let block = seal(block);
+    let index = block.header.index;
+    assert_rejected(chain, block, ChainError::WrongTarget { index, expected: EASY_BITS, found: 0x2000_ffff });
+}
+
+#[test]
+fn timestamp_must_be_after_median_time_past() {
+    let chain = chain();
+    let median_time_past = chain.median_time_past(&chain.blocks.last().unwrap().hash).unwrap();
+    let mut block = next_block(&chain);
+    block.header.timestamp = median_time_past;
+    let block = seal(block);
+    let index = block.header.index;
+    assert_rejected(chain, block, ChainError::TimestampTooOld { index, timestamp: median_time_past, median_time_past });
+}
+
+#[test]
+fn timestamp_must_not_be_far_in_the_future() {
+    let chain = chain();
+    let drift = chain.consensus.max_future_drift.as_millis();
+    let mut block = next_block(&chain);
+    block.header.timestamp = now() + drift + 60_000;
+    let block = seal(block);
+
+    let mut chain = chain;
+    match chain.submit_block(block.clone()) {
+        Err(ChainError::TimestampTooNew { index, timestamp, max }) => {
+            assert_eq!(index, block.header.index);
+            assert_eq!(timestamp, block.header.timestamp);
+            assert!(max < timestamp);
+        }
+        other => panic!("expected TimestampTooNew, got {:?}", other),
+    }
+
+    chain.blocks.push(block);
+    assert!(matches!(chain.is_valid(), Err(ChainError::TimestampTooNew { .. })));
+}
+
+#[test]
+fn coinbase_must_pay_the_miner_and_contributors() {
+    let chain = chain();
+    let mut block = next_block(&chain);
+    block.coinbase.outputs[0].address = "Thief".to_string();
+    block.header.merkle_root = block.calculate_merkle_root();
+    let block = seal(block);
+    let index = block.header.index;
+    assert_rejected(chain, block, ChainError::InvalidCoinbase { index });
+}
+
+#[test]
+fn coinbase_must_not_mint_more_than_the_schedule() {
+    let chain = chain();
+    let mut block = next_block(&chain);
+    let allowed = block.coinbase.total();
+    block.coinbase.outputs[0].amount += BigDecimal::from(1);
+    block.header.merkle_root = block.calculate_merkle_root();
+    let block = seal(block);
+    let index = block.header.index;
+    let found = block.coinbase.total();
+    assert_rejected(chain, block, ChainError::ExcessiveMint { index, allowed, found });
+}
+
+#[test]
+fn indices_must_be_sequential() {
+    let chain = chain();
+    let mut block = next_block(&chain);
+    let expected = block.header.index;
+    block.header.index += 1;
+    let block = seal(block);
+    assert_rejected(chain, block, ChainError::InvalidIndex { index: expected + 1, expected });
+}
+
+#[test]
+fn block_must_fit_size_limit() {
+    let mut rules = rules();
+    rules.max_block_size = 1_000;
+    let chain = chain_with(rules);
+
+    let transactions: Vec<Transaction> = (0..20)
+        .map(|nonce| Transaction {
+            sender: "Alice".to_string(),
+            receiver: "Bob".to_string(),
+            amount: BigDecimal::from(1),
+            fee: BigDecimal::from(0),
+            nonce,
+            chain_id: DEFAULT_CHAIN_ID,
+            sender_public_key: None,
+            signature: OptionalSerializableSignature(None),
+        })
+        .collect();
+    let tip = chain.blocks.last().unwrap();
+    let block = seal(Block::new(tip.header.index + 1, chain.next_timestamp(), tip.hash.clone(), "Miner".to_string(), chain.coinbase_for("Miner"), transactions, chain.next_bits()));
+    let size = common::encoding::encode(&block).len();
+    assert!(size > 1_000);
+
+    let index = block.header.index;
+    assert_rejected(chain, block, ChainError::BlockTooLarge { index, size, max: 1_000 });
+}
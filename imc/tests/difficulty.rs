@@ -0,0 +1,62 @@
+// Vectors for compact targets and retargeting. With the default 450 second block time and
+// 24 block window, a window is expected to take 10,800,000 milliseconds.
+
+use bigdecimal::num_bigint::BigUint;
+use imc::difficulty::{compact_to_target, retarget, target_to_compact, RetargetParams};
+
+const EXPECTED: u128 = 450_000 * 24;
+const BITS: u32 = 0x1e10_0000; // 2^236
+
+fn target(hex: &str) -> BigUint {
+    BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+#[test]
+fn compact_targets_round_trip() {
+    assert_eq!(compact_to_target(0x1d00_ffff), target("00000000ffff0000000000000000000000000000000000000000000000000000"));
+    assert_eq!(compact_to_target(BITS), BigUint::from(1u32) << 236u32);
+
+    for bits in [0x1d00_ffff, 0x1b04_04cb, BITS, 0x207f_ffff, 0x0312_3456, 0x0200_8000] {
+        assert_eq!(target_to_compact(&compact_to_target(bits)), bits, "{:#010x}", bits);
+    }
+
+    // A mantissa with its top bit set would read as negative, so it moves down a byte
+    assert_eq!(target_to_compact(&BigUint::from(0x80u32)), 0x0200_8000);
+    assert_eq!(compact_to_target(0x1d80_0000), BigUint::from(0u32));
+
+    // Precision below the top three bytes is rounded away
+    assert_eq!(target_to_compact(&target("00000000ffffff01000000000000000000000000000000000000000000000000")), 0x1d00_ffff);
+}
+
+#[test]
+fn on_time_windows_keep_the_target() {
+    assert_eq!(retarget(&RetargetParams::default(), BITS, EXPECTED), BITS);
+}
+
+#[test]
+fn fast_windows_make_the_target_harder() {
+    assert_eq!(retarget(&RetargetParams::default(), BITS, EXPECTED / 2), 0x1e08_0000);
+}
+
+#[test]
+fn slow_windows_make_the_target_easier() {
+    assert_eq!(retarget(&RetargetParams::default(), BITS, EXPECTED * 2), 0x1e20_0000);
+}
+
+#[test]
+fn changes_are_clamped_to_max_adjustment() {
+    let params = RetargetParams::default();
+    assert_eq!(params.max_adjustment, 4);
+    assert_eq!(retarget(&params, BITS, EXPECTED / 4), 0x1e04_0000);
+    assert_eq!(retarget(&params, BITS, 0), 0x1e04_0000);
+    assert_eq!(retarget(&params, BITS, EXPECTED * 4), 0x1e40_0000);
+    assert_eq!(retarget(&params, BITS, EXPECTED * 100), 0x1e40_0000);
+}
+
+#[test]
+fn targets_never_pass_the_pow_limit() {
+    let params = RetargetParams { pow_limit: 0x1e20_0000, ..RetargetParams::default() };
+    assert_eq!(retarget(&params, BITS, EXPECTED * 2), 0x1e20_0000);
+    assert_eq!(retarget(&params, BITS, EXPECTED * 4), 0x1e20_0000);
+    assert_eq!(retarget(&params, 0x1e20_0000, EXPECTED * 4), 0x1e20_0000);
+}
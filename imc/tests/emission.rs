@@ -0,0 +1,77 @@
+// Block rewards around a halving and at the supply cap.
+
+use bigdecimal::{BigDecimal, Zero};
+use imc::emission::EmissionSchedule;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+// 40 per block, halving every 10 blocks, capped at 510: blocks 1 to 9 mint 360, blocks 10
+// to 16 mint 20 each, and block 17 gets only the 10 left under the cap
+fn capped() -> EmissionSchedule {
+    EmissionSchedule {
+        genesis_allocation: BTreeMap::new(),
+        initial_reward: BigDecimal::from(40),
+        halving_interval: 10,
+        max_supply: BigDecimal::from(510),
+    }
+}
+
+#[test]
+fn rewards_halve_at_the_interval() {
+    let schedule = EmissionSchedule::default();
+    let interval = schedule.halving_interval;
+    assert_eq!(schedule.reward_at(0), BigDecimal::zero());
+    assert_eq!(schedule.reward_at(1), decimal("40"));
+    assert_eq!(schedule.reward_at(interval - 1), decimal("40"));
+    assert_eq!(schedule.reward_at(interval), decimal("20"));
+    assert_eq!(schedule.reward_at(2 * interval - 1), decimal("20"));
+    assert_eq!(schedule.reward_at(2 * interval), decimal("10"));
+    assert_eq!(schedule.total_supply_at(interval - 1), BigDecimal::from(40 * (interval - 1)));
+    assert_eq!(schedule.total_supply_at(interval), BigDecimal::from(40 * (interval - 1) + 20));
+}
+
+#[test]
+fn rewards_stop_at_the_cap() {
+    let schedule = capped();
+    assert_eq!(schedule.reward_at(9), decimal("40"));
+    assert_eq!(schedule.reward_at(10), decimal("20"));
+    assert_eq!(schedule.total_supply_at(16), decimal("500"));
+    assert_eq!(schedule.reward_at(17), decimal("10"));
+    assert_eq!(schedule.total_supply_at(17), decimal("510"));
+    assert_eq!(schedule.reward_at(18), BigDecimal::zero());
+    assert_eq!(schedule.reward_at(1_000_000), BigDecimal::zero());
+    assert_eq!(schedule.total_supply_at(u64::MAX), decimal("510"));
+}
+
+#[test]
+fn the_genesis_allocation_counts_towards_the_cap() {
+    let mut schedule = capped();
+    schedule.genesis_allocation.insert("genesis".to_string(), BigDecimal::from(480));
+    schedule.max_supply = BigDecimal::from(990);
+    assert_eq!(schedule.total_supply_at(0), decimal("480"));
+    assert_eq!(schedule.reward_at(17), decimal("10"));
+    assert_eq!(schedule.total_supply_at(17), decimal("990"));
+    assert_eq!(schedule.reward_at(18), BigDecimal::zero());
+}
+
+#[test]
+fn total_supply_never_exceeds_max_supply() {
+    let reached = EmissionSchedule { max_supply: decimal("39990.5"), ..EmissionSchedule::default() }; // Mid-block 1000
+    for schedule in [EmissionSchedule::default(), capped(), reached] {
+        let mut previous = schedule.total_supply_at(0);
+        for height in 1..2_000 {
+            let supply = schedule.total_supply_at(height);
+            assert!(supply <= schedule.max_supply, "supply {} at height {} exceeds {}", supply, height, schedule.max_supply);
+            assert_eq!(supply, &previous + schedule.reward_at(height), "height {}", height);
+            previous = supply;
+        }
+        for height in (11..64).map(|shift| 1u64 << shift).chain([u64::MAX - 1, u64::MAX]) {
+            let supply = schedule.total_supply_at(height);
+            assert!(supply <= schedule.max_supply, "supply {} at height {} exceeds {}", supply, height, schedule.max_supply);
+        }
+    }
+}
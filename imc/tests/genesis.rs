@@ -0,0 +1,128 @@
+// Network presets and genesis spec files: every preset must be runnable, the testnet faucet
+// must be spendable with its published key, and spec files a chain cannot run under must be
+// refused when loaded.
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::signature::OptionalSerializableSignature;
+use imc::genesis::{TESTNET_FAUCET_ADDRESS, TESTNET_FAUCET_KEY};
+use imc::prelude::*;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn faucet_key() -> SigningKey {
+    let bytes: Vec<u8> = (0..TESTNET_FAUCET_KEY.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&TESTNET_FAUCET_KEY[i..i + 2], 16).unwrap())
+        .collect();
+    SigningKey::from_slice(&bytes).unwrap()
+}
+
+fn spec_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("imc-genesis-{}-{}.json", name, std::process::id()))
+}
+
+// Saves `spec` and loads it back
+fn round_trip(name: &str, spec: &GenesisSpec) -> io::Result<GenesisSpec> {
+    let path = spec_path(name);
+    spec.save(&path).unwrap();
+    let loaded = GenesisSpec::load(&path);
+    let _ = fs::remove_file(&path);
+    loaded
+}
+
+fn assert_invalid(name: &str, spec: GenesisSpec, expected: &str) {
+    let error = round_trip(name, &spec).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", error);
+    assert!(error.to_string().contains(expected), "{:?} does not mention {:?}", error.to_string(), expected);
+}
+
+#[test]
+fn presets_are_valid_and_load_back_unchanged() {
+    for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+        let spec = network.genesis();
+        spec.validate().unwrap();
+        assert_eq!(round_trip(&spec.network, &spec).unwrap(), spec);
+    }
+}
+
+#[test]
+fn testnet_faucet_can_sign_and_spend() {
+    let key = faucet_key();
+    assert_eq!(address_from_public_key(&VerifyingKey::from(&key)), TESTNET_FAUCET_ADDRESS);
+
+    let genesis = GenesisSpec::testnet();
+    let mut chain = Blockchain::from_genesis(&genesis);
+    let allocated = genesis.consensus.emission.genesis_allocation[TESTNET_FAUCET_ADDRESS].clone();
+    assert_eq!(chain.get_balance(TESTNET_FAUCET_ADDRESS), allocated);
+
+    let receiver = address_from_public_key(&VerifyingKey::from(&SigningKey::from_slice(&[9u8; 32]).unwrap()));
+    let mut transaction = Transaction {
+        sender: TESTNET_FAUCET_ADDRESS.to_string(),
+        receiver: receiver.clone(),
+        amount: BigDecimal::from(100),
+        fee: BigDecimal::from(1),
+        nonce: 0,
+        chain_id: genesis.chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key);
+    chain.create_transaction(transaction).unwrap();
+    chain.mine_pending_transactions(receiver.clone()).unwrap();
+
+    assert_eq!(chain.get_balance(TESTNET_FAUCET_ADDRESS), allocated - BigDecimal::from(101));
+    assert!(chain.get_balance(&receiver) >= BigDecimal::from(100));
+    chain.is_valid().unwrap();
+}
+
+#[test]
+fn specs_that_cannot_run_are_refused() {
+    let mut spec = GenesisSpec::regtest();
+    spec.consensus.retarget.max_adjustment = 0;
+    assert_invalid("max-adjustment", spec, "max_adjustment");
+
+    let mut spec = GenesisSpec::regtest();
+    spec.consensus.retarget.window = 0;
+    assert_invalid("window", spec, "window");
+
+    let mut spec = GenesisSpec::regtest();
+    spec.consensus.retarget.target_block_time = Duration::ZERO;
+    assert_invalid("block-time", spec, "target_block_time");
+
+    let mut spec = GenesisSpec::regtest();
+    spec.consensus.retarget.pow_limit = 0x2080_0000; // Sign bit set
+    assert_invalid("pow-limit-negative", spec, "pow_limit");
+
+    let mut spec = GenesisSpec::regtest();
+    spec.consensus.retarget.pow_limit = 0x2200_0001; // More than 256 bits
+    assert_invalid("pow-limit-too-large", spec, "pow_limit");
+
+    let mut spec = GenesisSpec::testnet();
+    spec.consensus.retarget.pow_limit = 0x1e10_0000; // Harder than testnet's initial_bits
+    assert_invalid("initial-bits", spec, "initial_bits");
+}
+
+#[test]
+fn allocations_must_be_spendable_and_capped() {
+    let mut spec = GenesisSpec::testnet();
+    spec.consensus.emission.genesis_allocation.insert("Faucet".to_string(), BigDecimal::from(1));
+    assert_invalid("unknown-address", spec, "\"Faucet\"");
+
+    let mut typo = TESTNET_FAUCET_ADDRESS.to_string();
+    typo.replace_range(5..6, if &typo[5..6] == "X" { "Y" } else { "X" });
+    let mut spec = GenesisSpec::testnet();
+    spec.consensus.emission.genesis_allocation.insert(typo.clone(), BigDecimal::from(1));
+    assert_invalid("typo", spec, &typo);
+
+    let mut spec = GenesisSpec::testnet();
+    spec.consensus.emission.genesis_allocation.insert(TESTNET_FAUCET_ADDRESS.to_string(), BigDecimal::from(0));
+    assert_invalid("zero-allocation", spec, "not positive");
+
+    let mut spec = GenesisSpec::testnet();
+    spec.consensus.emission.max_supply = BigDecimal::from(999_999_999);
+    assert_invalid("over-cap", spec, "max_supply");
+}
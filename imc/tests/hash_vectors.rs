@@ -0,0 +1,94 @@
+// Golden hashes for transactions and blocks. These must only change together with the
+// `Encode::VERSION` of the type hashed.
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::encoding::{decode, encode, DecodeError};
+use common::signature::OptionalSerializableSignature;
+use imc::block::Block;
+use imc::coinbase::{Coinbase, CoinbaseOutput};
+use imc::blockchain::Blockchain;
+use imc::transaction::Transaction;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use std::str::FromStr;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sample_transaction() -> Transaction {
+    Transaction {
+        sender: "Alice".to_string(),
+        receiver: "Bob".to_string(),
+        amount: BigDecimal::from_str("10").unwrap(),
+        fee: BigDecimal::from_str("0.5").unwrap(),
+        nonce: 0,
+        chain_id: 1,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    }
+}
+
+#[test]
+fn transaction_vector() {
+    let tx = sample_transaction();
+    assert_eq!(
+        hex(&encode(&tx)),
+        "0100000005416c69636500000003426f620000000101ffffffffffffffff000000010500000000000000010000000000000000000000010000"
+    );
+    assert_eq!(tx.hash(), "baf0423abeec2926f8cc97e7657d3d6fed5395e7ffd6fcaaac0efdd92ec9e8c2");
+}
+
+#[test]
+fn transaction_hash_ignores_decimal_formatting() {
+    let mut tx = sample_transaction();
+    tx.amount = BigDecimal::from_str("10.00").unwrap();
+    assert_eq!(tx.hash(), sample_transaction().hash());
+}
+
+#[test]
+fn block_vector() {
+    let coinbase = Coinbase { outputs: vec![CoinbaseOutput { address: "Miner".to_string(), amount: BigDecimal::from_str("40").unwrap() }] };
+    let block = Block::new(1, 23, "0".repeat(64), "Miner".to_string(), coinbase, vec![sample_transaction()], 2);
+    assert_eq!(block.header.merkle_root, "ec9fff0e04b8edd7974c20fb6a7101313e8b819790cdc20801c7137d2d966367");
+    assert_eq!(block.hash, "b2eeed89c29c2a6fb1b872bc0d109bffe2e5ad69811796adaa5294ba3a235743");
+
+    let decoded: Block = decode(&encode(&block)).unwrap();
+    assert_eq!(decoded.hash, block.hash);
+    assert_eq!(decoded.calculate_hash(), block.hash);
+}
+
+#[test]
+fn header_fields_do_not_run_together() {
+    let a = Block::new(1, 23, "0".to_string(), String::new(), Coinbase::default(), vec![], 0);
+    let b = Block::new(12, 3, "0".to_string(), String::new(), Coinbase::default(), vec![], 0);
+    assert_ne!(a.hash, b.hash);
+}
+
+#[test]
+fn genesis_vector() {
+    assert_eq!(Blockchain::new().blocks[0].hash, "9a26400459b40f9b100243087d25edf750aaaa72ba13522e540e56018586888f");
+}
+
+#[test]
+fn decoded_transactions_still_verify() {
+    let key = SigningKey::from_slice(&[1; 32]).unwrap();
+    let mut tx = sample_transaction();
+    tx.sender = address_from_public_key(&VerifyingKey::from(&key));
+    tx.sign(&key);
+
+    let decoded: Transaction = decode(&encode(&tx)).unwrap();
+    assert_eq!(decoded.hash(), tx.hash());
+    decoded.verify_sender().unwrap();
+}
+
+#[test]
+fn other_versions_are_refused() {
+    // The version byte is hashed, so a transaction read at any other version could not be
+    // checked against its signature
+    let mut bytes = encode(&sample_transaction());
+    for version in [0, 2, 6] {
+        bytes[0] = version;
+        assert_eq!(decode::<Transaction>(&bytes).err(), Some(DecodeError::UnsupportedVersion(version)));
+    }
+}
@@ -0,0 +1,180 @@
+// What the mempool keeps, what it gives up, and the order it hands transactions to a block.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::encoding::encode;
+use ::common::signature::OptionalSerializableSignature;
+use crate::common::{address, funded_genesis, key};
+use imc::prelude::*;
+use std::str::FromStr;
+use std::time::Duration;
+
+const HOUR: u128 = 60 * 60 * 1000;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+// Regtest with 1,000 coins for each of the addresses of keys 1 to 3
+fn chain() -> Blockchain {
+    Blockchain::from_genesis(&funded_genesis(&[1, 2, 3]))
+}
+
+// A transfer of 10 from the address of key `from` to that of key 9. The mempool itself does
+// not check signatures, and unsigned transactions that differ only in fee all have one size.
+fn unsigned(from: u8, nonce: u64, fee: &str) -> Transaction {
+    Transaction {
+        sender: address(from),
+        receiver: address(9),
+        amount: BigDecimal::from(10),
+        fee: decimal(fee),
+        nonce,
+        chain_id: GenesisSpec::regtest().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    }
+}
+
+fn transfer(from: u8, nonce: u64, fee: &str) -> Transaction {
+    let mut transaction = unsigned(from, nonce, fee);
+    transaction.sign(&key(from));
+    transaction
+}
+
+fn fees(transactions: &[Transaction]) -> Vec<(String, u64, BigDecimal)> {
+    transactions.iter().map(|tx| (tx.sender.clone(), tx.nonce, tx.fee.clone())).collect()
+}
+
+#[test]
+fn templates_take_the_highest_fee_rate_first() {
+    let mut chain = chain();
+    chain.create_transaction(transfer(1, 0, "1")).unwrap();
+    chain.create_transaction(transfer(2, 0, "3")).unwrap();
+    chain.create_transaction(transfer(3, 0, "2")).unwrap();
+
+    let block = chain.block_template(address(9));
+    assert_eq!(fees(&block.transactions), vec![
+        (address(2), 0, decimal("3")),
+        (address(3), 0, decimal("2")),
+        (address(1), 0, decimal("1")),
+    ]);
+}
+
+#[test]
+fn a_sender_nonce_chain_stays_contiguous() {
+    let mut chain = chain();
+    chain.create_transaction(transfer(1, 0, "1")).unwrap();
+    chain.create_transaction(transfer(1, 1, "100")).unwrap();
+    chain.create_transaction(transfer(2, 0, "10")).unwrap();
+
+    // Nonce 1 pays the most but cannot go before nonce 0
+    let block = chain.block_template(address(9));
+    assert_eq!(fees(&block.transactions), vec![
+        (address(2), 0, decimal("10")),
+        (address(1), 0, decimal("1")),
+        (address(1), 1, decimal("100")),
+    ]);
+
+    // A transaction after a missing nonce is held back, whatever it pays
+    chain.mempool.insert(transfer(3, 1, "1000"), 0).unwrap();
+    let block = chain.block_template(address(9));
+    assert!(block.transactions.iter().all(|tx| tx.sender != address(3)));
+
+    chain.submit_block({
+        let mut block = block;
+        block.mine_block();
+        block
+    }).unwrap();
+    assert_eq!(chain.nonces[&address(1)], 2);
+}
+
+#[test]
+fn replacements_must_pay_the_fee_bump() {
+    let mut mempool = Mempool::new(MempoolConfig { replacement_bump: 10, ..MempoolConfig::default() });
+    let original = unsigned(1, 0, "10");
+    assert!(mempool.insert(original.clone(), 0).unwrap().is_none());
+
+    for fee in ["10", "10.99"] {
+        match mempool.insert(unsigned(1, 0, fee), 0) {
+            Err(TxError::ReplacementFeeTooLow { fee: offered, required }) => {
+                assert_eq!((offered, required), (decimal(fee), decimal("11")));
+            }
+            other => panic!("replacement paying {} was not refused: {:?}", fee, other),
+        }
+    }
+    assert!(mempool.contains(&original.hash()));
+
+    let replacement = unsigned(1, 0, "11");
+    let replaced = mempool.insert(replacement.clone(), 0).unwrap().unwrap();
+    assert_eq!(replaced.hash(), original.hash());
+    assert!(!mempool.contains(&original.hash()));
+    assert!(mempool.contains(&replacement.hash()));
+    assert_eq!(mempool.len(), 1);
+}
+
+#[test]
+fn a_full_pool_evicts_the_lowest_fee_rate() {
+    let size = encode(&unsigned(1, 0, "1")).len();
+    let mut mempool = Mempool::new(MempoolConfig { max_bytes: 3 * size, ..MempoolConfig::default() });
+    for (from, fee) in [(1, "2"), (2, "1"), (3, "3")] {
+        mempool.insert(unsigned(from, 0, fee), 0).unwrap();
+    }
+    assert_eq!(mempool.bytes(), 3 * size);
+
+    // Too cheap to push anything out
+    match mempool.insert(unsigned(4, 0, "1"), 0) {
+        Err(TxError::MempoolFull { min_fee_rate }) => assert_eq!(min_fee_rate.fee, decimal("1")),
+        other => panic!("a transaction paying the lowest fee rate was admitted: {:?}", other),
+    }
+
+    mempool.insert(unsigned(4, 0, "5"), 0).unwrap();
+    assert_eq!(mempool.len(), 3);
+    assert!(!mempool.contains(&unsigned(2, 0, "1").hash()));
+    for (from, fee) in [(1, "2"), (3, "3"), (4, "5")] {
+        assert!(mempool.contains(&unsigned(from, 0, fee).hash()));
+    }
+}
+
+#[test]
+fn transactions_expire_after_the_configured_age() {
+    let mut mempool = Mempool::new(MempoolConfig { expiry: Duration::from_secs(60 * 60), ..MempoolConfig::default() });
+    mempool.insert(unsigned(1, 0, "1"), 0).unwrap();
+    mempool.insert(unsigned(1, 1, "1"), 2 * HOUR).unwrap(); // Expires with the one before it
+    mempool.insert(unsigned(2, 0, "1"), 2 * HOUR).unwrap();
+
+    assert!(mempool.expire(HOUR).is_empty());
+    let expired = mempool.expire(HOUR + 1);
+    assert_eq!(fees(&expired), vec![(address(1), 0, decimal("1")), (address(1), 1, decimal("1"))]);
+    assert_eq!(mempool.len(), 1);
+    assert!(mempool.contains(&unsigned(2, 0, "1").hash()));
+
+    assert!(mempool.expire(3 * HOUR).is_empty());
+    assert_eq!(mempool.expire(3 * HOUR + 1).len(), 1);
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn pending_transfers_cannot_spend_the_same_coins_twice() {
+    let mut chain = chain();
+    let results: Vec<Result<TxId, TxError>> = (0..10)
+        .map(|_| {
+            let mut transaction = unsigned(1, chain.next_nonce(&address(1)), "1");
+            transaction.amount = BigDecimal::from(900);
+            transaction.sign(&key(1));
+            chain.create_transaction(transaction)
+        })
+        .collect();
+
+    assert!(results[0].is_ok());
+    for result in &results[1..] {
+        match result {
+            Err(TxError::InsufficientBalance { balance, required }) => {
+                assert_eq!((balance, required), (&BigDecimal::from(99), &BigDecimal::from(901)));
+            }
+            other => panic!("a second transfer of 900 was not refused: {:?}", other),
+        }
+    }
+    assert_eq!(chain.mempool.len(), 1);
+    assert_eq!(chain.block_template(address(9)).transactions.len(), 1);
+}
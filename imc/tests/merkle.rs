@@ -0,0 +1,81 @@
+// Golden Merkle roots for small trees, and inclusion proofs against them. Leaves are
+// SHA-256(0x00 || hash) and inner nodes SHA-256(0x01 || left || right), over hex strings.
+
+use imc::merkle::{merkle_root, MerkleProof, MerkleStep, Side, EMPTY_MERKLE_ROOT};
+
+const LEAF_A: &str = "88df0645999a1bc9dec19086e862403750a069436d7ecf7775256f78279b3fcb";
+const NODE_AB: &str = "0213bc5332c31aab3d5a53644449e5b54d6f74acfb722a8f9eaea7c248cb8d95";
+const ROOT_ABC: &str = "5cc9a9352350c5a30daf9d4472af4e25dbd38ec249c9ca79dc27689580a43ee6";
+// What the root of three would be if the odd node were paired with itself
+const ROOT_ABCC: &str = "7ac1fbd516fabe9f59a83ddd366cd9deefd418fa3ebcde664dbb547923973ed5";
+
+fn tx_hashes(count: usize) -> Vec<String> {
+    ["a", "b", "c"].iter().take(count).map(|c| c.repeat(64)).collect()
+}
+
+#[test]
+fn roots_of_small_trees() {
+    assert_eq!(merkle_root(&[]), EMPTY_MERKLE_ROOT);
+    assert_eq!(merkle_root(&tx_hashes(1)), LEAF_A);
+    assert_eq!(merkle_root(&tx_hashes(2)), NODE_AB);
+    assert_eq!(merkle_root(&tx_hashes(3)), ROOT_ABC);
+}
+
+#[test]
+fn odd_nodes_are_carried_up_not_duplicated() {
+    let mut duplicated = tx_hashes(3);
+    duplicated.push("c".repeat(64));
+    assert_ne!(merkle_root(&duplicated), merkle_root(&tx_hashes(3)));
+    assert_eq!(merkle_root(&duplicated), ROOT_ABCC);
+}
+
+#[test]
+fn inner_nodes_are_not_leaves() {
+    // A single transaction whose hash happens to be an inner node has a different root
+    assert_ne!(merkle_root(&[NODE_AB.to_string()]), NODE_AB);
+
+    // Nor can the inner node over a and b be proven as a transaction of the three-leaf tree
+    let leaf_c = merkle_root(&["c".repeat(64)]);
+    let forged = MerkleProof { index: 0, steps: vec![MerkleStep { hash: leaf_c, side: Side::Right }] };
+    assert!(!forged.verify(NODE_AB, ROOT_ABC));
+}
+
+#[test]
+fn proofs_verify_every_leaf() {
+    for count in 1..=3 {
+        let hashes = tx_hashes(count);
+        let root = merkle_root(&hashes);
+        for (index, hash) in hashes.iter().enumerate() {
+            let proof = MerkleProof::generate(&hashes, index).unwrap();
+            assert!(proof.verify(hash, &root), "leaf {} of {}", index, count);
+        }
+        assert!(MerkleProof::generate(&hashes, count).is_none());
+    }
+
+    // The carried-up leaf has a single step, straight to the root
+    let proof = MerkleProof::generate(&tx_hashes(3), 2).unwrap();
+    assert_eq!(proof.steps.len(), 1);
+    assert_eq!(proof.steps[0].hash, NODE_AB);
+}
+
+#[test]
+fn tampered_proofs_are_rejected() {
+    let hashes = tx_hashes(3);
+    let proof = MerkleProof::generate(&hashes, 0).unwrap();
+    assert!(proof.verify(&hashes[0], ROOT_ABC));
+
+    assert!(!proof.verify(&hashes[1], ROOT_ABC));
+    assert!(!proof.verify(&hashes[0], NODE_AB));
+
+    let mut wrong_sibling = proof.clone();
+    wrong_sibling.steps[0].hash = LEAF_A.to_string();
+    assert!(!wrong_sibling.verify(&hashes[0], ROOT_ABC));
+
+    let mut wrong_side = proof.clone();
+    wrong_side.steps[0].side = Side::Left;
+    assert!(!wrong_side.verify(&hashes[0], ROOT_ABC));
+
+    let mut truncated = proof;
+    truncated.steps.pop();
+    assert!(!truncated.verify(&hashes[0], ROOT_ABC));
+}
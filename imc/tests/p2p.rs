@@ -0,0 +1,200 @@
+// Several nodes on localhost ports, connected only through seeds and address gossip, must
+// end up agreeing on blocks and pending transactions. Peers that hold connections open or
+// send oversized requests must not exhaust a node, nor keep it from shutting down.
+
+#![cfg(feature = "p2p")]
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::signature::OptionalSerializableSignature;
+use imc::p2p::message::{read_message, write_message, Message, Version, MAX_INVENTORY, PROTOCOL_VERSION};
+use imc::prelude::*;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn key() -> SigningKey {
+    SigningKey::from_slice(&[7u8; 32]).unwrap()
+}
+
+fn funded() -> String {
+    address_from_public_key(&VerifyingKey::from(&key()))
+}
+
+// Regtest with coins for `funded()` at genesis
+fn genesis() -> GenesisSpec {
+    let mut genesis = GenesisSpec::regtest();
+    genesis.consensus.emission.genesis_allocation.insert(funded(), BigDecimal::from(1_000));
+    genesis.consensus.emission.max_supply += BigDecimal::from(1_000);
+    genesis
+}
+
+fn start(genesis: &GenesisSpec, seeds: Vec<SocketAddr>) -> Node {
+    let config = NodeConfig { listen_addr: "127.0.0.1:0".parse().unwrap(), seeds, ..NodeConfig::default() };
+    Node::start(config, Arc::new(Mutex::new(Blockchain::from_genesis(genesis)))).unwrap()
+}
+
+// Connects to `node` as a peer on its network and completes the handshake
+fn raw_peer(node: &Node) -> TcpStream {
+    let mut stream = TcpStream::connect(node.local_addr()).unwrap();
+    let genesis_hash = node.chain().lock().unwrap().blocks[0].hash.clone();
+    let version = Version {
+        protocol: PROTOCOL_VERSION,
+        chain_id: genesis().chain_id,
+        genesis_hash: genesis_hash.clone(),
+        best_height: 0,
+        best_hash: genesis_hash,
+        listen_port: 0,
+        nonce: 42,
+    };
+    write_message(&mut stream, &Message::Version(version)).unwrap();
+    write_message(&mut stream, &Message::Verack).unwrap();
+    assert!(matches!(read_message(&mut stream).unwrap(), Message::Version(_)));
+    assert!(matches!(read_message(&mut stream).unwrap(), Message::Verack));
+    stream
+}
+
+fn wait_until<F: FnMut() -> bool>(what: &str, mut condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn tip(node: &Node) -> String {
+    node.chain().lock().unwrap().blocks.last().unwrap().hash.clone()
+}
+
+#[test]
+fn blocks_and_transactions_reach_every_node() {
+    let genesis = genesis();
+    let a = start(&genesis, vec![]);
+    let b = start(&genesis, vec![a.local_addr()]);
+    // B only hears of A's address once connected, and C asks B for addresses just once
+    wait_until("B connects to A", || a.peer_count() == 1 && b.peer_count() == 1);
+    let c = start(&genesis, vec![b.local_addr()]);
+
+    // C only knows B, and learns about A from B
+    wait_until("every node is connected to both others", || a.peer_count() == 2 && b.peer_count() == 2 && c.peer_count() == 2);
+
+    let mut block = a.chain().lock().unwrap().block_template("Miner".to_string());
+    block.mine_block();
+    assert_eq!(a.submit_block(block.clone()), Ok(BlockStatus::ExtendedTip));
+    wait_until("every node has the block", || [&a, &b, &c].iter().all(|node| tip(node) == block.hash));
+
+    let mut transaction = Transaction {
+        sender: funded(),
+        receiver: funded(),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(1),
+        nonce: 0,
+        chain_id: genesis.chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key());
+    let tx_id = c.submit_transaction(transaction).unwrap();
+    wait_until("every node has the transaction", || [&a, &b, &c].iter().all(|node| node.chain().lock().unwrap().mempool.contains(&tx_id)));
+
+    // Mined on B this time; the other nodes confirm the transaction too
+    let hash = b.chain().lock().unwrap().mine_pending_transactions("Miner".to_string()).unwrap();
+    b.announce(Inventory::Block(hash.clone()));
+    wait_until("every node has the second block", || [&a, &b, &c].iter().all(|node| tip(node) == hash));
+    for node in [&a, &b, &c] {
+        let chain = node.chain().lock().unwrap();
+        assert!(chain.mempool.is_empty());
+        assert_eq!(chain.get_balance(&funded()), BigDecimal::from(999));
+        assert!(chain.is_valid().is_ok());
+    }
+}
+
+#[test]
+fn nodes_on_another_network_are_refused() {
+    let a = start(&genesis(), vec![]);
+    let mut other = genesis();
+    other.chain_id += 1;
+    let b = start(&other, vec![a.local_addr()]);
+    let c = start(&GenesisSpec::testnet(), vec![a.local_addr()]);
+    let d = start(&genesis(), vec![a.local_addr()]);
+
+    wait_until("the node on the same network connects", || d.peer_count() == 1);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(a.peer_count(), 1);
+    assert_eq!(b.peer_count(), 0);
+    assert_eq!(c.peer_count(), 0);
+    assert_eq!(a.peers()[0].version.genesis_hash, d.chain().lock().unwrap().blocks[0].hash);
+}
+
+#[test]
+fn shutdown_disconnects_peers() {
+    let genesis = genesis();
+    let a = start(&genesis, vec![]);
+    let b = start(&genesis, vec![a.local_addr()]);
+    wait_until("the nodes connect", || a.peer_count() == 1 && b.peer_count() == 1);
+
+    b.shutdown();
+    wait_until("the other node notices", || a.peer_count() == 0);
+}
+
+#[test]
+fn shutdown_closes_connections_still_in_the_handshake() {
+    let a = start(&genesis(), vec![]);
+    let mut peer = raw_peer(&a);
+    let mut stalled = TcpStream::connect(a.local_addr()).unwrap();
+    wait_until("the peer is registered", || a.peer_count() == 1);
+    thread::sleep(Duration::from_millis(200));
+
+    // Both are closed straight away, rather than once the handshake times out
+    let started = Instant::now();
+    a.shutdown();
+    assert!(read_message(&mut peer).is_err());
+    // The node's version was already sent, and nothing follows it
+    assert!(matches!(read_message(&mut stalled).unwrap(), Message::Version(_)));
+    assert!(read_message(&mut stalled).is_err());
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+}
+
+#[test]
+fn connections_in_the_handshake_count_towards_max_peers() {
+    let genesis = genesis();
+    let config = NodeConfig { listen_addr: "127.0.0.1:0".parse().unwrap(), max_peers: 2, ..NodeConfig::default() };
+    let a = Node::start(config, Arc::new(Mutex::new(Blockchain::from_genesis(&genesis)))).unwrap();
+
+    // Two connections that never send a version take up every slot
+    let stalled: Vec<TcpStream> = (0..2).map(|_| TcpStream::connect(a.local_addr()).unwrap()).collect();
+    thread::sleep(Duration::from_millis(200));
+    let b = start(&genesis, vec![a.local_addr()]);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(a.peer_count(), 0);
+    assert_eq!(b.peer_count(), 0);
+
+    drop(stalled);
+    thread::sleep(Duration::from_millis(200));
+    b.connect(a.local_addr());
+    wait_until("the nodes connect once the slots are free", || a.peer_count() == 1 && b.peer_count() == 1);
+}
+
+#[test]
+fn oversized_inventory_lists_disconnect_the_peer() {
+    let a = start(&genesis(), vec![]);
+    let mut peer = raw_peer(&a);
+    wait_until("the peer is registered", || a.peer_count() == 1);
+
+    // A full list is answered
+    let unknown: Vec<Inventory> = (0..MAX_INVENTORY).map(|i| Inventory::Tx(format!("{:064x}", i))).collect();
+    write_message(&mut peer, &Message::GetData(unknown.clone())).unwrap();
+    match read_message(&mut peer).unwrap() {
+        Message::NotFound(items) => assert_eq!(items, unknown),
+        message => panic!("expected notfound, got {}", message.command()),
+    }
+
+    // One more item and the connection is dropped
+    let mut oversized = unknown;
+    oversized.push(Inventory::Tx("f".repeat(64)));
+    write_message(&mut peer, &Message::GetData(oversized)).unwrap();
+    assert!(read_message(&mut peer).is_err());
+    wait_until("the peer is disconnected", || a.peer_count() == 0);
+}
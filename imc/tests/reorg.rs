@@ -0,0 +1,105 @@
+// Switching the active chain to a heavier branch and back: balances, nonces and pending
+// transactions must follow, and the switch must survive reopening the data directory.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::signature::OptionalSerializableSignature;
+use crate::common::{address, data_dir, genesis, key};
+use imc::prelude::*;
+use std::fs;
+
+// 10 from key 1 to key 2, with a fee of 1
+fn payment() -> Transaction {
+    let mut transaction = Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(1),
+        nonce: 0,
+        chain_id: genesis().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key(1));
+    transaction
+}
+
+// Mines `count` blocks on `chain`'s tip paying key `miner`, and returns them
+fn mine(chain: &mut Blockchain, miner: u8, count: usize) -> Vec<Block> {
+    (0..count).map(|_| {
+        chain.mine_pending_transactions(address(miner)).unwrap();
+        chain.blocks.last().unwrap().clone()
+    }).collect()
+}
+
+fn tip(chain: &Blockchain) -> String {
+    chain.blocks.last().unwrap().hash.clone()
+}
+
+// The payment is confirmed on `chain`
+fn assert_paid(chain: &Blockchain) {
+    assert_eq!(chain.get_balance(&address(1)), BigDecimal::from(989));
+    assert_eq!(chain.get_balance(&address(2)), BigDecimal::from(10));
+    assert_eq!(chain.nonces.get(&address(1)), Some(&1));
+    assert!(!chain.mempool.contains(&payment().hash()));
+}
+
+// The payment is pending again on `chain`
+fn assert_unpaid(chain: &Blockchain) {
+    assert_eq!(chain.get_balance(&address(1)), BigDecimal::from(1_000));
+    assert_eq!(chain.get_balance(&address(2)), BigDecimal::from(0));
+    assert_eq!(chain.nonces.get(&address(1)), None);
+    assert_eq!(chain.next_nonce(&address(1)), 1);
+    assert!(chain.mempool.contains(&payment().hash()));
+    assert_eq!(chain.spendable_balance(&address(1)), BigDecimal::from(989));
+}
+
+#[test]
+fn heavier_branches_take_over_and_survive_reopening() {
+    let genesis = genesis();
+    let dir = data_dir("switch");
+
+    // Branch A pays key 2 in its first block; branch B, one block longer, has no payment
+    let mut a = Blockchain::from_genesis(&genesis);
+    a.create_transaction(payment()).unwrap();
+    let branch_a = mine(&mut a, 3, 1);
+    let mut b = Blockchain::from_genesis(&genesis);
+    let branch_b = mine(&mut b, 4, 2);
+
+    let mut chain = Blockchain::open(&dir, &genesis).unwrap();
+    assert_eq!(chain.submit_block(branch_a[0].clone()), Ok(BlockStatus::ExtendedTip));
+    assert_paid(&chain);
+
+    assert_eq!(chain.submit_block(branch_b[0].clone()), Ok(BlockStatus::SideBranch));
+    assert_paid(&chain);
+    assert_eq!(chain.submit_block(branch_b[1].clone()), Ok(BlockStatus::Reorganized { disconnected: 1, connected: 2 }));
+    assert_eq!(tip(&chain), branch_b[1].hash);
+    assert_unpaid(&chain);
+    chain.is_valid().unwrap();
+
+    // The switch and the returned payment are stored, along with how to undo branch B
+    chain.flush().unwrap();
+    drop(chain);
+    let mut chain = Blockchain::open(&dir, &genesis).unwrap();
+    assert_eq!(tip(&chain), branch_b[1].hash);
+    assert_unpaid(&chain);
+
+    // Branch A grows past B, so the reopened chain switches back and confirms the payment
+    let branch_a: Vec<Block> = branch_a.into_iter().chain(mine(&mut a, 3, 2)).collect();
+    assert_eq!(chain.submit_block(branch_a[1].clone()), Ok(BlockStatus::SideBranch));
+    assert_eq!(chain.submit_block(branch_a[2].clone()), Ok(BlockStatus::Reorganized { disconnected: 2, connected: 3 }));
+    assert_eq!(tip(&chain), branch_a[2].hash);
+    assert_paid(&chain);
+    assert_eq!(chain.balances, a.balances);
+    chain.is_valid().unwrap();
+
+    chain.flush().unwrap();
+    drop(chain);
+    let chain = Blockchain::open(&dir, &genesis).unwrap();
+    assert_eq!(tip(&chain), branch_a[2].hash);
+    assert_eq!(chain.blocks.iter().map(|block| &block.hash).collect::<Vec<_>>(), a.blocks.iter().map(|block| &block.hash).collect::<Vec<_>>());
+    assert_paid(&chain);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,98 @@
+// Nonces and chain ids stop a signed transaction from being replayed or applied out of order,
+// whether it is offered to the mempool or arrives inside a block.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::signature::OptionalSerializableSignature;
+use crate::common::{address, genesis, key};
+use imc::prelude::*;
+
+// Regtest with 1,000 coins for the address of key 1
+fn chain() -> Blockchain {
+    Blockchain::from_genesis(&genesis())
+}
+
+fn payment(chain_id: u32, nonce: u64, fee: i64) -> Transaction {
+    let mut transaction = Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(fee),
+        nonce,
+        chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key(1));
+    transaction
+}
+
+// A block on the tip holding just `transaction`, which the mempool would not have let in
+fn block_with(chain: &Blockchain, transaction: Transaction) -> Block {
+    let mut block = chain.block_template(address(3));
+    block.transactions = vec![transaction];
+    block.header.merkle_root = block.calculate_merkle_root();
+    block.hash = block.calculate_hash();
+    block.mine_block();
+    block
+}
+
+fn assert_block_rejected(chain: &mut Blockchain, transaction: Transaction, error: ReplayError) {
+    let tip = chain.blocks.last().unwrap().hash.clone();
+    let block = block_with(chain, transaction.clone());
+    let expected = ChainError::InvalidTransaction {
+        index: block.header.index,
+        position: 0,
+        tx_id: transaction.hash(),
+        error: Box::new(TxError::Replay(error)),
+    };
+    assert_eq!(chain.submit_block(block), Err(expected));
+    assert_eq!(chain.blocks.last().unwrap().hash, tip);
+}
+
+#[test]
+fn confirmed_nonces_cannot_be_used_again() {
+    let mut chain = chain();
+    let chain_id = chain.chain_id;
+    chain.create_transaction(payment(chain_id, 0, 1)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    assert_eq!(chain.next_nonce(&address(1)), 1);
+
+    // Neither the confirmed transaction itself nor a different one with its nonce
+    let stale = ReplayError::StaleNonce { expected: 1, found: 0 };
+    assert_eq!(chain.create_transaction(payment(chain_id, 0, 1)), Err(TxError::Replay(stale.clone())));
+    assert_eq!(chain.create_transaction(payment(chain_id, 0, 5)), Err(TxError::Replay(stale.clone())));
+    assert_block_rejected(&mut chain, payment(chain_id, 0, 1), stale);
+    assert_eq!(chain.get_balance(&address(2)), BigDecimal::from(10));
+}
+
+#[test]
+fn nonces_must_not_skip_ahead() {
+    let mut chain = chain();
+    let chain_id = chain.chain_id;
+    let gap = ReplayError::NonceGap { expected: 0, found: 1 };
+    assert_eq!(chain.create_transaction(payment(chain_id, 1, 1)), Err(TxError::Replay(gap.clone())));
+    assert_block_rejected(&mut chain, payment(chain_id, 1, 1), gap);
+
+    // Pending transactions count towards the next nonce
+    chain.create_transaction(payment(chain_id, 0, 1)).unwrap();
+    let gap = ReplayError::NonceGap { expected: 1, found: 2 };
+    assert_eq!(chain.create_transaction(payment(chain_id, 2, 1)), Err(TxError::Replay(gap)));
+    assert_eq!(chain.create_transaction(payment(chain_id, 0, 1)), Err(TxError::Replay(ReplayError::DuplicateNonce { nonce: 0 })));
+    chain.create_transaction(payment(chain_id, 1, 1)).unwrap();
+    assert_eq!(chain.next_nonce(&address(1)), 2);
+}
+
+#[test]
+fn transactions_for_another_chain_are_refused() {
+    let mut chain = chain();
+    let other = chain.chain_id + 1;
+    let wrong_chain = ReplayError::WrongChain { expected: chain.chain_id, found: other };
+    assert_eq!(chain.create_transaction(payment(other, 0, 1)), Err(TxError::Replay(wrong_chain.clone())));
+    assert_block_rejected(&mut chain, payment(other, 0, 1), wrong_chain);
+
+    // The same payment signed for this chain is a different transaction
+    assert_ne!(payment(other, 0, 1).hash(), payment(chain.chain_id, 0, 1).hash());
+    chain.create_transaction(payment(chain.chain_id, 0, 1)).unwrap();
+}
@@ -0,0 +1,100 @@
+// Only the holder of the key behind the sender address can spend from it: the revealed
+// public key must map to the sender, and must have signed the transaction.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::signature::{OptionalSerializableSignature, SerializablePublicKey};
+use crate::common::{address, genesis, key};
+use imc::prelude::*;
+use p256::ecdsa::VerifyingKey;
+
+// Regtest with 1,000 coins for the address of key 1
+fn chain() -> Blockchain {
+    Blockchain::from_genesis(&genesis())
+}
+
+// An unsigned payment of 10 from the address of key 1
+fn payment(chain: &Blockchain) -> Transaction {
+    Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(1),
+        nonce: 0,
+        chain_id: chain.chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    }
+}
+
+fn assert_refused(chain: &mut Blockchain, transaction: Transaction, error: SignatureError) {
+    assert_eq!(transaction.verify_sender(), Err(error.clone()));
+    assert_eq!(chain.create_transaction(transaction.clone()), Err(TxError::Signature(error.clone())));
+
+    // Nor can a miner put it in a block
+    let mut block = chain.block_template(address(3));
+    block.transactions = vec![transaction.clone()];
+    block.header.merkle_root = block.calculate_merkle_root();
+    block.hash = block.calculate_hash();
+    block.mine_block();
+    let expected = ChainError::InvalidTransaction {
+        index: block.header.index,
+        position: 0,
+        tx_id: transaction.hash(),
+        error: Box::new(TxError::Signature(error)),
+    };
+    assert_eq!(chain.submit_block(block), Err(expected));
+    assert_eq!(chain.get_balance(&address(1)), BigDecimal::from(1_000));
+}
+
+#[test]
+fn the_sender_key_is_accepted() {
+    let mut chain = chain();
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(1));
+    transaction.verify_sender().unwrap();
+    chain.create_transaction(transaction).unwrap();
+}
+
+#[test]
+fn another_key_cannot_spend_from_the_sender() {
+    let mut chain = chain();
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(2));
+    assert_refused(&mut chain, transaction, SignatureError::AddressMismatch { address: address(2) });
+}
+
+#[test]
+fn the_revealed_key_cannot_be_swapped() {
+    let mut chain = chain();
+
+    // Signed by key 2 but claiming key 1, whose address matches the sender
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(2));
+    transaction.sender_public_key = Some(SerializablePublicKey(VerifyingKey::from(&key(1))));
+    assert_refused(&mut chain, transaction, SignatureError::InvalidSignature);
+
+    // Signed by key 1 but revealing key 2
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(1));
+    transaction.sender_public_key = Some(SerializablePublicKey(VerifyingKey::from(&key(2))));
+    assert_refused(&mut chain, transaction, SignatureError::AddressMismatch { address: address(2) });
+}
+
+#[test]
+fn the_key_and_signature_are_required() {
+    let mut chain = chain();
+    let unsigned = payment(&chain);
+    assert_refused(&mut chain, unsigned, SignatureError::MissingPublicKey);
+
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(1));
+    transaction.signature = OptionalSerializableSignature(None);
+    assert_refused(&mut chain, transaction, SignatureError::MissingSignature);
+
+    let mut transaction = payment(&chain);
+    transaction.sign(&key(1));
+    transaction.amount = BigDecimal::from(900);
+    assert_refused(&mut chain, transaction, SignatureError::InvalidSignature);
+}
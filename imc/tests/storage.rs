@@ -0,0 +1,116 @@
+// Chains written to a data directory and opened again, including after a crash between
+// writing block files and committing the state file, and refusing block files that were
+// tampered with.
+
+mod common;
+
+use bigdecimal::BigDecimal;
+use ::common::signature::OptionalSerializableSignature;
+use crate::common::{address, data_dir, genesis, key};
+use imc::prelude::*;
+use imc::storage::ChainStore;
+use std::fs;
+
+fn payment(nonce: u64) -> Transaction {
+    let mut transaction = Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(10),
+        fee: BigDecimal::from(1),
+        nonce,
+        chain_id: genesis().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key(1));
+    transaction
+}
+
+fn hashes(chain: &Blockchain) -> Vec<String> {
+    chain.blocks.iter().map(|block| block.hash.clone()).collect()
+}
+
+#[test]
+fn flushed_chains_open_as_they_were() {
+    let dir = data_dir("reopen");
+    let genesis = genesis();
+    let mut chain = Blockchain::open(&dir, &genesis).unwrap();
+    chain.create_transaction(payment(0)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    chain.create_transaction(payment(1)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    chain.create_transaction(payment(2)).unwrap(); // Still pending when the chain is closed
+    chain.flush().unwrap();
+
+    let reopened = Blockchain::open(&dir, &genesis).unwrap();
+    assert_eq!(hashes(&reopened), hashes(&chain));
+    assert_eq!(reopened.balances, chain.balances);
+    assert_eq!(reopened.nonces, chain.nonces);
+    assert_eq!(reopened.get_balance(&address(2)), BigDecimal::from(20));
+    assert!(reopened.mempool.contains(&payment(2).hash()));
+    reopened.is_valid().unwrap();
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_written_without_a_committed_state_are_ignored() {
+    let dir = data_dir("crash");
+    let genesis = genesis();
+    let mut chain = Blockchain::open(&dir, &genesis).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    chain.flush().unwrap();
+    let committed = hashes(&chain);
+    let balances = chain.balances.clone();
+
+    // The process dies after writing the next block's files but before the state file,
+    // and in the middle of writing another one
+    chain.create_transaction(payment(0)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    let uncommitted = chain.blocks.last().unwrap().clone();
+    let store = ChainStore::open(&dir).unwrap();
+    store.write_block(&uncommitted).unwrap();
+    fs::write(dir.join("blocks").join(format!("{}.tmp", "f".repeat(64))), b"partial").unwrap();
+    drop(chain);
+
+    let mut reopened = Blockchain::open(&dir, &genesis).unwrap();
+    assert_eq!(hashes(&reopened), committed);
+    assert_eq!(reopened.balances, balances);
+    assert_eq!(reopened.get_balance(&address(2)), BigDecimal::from(0));
+    reopened.is_valid().unwrap();
+
+    // The uncommitted block is only known as a side branch, and mining goes on from the
+    // committed tip
+    assert!(reopened.block_tree().get(&uncommitted.hash).is_some());
+    assert!(matches!(reopened.submit_block(uncommitted.clone()), Err(ChainError::DuplicateBlock { .. })));
+    reopened.create_transaction(payment(0)).unwrap();
+    reopened.mine_pending_transactions(address(3)).unwrap();
+    assert_eq!(reopened.get_balance(&address(2)), BigDecimal::from(10));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn block_files_must_match_their_header() {
+    let dir = data_dir("tampered");
+    let mut chain = Blockchain::from_genesis(&genesis());
+    chain.create_transaction(payment(0)).unwrap();
+    chain.mine_pending_transactions(address(3)).unwrap();
+    let block = chain.blocks.last().unwrap().clone();
+    let store = ChainStore::open(&dir).unwrap();
+    store.write_block(&block).unwrap();
+    assert_eq!(store.read_block(&block.hash).unwrap().unwrap().hash, block.hash);
+
+    // Different transactions under an untouched header still hash the same
+    let mut swapped = block.clone();
+    swapped.transactions = vec![payment(1)];
+    store.write_block(&swapped).unwrap();
+    assert_eq!(store.read_block(&block.hash).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    let mut renamed = block.clone();
+    renamed.header.nonce += 1;
+    store.write_block(&renamed).unwrap();
+    assert_eq!(store.read_block(&block.hash).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,185 @@
+// A fresh node must download a long chain from its peers, pick up where it left off after a
+// restart, and ban peers that feed it invalid headers or blocks.
+
+#![cfg(feature = "p2p")]
+
+use bigdecimal::BigDecimal;
+use imc::p2p::message::{read_message, write_message, Message, Version, PROTOCOL_VERSION};
+use imc::prelude::*;
+use std::fs;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn genesis() -> GenesisSpec {
+    GenesisSpec::regtest()
+}
+
+fn mined_chain(length: u64) -> Vec<Block> {
+    let mut chain = Blockchain::from_genesis(&genesis());
+    for _ in 0..length {
+        chain.mine_pending_transactions("Miner".to_string()).unwrap();
+    }
+    chain.blocks
+}
+
+fn config(seeds: Vec<SocketAddr>) -> NodeConfig {
+    NodeConfig {
+        listen_addr: "127.0.0.1:0".parse().unwrap(),
+        seeds,
+        request_timeout: Duration::from_secs(2),
+        ..NodeConfig::default()
+    }
+}
+
+// A node holding `blocks`
+fn serving(blocks: &[Block]) -> Node {
+    let mut chain = Blockchain::from_genesis(&genesis());
+    for block in &blocks[1..] {
+        chain.submit_block(block.clone()).unwrap();
+    }
+    Node::start(config(vec![]), Arc::new(Mutex::new(chain))).unwrap()
+}
+
+fn fresh(seeds: Vec<SocketAddr>) -> Node {
+    Node::start(config(seeds), Arc::new(Mutex::new(Blockchain::from_genesis(&genesis())))).unwrap()
+}
+
+fn wait_until<F: FnMut() -> bool>(what: &str, mut condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(120);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn tip(node: &Node) -> String {
+    node.chain().lock().unwrap().blocks.last().unwrap().hash.clone()
+}
+
+fn data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("imc-sync-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+enum Tamper {
+    Headers,
+    Blocks,
+}
+
+// A peer on the right network that claims a long chain and serves `blocks`, tampered with
+fn malicious_peer(blocks: Vec<Block>, tamper: Tamper) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let version = Version {
+                protocol: PROTOCOL_VERSION,
+                chain_id: genesis().chain_id,
+                genesis_hash: blocks[0].hash.clone(),
+                best_height: 100_000,
+                best_hash: blocks.last().unwrap().hash.clone(),
+                listen_port: addr.port() as u32,
+                nonce: 42,
+            };
+            let _ = write_message(&mut stream, &Message::Version(version));
+            let _ = write_message(&mut stream, &Message::Verack);
+            while let Ok(message) = read_message(&mut stream) {
+                let reply = match message {
+                    Message::GetHeaders(_) => {
+                        let mut headers: Vec<BlockHeader> = blocks[1..].iter().map(|block| block.header.clone()).collect();
+                        if let Tamper::Headers = tamper {
+                            headers[10].bits = 0x2000_ffff;
+                        }
+                        Message::Headers(headers)
+                    }
+                    Message::GetData(items) => match (&tamper, items.first()) {
+                        (Tamper::Blocks, Some(Inventory::Block(hash))) => {
+                            let mut block = blocks.iter().find(|block| &block.hash == hash).unwrap().clone();
+                            block.coinbase.outputs.push(CoinbaseOutput { address: "Thief".to_string(), amount: BigDecimal::from(1) });
+                            Message::Block(Box::new(block))
+                        }
+                        _ => Message::NotFound(items),
+                    },
+                    _ => continue,
+                };
+                if write_message(&mut stream, &reply).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    addr
+}
+
+#[test]
+fn fresh_node_downloads_the_chain_from_its_peers() {
+    let blocks = mined_chain(3_000);
+    let a = serving(&blocks);
+    let b = serving(&blocks);
+    let fresh = fresh(vec![a.local_addr(), b.local_addr()]);
+
+    wait_until("the fresh node reaches the tip", || tip(&fresh) == blocks.last().unwrap().hash);
+    let status = fresh.sync_status();
+    assert_eq!(status.height, 3_000);
+    assert_eq!(status.best_header_height, 3_000);
+
+    let synced = fresh.chain().lock().unwrap();
+    let source = a.chain().lock().unwrap();
+    assert_eq!(synced.blocks.len(), source.blocks.len());
+    assert_eq!(synced.get_balance("Miner"), source.get_balance("Miner"));
+    assert_eq!(synced.total_supply(), source.total_supply());
+}
+
+#[test]
+fn sync_resumes_after_a_restart() {
+    let blocks = mined_chain(1_500);
+    let a = serving(&blocks);
+    let dir = data_dir("resume");
+
+    let chain = Blockchain::open(&dir, &genesis()).unwrap();
+    let node = Node::start(config(vec![a.local_addr()]), Arc::new(Mutex::new(chain))).unwrap();
+    wait_until("part of the chain is downloaded", || node.sync_status().height >= 500);
+    node.shutdown();
+    drop(node);
+
+    let chain = Blockchain::open(&dir, &genesis()).unwrap();
+    let height = chain.blocks.len() as u64 - 1;
+    assert!(height >= 500);
+    assert_eq!(chain.blocks.last().unwrap().hash, blocks[height as usize].hash);
+
+    let node = Node::start(config(vec![a.local_addr()]), Arc::new(Mutex::new(chain))).unwrap();
+    wait_until("the restarted node reaches the tip", || tip(&node) == blocks.last().unwrap().hash);
+    node.shutdown();
+    assert_eq!(Blockchain::open(&dir, &genesis()).unwrap().blocks.len(), blocks.len());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn peer_serving_invalid_headers_is_banned() {
+    let blocks = mined_chain(200);
+    let a = serving(&blocks);
+    let malicious = malicious_peer(blocks.clone(), Tamper::Headers);
+    let fresh = fresh(vec![malicious, a.local_addr()]);
+
+    wait_until("the malicious peer is banned", || fresh.is_banned(malicious));
+    wait_until("the fresh node reaches the tip", || tip(&fresh) == blocks.last().unwrap().hash);
+    assert!(!fresh.is_banned(a.local_addr()));
+    assert!(fresh.peers().iter().all(|peer| peer.addr != malicious));
+}
+
+#[test]
+fn peer_serving_blocks_that_do_not_match_their_headers_is_banned() {
+    let blocks = mined_chain(200);
+    let a = serving(&blocks);
+    let malicious = malicious_peer(blocks.clone(), Tamper::Blocks);
+    let fresh = fresh(vec![malicious, a.local_addr()]);
+
+    wait_until("the malicious peer is banned", || fresh.is_banned(malicious));
+    wait_until("the fresh node reaches the tip", || tip(&fresh) == blocks.last().unwrap().hash);
+    assert!(fresh.chain().lock().unwrap().is_valid().is_ok());
+}
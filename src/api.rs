@@ -0,0 +1,366 @@
+//! The methods `infinimathd` answers over RPC.
+//!
+//! Parameters are positional, given as a JSON array; `?` marks optional ones. Amounts are
+//! decimal strings so that they round-trip exactly. Public keys and signatures are arrays of
+//! bytes. The result types are shared with the `infinimath` client, which decodes responses
+//! into them.
+//!
+//! | Method | Params | Result |
+//! |---|---|---|
+//! | `getinfo` | | [`NodeInfo`] |
+//! | `getblock` | hash | [`BlockInfo`], side branches included |
+//! | `getblockbyheight` | height | [`BlockInfo`] on the active chain |
+//! | `gettransaction` | txid | [`TransactionInfo`], pending or on the active chain |
+//! | `getaccount` | address | [`AccountInfo`] |
+//! | `getbalance` | address | confirmed balance, an amount |
+//! | `sendrawtransaction` | transaction | txid |
+//! | `getmempool` | address? | pending transactions, all or sent or received by `address` |
+//! | `getdifficulty` | | [`DifficultyInfo`] |
+//! | `generate` | address | hash of a block mined paying `address` |
+//! | `getblocktemplate` | address | [`BlockTemplate`] of a block paying `address` |
+//! | `submitblock` | header | [`SubmittedBlock`] |
+//! | `verifychain` | | `null` if the whole chain is valid |
+//! | `getsubchainblock` | number | sub-chain block |
+//! | `createsubchainblock` | | the sub-chain block added, holding the next prime |
+//! | `minesubchainblock` | difficulty | the sub-chain block mined |
+//! | `getsubchainbalance` | address | sub-chain balance, a number |
+//! | `createpixblocks` | | the sub-chain blocks added, holding the next digits of pi |
+//! | `callcontract` | id, function, params | output of the contract, a string |
+//! | `stop` | | a message; the daemon shuts down |
+//!
+//! A transaction is `{sender, receiver, amount, fee, nonce, chain_id, sender_public_key,
+//! signature}`, signed over its canonical encoding. A block is `{header: {index, timestamp,
+//! previous_hash, merkle_root, miner, bits, nonce}, hash, coinbase: {outputs: [{address,
+//! amount}]}, transactions}`. `callcontract` params are an object of string values.
+//!
+//! Miners outside the node call `getblocktemplate`, search the template header's `nonce`
+//! until its hash meets `target`, and send back the header alone with `submitblock`. The
+//! node rebuilds the block from the template with the header's `merkle_root`, so only the
+//! nonce, and the timestamp within the consensus limits, may be changed. Templates are
+//! remembered for the last [`DEFAULT_MAX_TEMPLATES`](imc::work::DEFAULT_MAX_TEMPLATES)
+//! requests; a template whose tip has moved on still mines a valid block, just on a side
+//! branch.
+//!
+//! Failures use the codes in [`crate::errors`]: a refused transaction or block gets the code
+//! of the chain error that refused it.
+
+use crate::errors::{CONTRACT_ERROR, NOT_FOUND};
+use crate::miner::mine_block;
+use crate::rpc::{RpcError, INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use common::address::validate_address;
+use imc::block::{Block, BlockHeader};
+use imc::block_tree::BlockStatus;
+use imc::difficulty::compact_to_target;
+use imc::p2p::node::Node;
+use imc::transaction::{Transaction, TxId};
+use imc::work::{BlockTemplate, WorkQueue};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use subchains::utils::{pix, primex};
+use subchains::{calculate_subchain_hash, SubChain, SubChainBlock};
+
+const PIX_BLOCKS_PER_CALL: usize = 10;
+
+/// Result of `getinfo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub network: String,
+    pub chain_id: u32,
+    pub height: u64,
+    pub best_hash: String,
+    pub best_header_height: u64,
+    pub peers: usize,
+    pub mempool_size: usize,
+}
+
+/// Result of `getblock` and `getblockbyheight`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockInfo {
+    pub height: u64,
+    pub confirmations: u64, // 0 for blocks off the active chain
+    pub block: Block,
+}
+
+/// Result of `gettransaction`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionInfo {
+    pub transaction: Transaction,
+    pub block_hash: Option<String>, // None while pending
+    pub height: Option<u64>,
+    pub confirmations: u64,
+}
+
+/// Result of `getaccount`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub address: String,
+    pub balance: BigDecimal, // Confirmed
+    pub spendable_balance: BigDecimal, // Confirmed, less what pending transactions spend
+    pub next_nonce: u64, // Counting pending transactions
+    pub used: bool, // Whether the address appears anywhere on the chain
+}
+
+/// Result of `getdifficulty`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyInfo {
+    pub bits: u32, // Compact target of the tip
+    pub next_bits: u32, // Compact target the next block must meet
+    pub next_target: String, // `next_bits` expanded, 64 hex digits
+    pub chain_work: String, // Expected hashes behind the active chain, a decimal integer
+}
+
+/// Result of `submitblock`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmittedBlock {
+    pub hash: String,
+    pub status: String, // `extended_tip`, `reorganized` or `side_branch`
+}
+
+pub struct Api {
+    node: Arc<Node>,
+    subchain: Arc<Mutex<SubChain>>,
+    work: Mutex<WorkQueue>, // Locked after the chain
+    stop: Arc<AtomicBool>, // Set by `stop`, and watched by `generate`
+}
+
+impl Api {
+    pub fn new(node: Arc<Node>, subchain: Arc<Mutex<SubChain>>, stop: Arc<AtomicBool>) -> Self {
+        Api { node, subchain, work: Mutex::new(WorkQueue::default()), stop }
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "getinfo" => to_value(self.info()),
+            "getblock" => to_value(self.block(&param::<String>(&params, 0, "hash")?)?),
+            "getblockbyheight" => to_value(self.block_by_height(param(&params, 0, "height")?)?),
+            "gettransaction" => to_value(self.transaction(&param::<String>(&params, 0, "txid")?)?),
+            "getaccount" => to_value(self.account(&param::<String>(&params, 0, "address")?)),
+            "getbalance" => {
+                let address: String = param(&params, 0, "address")?;
+                to_value(self.node.chain().lock().unwrap().get_balance(&address))
+            }
+            "getmempool" => to_value(self.mempool(optional_param::<String>(&params, 0, "address")?)),
+            "sendrawtransaction" => to_value(self.send(param(&params, 0, "transaction")?)?),
+            "getdifficulty" => to_value(self.difficulty()),
+            "generate" => to_value(self.generate(param(&params, 0, "address")?)?),
+            "getblocktemplate" => to_value(self.block_template(param(&params, 0, "address")?)?),
+            "submitblock" => to_value(self.submit_block(param(&params, 0, "header")?)?),
+            "verifychain" => to_value(self.node.chain().lock().unwrap().is_valid()?),
+            "getsubchainblock" => to_value(self.subchain_block(param(&params, 0, "number")?)?),
+            "createsubchainblock" => to_value(self.create_subchain_block()?),
+            "minesubchainblock" => to_value(self.mine_subchain_block(param(&params, 0, "difficulty")?)?),
+            "getsubchainbalance" => to_value(self.subchain.lock().unwrap().get_balance(&param::<String>(&params, 0, "address")?)),
+            "createpixblocks" => to_value(self.create_pix_blocks()?),
+            "callcontract" => {
+                let id: String = param(&params, 0, "id")?;
+                let function: String = param(&params, 1, "function")?;
+                let arguments = optional_param(&params, 2, "params")?.unwrap_or_default();
+                to_value(self.call_contract(&id, &function, arguments)?)
+            }
+            "stop" => {
+                self.stop.store(true, Ordering::SeqCst);
+                to_value("infinimathd stopping")
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        }
+    }
+
+    fn info(&self) -> NodeInfo {
+        let status = self.node.sync_status();
+        let chain = self.node.chain().lock().unwrap();
+        NodeInfo {
+            network: chain.genesis().network.clone(),
+            chain_id: chain.chain_id,
+            height: status.height,
+            best_hash: chain.blocks.last().unwrap().hash.clone(),
+            best_header_height: status.best_header_height,
+            peers: self.node.peer_count(),
+            mempool_size: chain.mempool.len(),
+        }
+    }
+
+    fn account(&self, address: &str) -> AccountInfo {
+        let chain = self.node.chain().lock().unwrap();
+        AccountInfo {
+            address: address.to_string(),
+            balance: chain.get_balance(address),
+            spendable_balance: chain.spendable_balance(address),
+            next_nonce: chain.next_nonce(address),
+            used: chain.is_address_used(address),
+        }
+    }
+
+    fn block(&self, hash: &str) -> Result<BlockInfo, RpcError> {
+        let chain = self.node.chain().lock().unwrap();
+        let node = chain.block_tree().get(hash).ok_or_else(|| not_found(format!("block {}", hash)))?;
+        let tip = chain.blocks.len() as u64 - 1;
+        let on_active_chain = chain.blocks.get(node.height as usize).is_some_and(|block| block.hash == hash);
+        let confirmations = if on_active_chain { tip - node.height + 1 } else { 0 };
+        Ok(BlockInfo { height: node.height, confirmations, block: node.block.clone() })
+    }
+
+    fn block_by_height(&self, height: u64) -> Result<BlockInfo, RpcError> {
+        let chain = self.node.chain().lock().unwrap();
+        let block = chain.blocks.get(height as usize).ok_or_else(|| not_found(format!("block at height {}", height)))?;
+        Ok(BlockInfo { height, confirmations: chain.blocks.len() as u64 - height, block: block.clone() })
+    }
+
+    // Looks in the mempool, then down the active chain from the tip
+    fn transaction(&self, tx_id: &str) -> Result<TransactionInfo, RpcError> {
+        let chain = self.node.chain().lock().unwrap();
+        if let Some(entry) = chain.mempool.get(tx_id) {
+            return Ok(TransactionInfo { transaction: entry.transaction.clone(), block_hash: None, height: None, confirmations: 0 });
+        }
+        let tip = chain.blocks.len() as u64 - 1;
+        for block in chain.blocks.iter().rev() {
+            if let Some(transaction) = block.transactions.iter().find(|transaction| transaction.hash() == tx_id) {
+                return Ok(TransactionInfo {
+                    transaction: transaction.clone(),
+                    block_hash: Some(block.hash.clone()),
+                    height: Some(block.header.index),
+                    confirmations: tip - block.header.index + 1,
+                });
+            }
+        }
+        Err(not_found(format!("transaction {}", tx_id)))
+    }
+
+    fn difficulty(&self) -> DifficultyInfo {
+        let chain = self.node.chain().lock().unwrap();
+        let next_bits = chain.next_bits();
+        DifficultyInfo {
+            bits: chain.blocks.last().unwrap().header.bits,
+            next_bits,
+            next_target: format!("{:064x}", compact_to_target(next_bits)),
+            chain_work: chain.chain_work().to_string(),
+        }
+    }
+
+    fn mempool(&self, address: Option<String>) -> Vec<Transaction> {
+        let chain = self.node.chain().lock().unwrap();
+        match address {
+            Some(address) => chain.mempool.for_address(&address).into_iter().cloned().collect(),
+            None => chain.mempool.transactions(),
+        }
+    }
+
+    fn send(&self, transaction: Transaction) -> Result<TxId, RpcError> {
+        Ok(self.node.submit_transaction(transaction)?)
+    }
+
+    // Mines one block paying `address` and returns its hash
+    fn generate(&self, address: String) -> Result<String, RpcError> {
+        validate_address(&address).map_err(|e| RpcError::invalid_params(format!("invalid address {}: {}", address, e)))?;
+        match mine_block(&self.node, &address, &self.stop) {
+            Some(result) => Ok(result?),
+            None => Err(RpcError::server("infinimathd is stopping")),
+        }
+    }
+
+    fn block_template(&self, address: String) -> Result<BlockTemplate, RpcError> {
+        validate_address(&address).map_err(|e| RpcError::invalid_params(format!("invalid address {}: {}", address, e)))?;
+        let chain = self.node.chain().lock().unwrap();
+        Ok(self.work.lock().unwrap().template(&chain, address))
+    }
+
+    fn submit_block(&self, header: BlockHeader) -> Result<SubmittedBlock, RpcError> {
+        let block = self.work.lock().unwrap().block_for(header)?;
+        let hash = block.hash.clone();
+        let status = match self.node.submit_block(block)? {
+            BlockStatus::ExtendedTip => "extended_tip",
+            BlockStatus::Reorganized { .. } => "reorganized",
+            BlockStatus::SideBranch => "side_branch",
+        };
+        Ok(SubmittedBlock { hash, status: status.to_string() })
+    }
+
+    fn subchain_block(&self, number: u64) -> Result<SubChainBlock, RpcError> {
+        let subchain = self.subchain.lock().unwrap();
+        subchain.blocks.get(number as usize).cloned().ok_or_else(|| not_found(format!("sub-chain block {}", number)))
+    }
+
+    // Adds a block holding the prime after the one in the latest block
+    fn create_subchain_block(&self) -> Result<SubChainBlock, RpcError> {
+        let mut subchain = self.subchain.lock().unwrap();
+        let last_prime = primex::get_last_prime_or_initialize(subchain.blocks.last().map(|block| block.result.as_str()));
+        let next_prime = primex::find_next_prime(&last_prime, 5);
+        let block = next_subchain_block(&subchain, next_prime.to_string());
+        subchain.add_block(block.clone());
+        flush(&mut subchain)?;
+        Ok(block)
+    }
+
+    fn mine_subchain_block(&self, difficulty: usize) -> Result<SubChainBlock, RpcError> {
+        let mut subchain = self.subchain.lock().unwrap();
+        let mut block = subchain.get_latest_block().clone();
+        subchain.mine_block(&mut block, difficulty);
+        flush(&mut subchain)?;
+        Ok(block)
+    }
+
+    // Adds blocks holding the next decimals of Pi
+    fn create_pix_blocks(&self) -> Result<Vec<SubChainBlock>, RpcError> {
+        let mut subchain = self.subchain.lock().unwrap();
+        let mut blocks = Vec::new();
+        for _ in 0..PIX_BLOCKS_PER_CALL {
+            let next_pi = pix::find_next_pi(subchain.blocks.len());
+            let block = next_subchain_block(&subchain, next_pi.to_string());
+            subchain.add_block(block.clone());
+            blocks.push(block);
+        }
+        flush(&mut subchain)?;
+        Ok(blocks)
+    }
+
+    fn call_contract(&self, id: &str, function: &str, params: HashMap<String, String>) -> Result<String, RpcError> {
+        let mut chain = self.node.chain().lock().unwrap();
+        if !chain.smart_contracts.contains_key(id) {
+            return Err(not_found(format!("contract {}", id)));
+        }
+        chain.execute_smart_contract(id, function, params).map_err(|e| RpcError::new(CONTRACT_ERROR, e))
+    }
+}
+
+fn next_subchain_block(subchain: &SubChain, result: String) -> SubChainBlock {
+    let mut block = SubChainBlock {
+        block_number: subchain.blocks.len() as u64,
+        timestamp: Utc::now().timestamp() as u64,
+        result,
+        prev_block_hash: subchain.get_last_block_hash(),
+        nonce: 0,
+        hash: String::new(),
+    };
+    block.hash = calculate_subchain_hash(&block);
+    block
+}
+
+fn not_found(what: String) -> RpcError {
+    RpcError::new(NOT_FOUND, format!("{} not found", what))
+}
+
+fn flush(subchain: &mut SubChain) -> Result<(), RpcError> {
+    subchain.flush().map_err(|e| RpcError::server(format!("failed to save sub-chain data: {}", e)))
+}
+
+fn to_value<T: Serialize>(result: T) -> Result<Value, RpcError> {
+    serde_json::to_value(result).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn param<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<T, RpcError> {
+    optional_param(params, index, name)?.ok_or_else(|| RpcError::invalid_params(format!("missing parameter {}", name)))
+}
+
+fn optional_param<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid parameter {}: {}", name, e))),
+    }
+}
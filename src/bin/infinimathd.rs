@@ -0,0 +1,44 @@
+use infinimath::config::{take_flag, take_genesis_spec, DaemonConfig, DEFAULT_CONFIG_FILE};
+use infinimath::daemon::Daemon;
+use log::error;
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use std::sync::atomic::Ordering;
+
+fn fail(message: String) -> ! {
+    error!("{}", message);
+    exit(1);
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut args: Vec<String> = env::args().collect();
+    let genesis = take_genesis_spec(&mut args).unwrap_or_else(|e| fail(e));
+    let config_file = take_flag(&mut args, &["--config"]).unwrap_or_else(|e| fail(e)).map(|(_, file)| file);
+    if args.len() > 1 {
+        eprintln!("Usage: infinimathd [--config <file>] [--network <mainnet|testnet|regtest> | --genesis <file>]");
+        exit(2);
+    }
+
+    // An explicit --config must exist; the default one is optional
+    let config = match config_file {
+        Some(file) => DaemonConfig::load(&file).unwrap_or_else(|e| fail(format!("Failed to load {}: {}", file, e))),
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+            DaemonConfig::load(DEFAULT_CONFIG_FILE).unwrap_or_else(|e| fail(format!("Failed to load {}: {}", DEFAULT_CONFIG_FILE, e)))
+        }
+        None => DaemonConfig::default(),
+    };
+    let genesis = match genesis {
+        Some(genesis) => genesis,
+        None => config.genesis_spec().unwrap_or_else(|e| fail(format!("Failed to load the genesis spec: {}", e))),
+    };
+
+    let daemon = Daemon::start(&config, &genesis).unwrap_or_else(|e| fail(format!("Failed to start: {}", e)));
+    let stop = daemon.stop_handle();
+    ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst)).unwrap_or_else(|e| fail(format!("Failed to handle signals: {}", e)));
+
+    daemon.wait();
+    daemon.shutdown();
+}
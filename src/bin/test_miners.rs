@@ -1,7 +1,9 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use imc::blockchain::{Blockchain, Block};
+use imc::block_tree::BlockStatus;
+use imc::blockchain::Blockchain;
+use imc::work::WorkQueue;
 use rand::Rng;
 use std::fs::File;
 use std::io::Write;
@@ -24,30 +26,30 @@ impl Miner {
         }
     }
 
-    fn mine(&mut self, blockchain: Arc<Mutex<Blockchain>>, duration: Duration, miners: Arc<Mutex<Vec<Miner>>>) {
+    // Nonces are searched on a block template with the chain unlocked, so miners only
+    // contend for the chain to fetch work and to submit a solution
+    fn mine(&mut self, blockchain: Arc<Mutex<Blockchain>>, work: Arc<Mutex<WorkQueue>>, duration: Duration, miners: Arc<Mutex<Vec<Miner>>>) {
         let start_time = Instant::now();
         while start_time.elapsed() < duration {
-            let mut blockchain = blockchain.lock().unwrap();
-            let previous_block = blockchain.blocks.last().unwrap();
-            let mut block = Block::new(
-                previous_block.index + 1,
-                start_time.elapsed().as_millis() as u128,
-                previous_block.hash.clone(),
-                format!("Miner {}'s block", self.id),
-                0,
-            );
-
-            // Simulate mining with computing power
-            for _ in 0..self.computing_power {
-                block.nonce += 1;
-                block.hash = block.calculate_hash();
-                if &block.hash[..blockchain.difficulty] == &"0".repeat(blockchain.difficulty) {
+            let template = {
+                let blockchain = blockchain.lock().unwrap();
+                work.lock().unwrap().template(&blockchain, self.id.clone())
+            };
+
+            // Simulate mining with computing power, starting over when someone else extends the chain
+            let mut nonce = 0;
+            while start_time.elapsed() < duration && blockchain.lock().unwrap().blocks.last().unwrap().hash == template.header.previous_hash {
+                if let Some(header) = template.solve(nonce..nonce + self.computing_power) {
+                    let block = work.lock().unwrap().block_for(header).expect("Template was forgotten");
+                    if blockchain.lock().unwrap().submit_block(block).expect("Mined block was rejected") == BlockStatus::SideBranch {
+                        break; // Another miner got there first
+                    }
                     println!("Miner {} mined a block!", self.id);
-                    blockchain.blocks.push(block);
                     self.blocks_mined += 1;
                     self.distribute_rewards(miners.clone());
                     return; // Exit the mining loop after finding a valid block
                 }
+                nonce += self.computing_power;
             }
         }
     }
@@ -86,11 +88,9 @@ impl Miner {
 }
 
 fn main() {
-    let blockchain = Arc::new(Mutex::new({
-        let mut bc = Blockchain::new();
-        bc.difficulty = 5; // Increase difficulty to make mining more challenging
-        bc
-    }));
+    // The initial target needs about as much work as five leading zero hex digits
+    let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+    let work = Arc::new(Mutex::new(WorkQueue::new(1000))); // A template for each miner
     let mut rng = rand::thread_rng(); // Create a random number generator
 
     // Create 1000 miners with random computing power
@@ -106,10 +106,11 @@ fn main() {
     let mut handles = vec![];
     for i in 0..1000 {
         let blockchain = Arc::clone(&blockchain);
+        let work = Arc::clone(&work);
         let miners = Arc::clone(&miners);
         let handle = thread::spawn(move || {
             let mut miner = miners.lock().unwrap()[i].clone();
-            miner.mine(blockchain, duration, miners);
+            miner.mine(blockchain, work, duration, miners);
         });
         handles.push(handle);
     }
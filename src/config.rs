@@ -0,0 +1,167 @@
+//! Settings of `infinimathd`, read from a JSON file, and the command-line flags the daemon and
+//! the `infinimath` client share.
+//!
+//! Every field is optional. A minimal regtest config looks like:
+//!
+//! ```json
+//! {
+//!   "network": "regtest",
+//!   "p2p": { "seeds": ["127.0.0.1:29333"] },
+//!   "mining": { "enabled": true, "address": "<address>" }
+//! }
+//! ```
+
+use common::storage::read_json;
+use imc::genesis::{GenesisSpec, Network};
+use imc::p2p::node::{NodeConfig, DEFAULT_PORT};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Read from the working directory when `infinimathd` is given no `--config`.
+pub const DEFAULT_CONFIG_FILE: &str = "infinimathd.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    pub network: String, // One of the built-in networks, unless `genesis` is set
+    pub genesis: Option<PathBuf>, // Genesis spec file of a custom network
+    pub data_dir: PathBuf,
+    pub p2p: P2pConfig,
+    pub rpc: RpcConfig,
+    pub mining: MiningConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct P2pConfig {
+    pub listen: Option<SocketAddr>, // All interfaces on the network's default port if not set
+    pub seeds: Vec<SocketAddr>,
+    pub max_peers: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RpcConfig {
+    pub listen: Option<SocketAddr>, // Localhost on the network's default port if not set
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MiningConfig {
+    pub enabled: bool,
+    pub address: String, // Paid the rewards of mined blocks
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            network: "mainnet".to_string(),
+            genesis: None,
+            data_dir: PathBuf::from("chaindata"),
+            p2p: P2pConfig::default(),
+            rpc: RpcConfig::default(),
+            mining: MiningConfig::default(),
+        }
+    }
+}
+
+impl Default for P2pConfig {
+    fn default() -> Self {
+        P2pConfig { listen: None, seeds: vec![], max_peers: NodeConfig::default().max_peers }
+    }
+}
+
+impl DaemonConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        read_json(path)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())))
+    }
+
+    /// The spec of the network this config selects.
+    pub fn genesis_spec(&self) -> io::Result<GenesisSpec> {
+        match &self.genesis {
+            Some(path) => GenesisSpec::load(path),
+            None => self.network.parse::<Network>()
+                .map(|network| network.genesis())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)),
+        }
+    }
+
+    /// Where the main chain of `genesis`'s network is stored. Mainnet keeps the layout from
+    /// before there were other networks.
+    pub fn chain_dir(&self, genesis: &GenesisSpec) -> PathBuf {
+        match genesis.network.as_str() {
+            "mainnet" => self.data_dir.join("imc"),
+            network => self.data_dir.join(network).join("imc"),
+        }
+    }
+
+    /// Where the sub-chain of `genesis`'s network is stored, laid out like [`Self::chain_dir`].
+    pub fn subchain_dir(&self, genesis: &GenesisSpec) -> PathBuf {
+        match genesis.network.as_str() {
+            "mainnet" => self.data_dir.join("subchain"),
+            network => self.data_dir.join(network).join("subchain"),
+        }
+    }
+
+    pub fn node_config(&self, genesis: &GenesisSpec) -> NodeConfig {
+        let (p2p_port, _) = default_ports(&genesis.network);
+        NodeConfig {
+            listen_addr: self.p2p.listen.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], p2p_port))),
+            seeds: self.p2p.seeds.clone(),
+            max_peers: self.p2p.max_peers,
+            ..NodeConfig::default()
+        }
+    }
+
+    pub fn rpc_addr(&self, genesis: &GenesisSpec) -> SocketAddr {
+        self.rpc.listen.unwrap_or_else(|| default_rpc_addr(&genesis.network))
+    }
+}
+
+/// Default P2P and RPC ports of a network, different for each so that nodes of several
+/// networks can run on one machine.
+pub fn default_ports(network: &str) -> (u16, u16) {
+    match network {
+        "mainnet" => (DEFAULT_PORT, DEFAULT_PORT - 1),
+        "testnet" => (19333, 19332),
+        _ => (29333, 29332),
+    }
+}
+
+pub fn default_rpc_addr(network: &str) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], default_ports(network).1))
+}
+
+/// Removes `--network <name>` or `--genesis <file>` from `args` and returns the spec they
+/// select, if either is given.
+pub fn take_genesis_spec(args: &mut Vec<String>) -> Result<Option<GenesisSpec>, String> {
+    let mut spec = None;
+    while let Some(value) = take_flag(args, &["--network", "--genesis"])? {
+        if spec.is_some() {
+            return Err("Use only one of --network and --genesis".to_string());
+        }
+        let (flag, value) = value;
+        spec = Some(if flag == "--network" {
+            value.parse::<Network>().map_err(|e| e.to_string())?.genesis()
+        } else {
+            GenesisSpec::load(&value).map_err(|e| format!("Failed to load genesis spec {}: {}", value, e))?
+        });
+    }
+    Ok(spec)
+}
+
+/// Removes the first of `flags` from `args` along with its value, and returns both.
+pub fn take_flag(args: &mut Vec<String>, flags: &[&str]) -> Result<Option<(String, String)>, String> {
+    let Some(position) = args.iter().position(|arg| flags.contains(&arg.as_str())) else {
+        return Ok(None);
+    };
+    let flag = args.remove(position);
+    if position >= args.len() {
+        return Err(format!("{} needs a value", flag));
+    }
+    let value = args.remove(position);
+    Ok(Some((flag, value)))
+}
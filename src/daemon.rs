@@ -0,0 +1,96 @@
+//! `infinimathd`: the persisted chain served to peers and RPC clients, with optional mining.
+//!
+//! Each part runs on threads of its own: the P2P [`Node`], the [`RpcServer`] and the
+//! [`Miner`]. They share the chain through the node. Shutting down stops them in reverse
+//! order of their dependencies, then writes out what is not saved yet.
+
+use crate::api::Api;
+use crate::config::DaemonConfig;
+use crate::miner::Miner;
+use crate::rpc::{Handler, RpcServer};
+use common::address::validate_address;
+use imc::blockchain::Blockchain;
+use imc::genesis::GenesisSpec;
+use imc::p2p::node::Node;
+use log::{error, info};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use subchains::SubChain;
+
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct Daemon {
+    node: Arc<Node>,
+    subchain: Arc<Mutex<SubChain>>,
+    rpc: RpcServer,
+    miner: Option<Miner>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Daemon {
+    /// Opens the stored chains of `genesis`'s network and starts serving them as `config`
+    /// says.
+    pub fn start(config: &DaemonConfig, genesis: &GenesisSpec) -> io::Result<Daemon> {
+        if config.mining.enabled {
+            validate_address(&config.mining.address)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid mining address {:?}: {}", config.mining.address, e)))?;
+        }
+
+        let chain = Blockchain::open(config.chain_dir(genesis), genesis)?;
+        info!("Loaded the {} chain at height {}", genesis.network, chain.blocks.len() - 1);
+        let subchain = SubChain::open(config.subchain_dir(genesis)).map_err(|e| io::Error::other(e.to_string()))?;
+        let subchain = Arc::new(Mutex::new(subchain));
+
+        let node = Arc::new(Node::start(config.node_config(genesis), Arc::new(Mutex::new(chain)))?);
+        let stop = Arc::new(AtomicBool::new(false));
+        let api = Api::new(Arc::clone(&node), Arc::clone(&subchain), Arc::clone(&stop));
+        let handler: Arc<Handler> = Arc::new(move |method: &str, params| api.call(method, params));
+        let rpc = RpcServer::start(config.rpc_addr(genesis), handler)?;
+        let miner = config.mining.enabled.then(|| Miner::start(Arc::clone(&node), config.mining.address.clone()));
+
+        Ok(Daemon { node, subchain, rpc, miner, stop })
+    }
+
+    pub fn p2p_addr(&self) -> SocketAddr {
+        self.node.local_addr()
+    }
+
+    pub fn rpc_addr(&self) -> SocketAddr {
+        self.rpc.local_addr()
+    }
+
+    pub fn node(&self) -> &Arc<Node> {
+        &self.node
+    }
+
+    /// Setting this makes [`Daemon::wait`] return, as the `stop` RPC does.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Blocks until the daemon is asked to stop.
+    pub fn wait(&self) {
+        while !self.stop.load(Ordering::SeqCst) {
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// Stops RPC, mining and P2P, then saves the chains.
+    pub fn shutdown(mut self) {
+        info!("Shutting down");
+        self.stop.store(true, Ordering::SeqCst);
+        self.rpc.shutdown();
+        if let Some(mut miner) = self.miner.take() {
+            miner.shutdown();
+        }
+        self.node.shutdown(); // Flushes the main chain
+        if let Err(e) = self.subchain.lock().unwrap().flush() {
+            error!("Failed to save sub-chain data: {}", e);
+        }
+        info!("Shut down");
+    }
+}
@@ -0,0 +1,116 @@
+//! Error codes of the RPC methods, one for each way the chain can refuse a request.
+//!
+//! Codes outside the JSON-RPC 2.0 ones in [`crate::rpc`] are grouped by what was refused:
+//! `-32001..` for lookups, `-32100..` for transactions and `-32200..` for blocks. They are
+//! part of the API and never change meaning once published; new ones take the next free code.
+
+use crate::rpc::RpcError;
+use imc::error::{ChainError, TxError};
+use imc::transaction::{ReplayError, SignatureError};
+use imc::work::WorkError;
+
+/// The block, transaction, sub-chain block or contract asked for does not exist.
+pub const NOT_FOUND: i64 = -32001;
+/// The contract exists but refused the call.
+pub const CONTRACT_ERROR: i64 = -32002;
+/// A submitted header is not for any block template the node remembers.
+pub const UNKNOWN_TEMPLATE: i64 = -32003;
+
+// Transactions, from `TxError`
+pub const INVALID_ADDRESS: i64 = -32100;
+pub const NON_POSITIVE_AMOUNT: i64 = -32101;
+pub const NEGATIVE_FEE: i64 = -32102;
+pub const MISSING_PUBLIC_KEY: i64 = -32103;
+pub const ADDRESS_MISMATCH: i64 = -32104;
+pub const MISSING_SIGNATURE: i64 = -32105;
+pub const INVALID_SIGNATURE: i64 = -32106;
+pub const WRONG_CHAIN: i64 = -32107;
+pub const STALE_NONCE: i64 = -32108;
+pub const DUPLICATE_NONCE: i64 = -32109;
+pub const NONCE_GAP: i64 = -32110;
+pub const INSUFFICIENT_BALANCE: i64 = -32111;
+pub const REPLACEMENT_FEE_TOO_LOW: i64 = -32112;
+pub const MEMPOOL_FULL: i64 = -32113;
+
+// Blocks, from `ChainError`
+pub const INVALID_HASH: i64 = -32200;
+pub const BROKEN_LINK: i64 = -32201;
+pub const INVALID_MERKLE_ROOT: i64 = -32202;
+pub const INVALID_COINBASE: i64 = -32203;
+pub const EXCESSIVE_MINT: i64 = -32204;
+pub const INVALID_TRANSACTION: i64 = -32205;
+pub const INSUFFICIENT_PROOF_OF_WORK: i64 = -32206;
+pub const WRONG_TARGET: i64 = -32207;
+pub const INVALID_INDEX: i64 = -32208;
+pub const TIMESTAMP_TOO_OLD: i64 = -32209;
+pub const TIMESTAMP_TOO_NEW: i64 = -32210;
+pub const BLOCK_TOO_LARGE: i64 = -32211;
+pub const DUPLICATE_BLOCK: i64 = -32212;
+pub const UNKNOWN_PARENT: i64 = -32213;
+pub const INVALID_ANCESTOR: i64 = -32214;
+pub const STATE_MISMATCH: i64 = -32215;
+
+pub fn tx_error_code(e: &TxError) -> i64 {
+    match e {
+        TxError::InvalidAddress(_) => INVALID_ADDRESS,
+        TxError::NonPositiveAmount => NON_POSITIVE_AMOUNT,
+        TxError::NegativeFee => NEGATIVE_FEE,
+        TxError::Signature(SignatureError::MissingPublicKey) => MISSING_PUBLIC_KEY,
+        TxError::Signature(SignatureError::AddressMismatch { .. }) => ADDRESS_MISMATCH,
+        TxError::Signature(SignatureError::MissingSignature) => MISSING_SIGNATURE,
+        TxError::Signature(SignatureError::InvalidSignature) => INVALID_SIGNATURE,
+        TxError::Replay(ReplayError::WrongChain { .. }) => WRONG_CHAIN,
+        TxError::Replay(ReplayError::StaleNonce { .. }) => STALE_NONCE,
+        TxError::Replay(ReplayError::DuplicateNonce { .. }) => DUPLICATE_NONCE,
+        TxError::Replay(ReplayError::NonceGap { .. }) => NONCE_GAP,
+        TxError::InsufficientBalance { .. } => INSUFFICIENT_BALANCE,
+        TxError::ReplacementFeeTooLow { .. } => REPLACEMENT_FEE_TOO_LOW,
+        TxError::MempoolFull { .. } => MEMPOOL_FULL,
+    }
+}
+
+pub fn chain_error_code(e: &ChainError) -> i64 {
+    match e {
+        ChainError::InvalidHash { .. } => INVALID_HASH,
+        ChainError::BrokenLink { .. } => BROKEN_LINK,
+        ChainError::InvalidMerkleRoot { .. } => INVALID_MERKLE_ROOT,
+        ChainError::InvalidCoinbase { .. } => INVALID_COINBASE,
+        ChainError::ExcessiveMint { .. } => EXCESSIVE_MINT,
+        ChainError::InvalidTransaction { .. } => INVALID_TRANSACTION,
+        ChainError::InsufficientProofOfWork { .. } => INSUFFICIENT_PROOF_OF_WORK,
+        ChainError::WrongTarget { .. } => WRONG_TARGET,
+        ChainError::InvalidIndex { .. } => INVALID_INDEX,
+        ChainError::TimestampTooOld { .. } => TIMESTAMP_TOO_OLD,
+        ChainError::TimestampTooNew { .. } => TIMESTAMP_TOO_NEW,
+        ChainError::BlockTooLarge { .. } => BLOCK_TOO_LARGE,
+        ChainError::DuplicateBlock { .. } => DUPLICATE_BLOCK,
+        ChainError::UnknownParent { .. } => UNKNOWN_PARENT,
+        ChainError::InvalidAncestor { .. } => INVALID_ANCESTOR,
+        ChainError::StateMismatch { .. } => STATE_MISMATCH,
+    }
+}
+
+impl From<TxError> for RpcError {
+    fn from(e: TxError) -> Self {
+        RpcError::new(tx_error_code(&e), e.to_string())
+    }
+}
+
+// A block refused for one of its transactions also carries the transaction's own code
+impl From<ChainError> for RpcError {
+    fn from(e: ChainError) -> Self {
+        let mut error = RpcError::new(chain_error_code(&e), e.to_string());
+        if let ChainError::InvalidTransaction { tx_id, error: tx_error, .. } = &e {
+            error.data = Some(serde_json::json!({ "tx_id": tx_id, "code": tx_error_code(tx_error) }));
+        }
+        error
+    }
+}
+
+impl From<WorkError> for RpcError {
+    fn from(e: WorkError) -> Self {
+        match e {
+            WorkError::UnknownTemplate { .. } => RpcError::new(UNKNOWN_TEMPLATE, e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! The `infinimathd` daemon and the pieces the `infinimath` client shares with it: its
+//! config file, the RPC transport and the results of its RPC methods.
+
+pub mod api;
+pub mod config;
+pub mod daemon;
+pub mod errors;
+pub mod miner;
+pub mod rpc;
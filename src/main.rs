@@ -1,40 +1,168 @@
-use common::wallet::Wallet;
-use imc::blockchain::{Blockchain, Transaction};
-use subchains::{SubChain, SubChainBlock};
-use subchains::utils::{primex, pix};
+use common::address::validate_address;
+use common::keystore::{is_keystore_file, WalletError};
+use common::wallet::{HdWallet, Wallet, WalletFile};
+use common::signature::OptionalSerializableSignature;
+use imc::transaction::{Transaction, TxId};
+use imc::work::BlockTemplate;
+use infinimath::api::{AccountInfo, NodeInfo, SubmittedBlock};
+use infinimath::config::{default_rpc_addr, take_flag, take_genesis_spec};
+use infinimath::rpc::{ClientError, RpcClient};
+use subchains::SubChainBlock;
 use bigdecimal::BigDecimal;
+use serde_json::json;
 use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::exit;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use chrono::Utc;
-use sha2::{Sha256, Digest};
 
-fn print_all_blocks(subchain: &SubChain) {
-    for block in &subchain.blocks {
-        println!("{:?}", block);
+// The chains live in `infinimathd`; this CLI keeps the wallet and talks to the daemon over RPC
+const WALLET_FILE: &str = "wallet.dat";
+// Set this to use the CLI from scripts; otherwise the passphrase is prompted for
+const PASSPHRASE_ENV: &str = "INFINIMATH_WALLET_PASSPHRASE";
+// The passphrase `change_passphrase` switches to, for the same purpose
+const NEW_PASSPHRASE_ENV: &str = "INFINIMATH_NEW_WALLET_PASSPHRASE";
+// Nonces `mine` tries before checking whether its template is still on the tip
+const NONCES_PER_BATCH: u64 = 1_000_000;
+
+// Prints why the command cannot go on and exits with a failure status
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    exit(1);
+}
+
+fn prompt_password(prompt: &str) -> String {
+    rpassword::prompt_password(prompt).unwrap_or_else(|e| fail(format!("Failed to read passphrase: {}", e)))
+}
+
+fn read_passphrase(prompt: &str) -> String {
+    env::var(PASSPHRASE_ENV).unwrap_or_else(|_| prompt_password(prompt))
+}
+
+fn read_new_passphrase() -> Option<String> {
+    read_new_passphrase_from(PASSPHRASE_ENV)
+}
+
+// Takes a new passphrase from `var`, or prompts for it twice
+fn read_new_passphrase_from(var: &str) -> Option<String> {
+    if let Ok(passphrase) = env::var(var) {
+        return Some(passphrase);
+    }
+    let passphrase = prompt_password("New wallet passphrase: ");
+    if passphrase != prompt_password("Repeat passphrase: ") {
+        eprintln!("Passphrases do not match");
+        return None;
+    }
+    Some(passphrase)
+}
+
+// Unlocks the wallet file, encrypting it in place first if it is an old unencrypted one.
+// The passphrase is returned too so that changes such as new addresses can be saved.
+fn unlock_wallet() -> Option<(WalletFile, String)> {
+    match is_keystore_file(WALLET_FILE) {
+        Ok(true) => {
+            let passphrase = read_passphrase("Wallet passphrase: ");
+            match WalletFile::unlock(WALLET_FILE, &passphrase) {
+                Ok(wallet) => Some((wallet, passphrase)),
+                Err(e) => {
+                    eprintln!("Failed to unlock wallet: {}", e);
+                    None
+                }
+            }
+        }
+        Ok(false) => {
+            let wallet = Wallet::load_from_file(WALLET_FILE).unwrap_or_else(|e| fail(format!("Failed to load wallet: {}", e)));
+            println!("{} is not encrypted. Choose a passphrase to encrypt it.", WALLET_FILE);
+            let passphrase = read_new_passphrase()?;
+            wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+            Some((WalletFile::Legacy(wallet), passphrase))
+        }
+        Err(e) => {
+            eprintln!("Failed to load wallet: {}", e);
+            None
+        }
+    }
+}
+
+// Loads a single-key wallet file from before HD wallets, encrypted or not.
+fn load_legacy_wallet(path: &str) -> Option<Wallet> {
+    let result = match is_keystore_file(path) {
+        Ok(true) => Wallet::unlock(path, &read_passphrase(&format!("Passphrase for {}: ", path))),
+        Ok(false) => Wallet::load_from_file(path).map_err(WalletError::from),
+        Err(e) => Err(e.into()),
+    };
+    result.map_err(|e| eprintln!("Failed to load {}: {}", path, e)).ok()
+}
+
+// Prints why a call to the daemon failed and gives up on the command
+fn rpc_failed(e: ClientError) {
+    eprintln!("{}", e);
+    if let ClientError::Io(_) = e {
+        eprintln!("Is infinimathd running? Use --rpc <address> if it listens elsewhere.");
     }
 }
 
-fn calculate_hash(block: &SubChainBlock) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(format!("{:?}{:?}{:?}{:?}{:?}", block.block_number, block.timestamp, block.result, block.prev_block_hash, block.nonce));
-    let result = hasher.finalize();
-    format!("{:x}", result)
+// Mines a block here rather than in the daemon: gets a template, searches nonces in batches
+// and starts over on a fresh template whenever the daemon's tip moves.
+fn mine(client: &RpcClient, address: &str) -> Result<SubmittedBlock, ClientError> {
+    loop {
+        let template: BlockTemplate = client.call("getblocktemplate", json!([address]))?;
+        let mut start = 0;
+        loop {
+            if let Some(header) = template.solve(start..start + NONCES_PER_BATCH) {
+                return client.call("submitblock", json!([header]));
+            }
+            start += NONCES_PER_BATCH;
+            if client.call::<NodeInfo>("getinfo", json!([]))?.best_hash != template.header.previous_hash {
+                break;
+            }
+        }
+    }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    
+    // Library diagnostics go through `log`; show them at info level unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut args: Vec<String> = env::args().collect();
+    let (genesis, rpc) = match take_genesis_spec(&mut args).and_then(|genesis| Ok((genesis.unwrap_or_default(), take_flag(&mut args, &["--rpc"])?))) {
+        Ok(flags) => flags,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let rpc_addr = match rpc {
+        Some((_, addr)) => match addr.parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(e) => {
+                eprintln!("Invalid RPC address {}: {}", addr, e);
+                return;
+            }
+        },
+        None => default_rpc_addr(&genesis.network),
+    };
+    let client = RpcClient::new(rpc_addr);
+
     // Check if the required argument is provided
     if args.len() < 2 {
-        eprintln!("Usage: infinimath <command> [<args>]");
+        eprintln!("Usage: infinimath [--network <mainnet|testnet|regtest> | --genesis <file>] [--rpc <address>] <command> [<args>]");
+        eprintln!("Chain commands are answered by a running infinimathd, on the network's default RPC port unless --rpc is given.");
         eprintln!("Commands:");
         eprintln!("  create_wallet");
+        eprintln!("  restore_wallet <mnemonic words...>");
         eprintln!("  load_wallet");
-        eprintln!("  send_transaction <sender> <receiver> <amount> <fee>");
+        eprintln!("  new_address");
+        eprintln!("  import_wallet <legacy_wallet_file>");
+        eprintln!("  change_passphrase");
+        eprintln!("  send_transaction <receiver> <amount> <fee> [<from_address>]");
         eprintln!("  mine <miner_address>");
         eprintln!("  balance <address>");
+        eprintln!("  pending <address>");
         eprintln!("  is_valid");
+        eprintln!("  info");
+        eprintln!("  stop");
+        eprintln!("  export_genesis <file>");
         eprintln!("  create_subchain_block");
         eprintln!("  mine_subchain_block <difficulty>");
         eprintln!("  subchain_balance <address>");
@@ -44,45 +172,187 @@ fn main() {
 
     let command = &args[1];
 
-    // Initialize a Mutex-wrapped Blockchain to ensure safe concurrent access
-    let blockchain = Arc::new(Mutex::new(Blockchain::new()));
-    let subchain = Arc::new(Mutex::new(SubChain::new()));
-
     match command.as_str() {
         "create_wallet" => {
-            let wallet = Wallet::new();
-            wallet.save_to_file("wallet.dat").expect("Failed to save wallet");
-            println!("New wallet created with address: {}", wallet.get_address());
+            if Path::new(WALLET_FILE).exists() {
+                eprintln!("{} already exists; move it away first to create a new wallet", WALLET_FILE);
+                return;
+            }
+            let Some(passphrase) = read_new_passphrase() else { return };
+            let mut wallet = HdWallet::generate();
+            let address = wallet.new_address();
+            wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+            println!("Recovery phrase, write it down and keep it offline:");
+            println!();
+            println!("    {}", wallet.mnemonic());
+            println!();
+            println!("Anyone with this phrase can spend your funds. It is the only way to restore the wallet.");
+            println!("New wallet created with address: {}", address);
+        }
+        "restore_wallet" => {
+            if args.len() < 3 {
+                eprintln!("Usage: restore_wallet <mnemonic words...>");
+                return;
+            }
+            if Path::new(WALLET_FILE).exists() {
+                eprintln!("{} already exists; move it away first to restore a wallet", WALLET_FILE);
+                return;
+            }
+
+            // The daemon tells which derived addresses have been used; the first failure stops the scan
+            let phrase = args[2..].join(" ");
+            let mut failure = None;
+            let restored = HdWallet::restore(&phrase, |address| {
+                if failure.is_some() {
+                    return false;
+                }
+                match client.call::<AccountInfo>("getaccount", json!([address])) {
+                    Ok(account) => account.used,
+                    Err(e) => {
+                        failure = Some(e);
+                        false
+                    }
+                }
+            });
+            if let Some(e) = failure {
+                return rpc_failed(e);
+            }
+            let wallet = match restored {
+                Ok(wallet) => wallet,
+                Err(e) => {
+                    eprintln!("Failed to restore wallet: {}", e);
+                    return;
+                }
+            };
+            let Some(passphrase) = read_new_passphrase() else { return };
+            wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+            println!("Wallet restored with {} addresses:", wallet.addresses.len());
+            for derived in &wallet.addresses {
+                println!("  {} {}", derived.address, wallet.path(derived.index));
+            }
         }
         "load_wallet" => {
-            let wallet = Wallet::load_from_file("wallet.dat").expect("Failed to load wallet");
-            println!("Wallet loaded with address: {}", wallet.get_address());
+            let Some((wallet, _)) = unlock_wallet() else { return };
+            match &wallet {
+                WalletFile::Hd(wallet) => {
+                    println!("HD wallet loaded with addresses:");
+                    for derived in &wallet.addresses {
+                        let used = if derived.used { " (used)" } else { "" };
+                        println!("  {} {}{}", derived.address, wallet.path(derived.index), used);
+                    }
+                    for imported in &wallet.imported {
+                        println!("  {} (imported)", imported.get_address());
+                    }
+                }
+                WalletFile::Legacy(wallet) => {
+                    println!("Single-key wallet loaded with address: {}", wallet.get_address());
+                    println!("Create an HD wallet and import this file with import_wallet to back it up with a recovery phrase.");
+                }
+            }
+        }
+        "new_address" => {
+            let Some((wallet, passphrase)) = unlock_wallet() else { return };
+            let WalletFile::Hd(mut wallet) = wallet else {
+                eprintln!("{} is a single-key wallet and cannot derive new addresses", WALLET_FILE);
+                return;
+            };
+            let address = wallet.new_address();
+            wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+            println!("New address: {}", address);
+        }
+        "import_wallet" => {
+            if args.len() < 3 {
+                eprintln!("Usage: import_wallet <legacy_wallet_file>");
+                return;
+            }
+
+            let Some(legacy) = load_legacy_wallet(&args[2]) else { return };
+            let Some((wallet, passphrase)) = unlock_wallet() else { return };
+            let WalletFile::Hd(mut wallet) = wallet else {
+                eprintln!("{} is a single-key wallet; create an HD wallet to import keys into", WALLET_FILE);
+                return;
+            };
+            let address = legacy.get_address();
+            wallet.import_legacy(legacy);
+            wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+            println!("Imported {}. It is not covered by the recovery phrase, so keep a backup of {}.", address, args[2]);
+        }
+        "change_passphrase" => {
+            let old_passphrase = read_passphrase("Current passphrase: ");
+            let Some(new_passphrase) = read_new_passphrase_from(NEW_PASSPHRASE_ENV) else { return };
+            match WalletFile::change_passphrase(WALLET_FILE, &old_passphrase, &new_passphrase) {
+                Ok(()) => println!("Wallet passphrase changed"),
+                Err(e) => eprintln!("Failed to change passphrase: {}", e),
+            }
         }
         "send_transaction" => {
-            if args.len() < 6 {
-                eprintln!("Usage: send_transaction <sender> <receiver> <amount> <fee>");
+            if args.len() < 5 {
+                eprintln!("Usage: send_transaction <receiver> <amount> <fee> [<from_address>]");
                 return;
             }
 
-            let sender = &args[2];
-            let receiver = &args[3];
-            let amount = BigDecimal::from_str(&args[4]).expect("Invalid amount");
-            let fee = BigDecimal::from_str(&args[5]).expect("Invalid fee");
+            let receiver = &args[2];
+            if let Err(e) = validate_address(receiver) {
+                eprintln!("Invalid receiver address {}: {}", receiver, e);
+                return;
+            }
+            let amount = BigDecimal::from_str(&args[3]).unwrap_or_else(|e| fail(format!("Invalid amount {}: {}", args[3], e)));
+            let fee = BigDecimal::from_str(&args[4]).unwrap_or_else(|e| fail(format!("Invalid fee {}: {}", args[4], e)));
+
+            // Funds can only be spent from the address of the key that signs the transaction
+            let Some((wallet_file, passphrase)) = unlock_wallet() else { return };
+            let info: NodeInfo = match client.call("getinfo", json!([])) {
+                Ok(info) => info,
+                Err(e) => return rpc_failed(e),
+            };
+            let mut accounts = Vec::new();
+            for address in wallet_file.addresses() {
+                match client.call::<AccountInfo>("getaccount", json!([address])) {
+                    Ok(account) => accounts.push(account),
+                    Err(e) => return rpc_failed(e),
+                }
+            }
 
-            let wallet = Wallet::load_from_file("wallet.dat").expect("Failed to load wallet");
+            // Without an explicit sender, spend from the address holding the most funds
+            let sender = match args.get(5) {
+                Some(from) => from.clone(),
+                None => accounts.iter()
+                    .max_by_key(|account| account.balance.clone())
+                    .map(|account| account.address.clone())
+                    .unwrap_or_else(|| fail("Wallet has no addresses".to_string())),
+            };
+            let Some(wallet) = wallet_file.wallet_for(&sender) else {
+                eprintln!("{} does not belong to this wallet", sender);
+                return;
+            };
+            let sender = &sender;
+            let nonce = accounts.iter().find(|account| &account.address == sender).map(|account| account.next_nonce).unwrap_or(0);
 
             let mut transaction = Transaction {
                 sender: sender.clone(),
                 receiver: receiver.clone(),
                 amount,
                 fee,
-                signature: common::wallet::OptionalSerializableSignature(None),
+                nonce,
+                chain_id: info.chain_id,
+                sender_public_key: None,
+                signature: OptionalSerializableSignature(None),
             };
 
             transaction.sign(&wallet.private_key);
 
-            blockchain.lock().unwrap().create_transaction(transaction);
-            println!("Transaction from {} to {} created", sender, receiver);
+            match client.call::<TxId>("sendrawtransaction", json!([transaction])) {
+                Ok(tx_id) => {
+                    if let WalletFile::Hd(mut wallet) = wallet_file {
+                        wallet.mark_used(sender);
+                        wallet.mark_used(receiver);
+                        wallet.save_encrypted(WALLET_FILE, &passphrase).unwrap_or_else(|e| fail(format!("Failed to save wallet: {}", e)));
+                    }
+                    println!("Transaction {} from {} to {} created", tx_id, sender, receiver);
+                }
+                Err(ClientError::Rpc(e)) => eprintln!("Transaction rejected: {}", e),
+                Err(e) => rpc_failed(e),
+            }
         }
         "mine" => {
             if args.len() < 3 {
@@ -90,12 +360,15 @@ fn main() {
                 return;
             }
 
-            let miner_address = args[2].clone();
-            blockchain.lock().unwrap().mine_pending_transactions(miner_address);
-
-            println!("Mining complete. Blockchain status:");
-            for block in &blockchain.lock().unwrap().blocks {
-                println!("{:?}", block);
+            let miner_address = &args[2];
+            if let Err(e) = validate_address(miner_address) {
+                eprintln!("Invalid miner address {}: {}", miner_address, e);
+                return;
+            }
+            match mine(&client, miner_address) {
+                Ok(block) => println!("Mined block {} ({})", block.hash, block.status),
+                Err(ClientError::Rpc(e)) => eprintln!("Mined block was rejected: {}", e),
+                Err(e) => rpc_failed(e),
             }
         }
         "balance" => {
@@ -105,46 +378,74 @@ fn main() {
             }
 
             let address = &args[2];
-            let balance = blockchain.lock().unwrap().get_balance(address);
-            println!("Balance of {}: {}", address, balance);
+            match client.call::<BigDecimal>("getbalance", json!([address])) {
+                Ok(balance) => println!("Balance of {}: {}", address, balance),
+                Err(e) => rpc_failed(e),
+            }
+        }
+        "pending" => {
+            if args.len() < 3 {
+                eprintln!("Usage: pending <address>");
+                return;
+            }
+
+            let address = &args[2];
+            let result = client.call::<Vec<Transaction>>("getmempool", json!([address]))
+                .and_then(|transactions| Ok((transactions, client.call::<AccountInfo>("getaccount", json!([address]))?)));
+            match result {
+                Ok((transactions, account)) => {
+                    println!("{} pending transactions for {}, spendable balance {}", transactions.len(), address, account.spendable_balance);
+                    for tx in transactions {
+                        println!("  {} nonce {}: {} -> {} amount {} fee {}", tx.hash(), tx.nonce, tx.sender, tx.receiver, tx.amount, tx.fee);
+                    }
+                }
+                Err(e) => rpc_failed(e),
+            }
         }
         "is_valid" => {
-            let is_valid = blockchain.lock().unwrap().is_valid();
-            println!("Is blockchain valid? {}", is_valid);
+            match client.call::<()>("verifychain", json!([])) {
+                Ok(()) => println!("Is blockchain valid? true"),
+                Err(ClientError::Rpc(e)) => println!("Is blockchain valid? false: {}", e.message),
+                Err(e) => rpc_failed(e),
+            }
+        }
+        "info" => {
+            match client.call::<NodeInfo>("getinfo", json!([])) {
+                Ok(info) => {
+                    println!("Network {} (chain id {})", info.network, info.chain_id);
+                    println!("Height {} of {} known, tip {}", info.height, info.best_header_height, info.best_hash);
+                    println!("{} peers, {} pending transactions", info.peers, info.mempool_size);
+                }
+                Err(e) => rpc_failed(e),
+            }
+        }
+        "stop" => {
+            match client.call::<String>("stop", json!([])) {
+                Ok(message) => println!("{}", message),
+                Err(e) => rpc_failed(e),
+            }
+        }
+        "export_genesis" => {
+            if args.len() < 3 {
+                eprintln!("Usage: export_genesis <file>");
+                return;
+            }
+
+            match genesis.save(&args[2]) {
+                Ok(()) => println!("Genesis spec for {} written to {}", genesis.network, args[2]),
+                Err(e) => eprintln!("Failed to write {}: {}", args[2], e),
+            }
         }
         "create_subchain_block" => {
             println!("Creating sub-chain block");
-
-            {
-                let mut subchain_lock = subchain.lock().unwrap();
-
-                // Get the last prime from the previous block or initialize the first prime
-                let last_prime = primex::get_last_prime_or_initialize(
-                    subchain_lock.blocks.last().map(|block| block.result.as_str())
-                );
-
-                // Find the next prime number starting from the last prime
-                let next_prime = primex::find_next_prime(&last_prime, 5);
-
-                println!("New prime found: {:?}", next_prime);
-
-                let mut subchain_block = SubChainBlock {
-                    block_number: subchain_lock.blocks.len() as u64 + 1,
-                    timestamp: Utc::now().timestamp() as u64,
-                    result: next_prime.to_string(),
-                    prev_block_hash: subchain_lock.get_last_block_hash(),
-                    nonce: 0,
-                    hash: String::new(),
-                };
-
-                subchain_block.hash = calculate_hash(&subchain_block);
-
-                println!("Sub-chain block details: {:?}", subchain_block);
-
-                subchain_lock.add_block(subchain_block);
+            match client.call::<SubChainBlock>("createsubchainblock", json!([])) {
+                Ok(block) => {
+                    println!("New prime found: {}", block.result);
+                    println!("Sub-chain block details: {:?}", block);
+                    println!("Sub-chain block successfully added.");
+                }
+                Err(e) => rpc_failed(e),
             }
-
-            println!("Sub-chain block successfully added.");
         }
         "mine_subchain_block" => {
             if args.len() < 3 {
@@ -152,11 +453,11 @@ fn main() {
                 return;
             }
 
-            let difficulty = args[2].parse::<usize>().expect("Invalid difficulty");
-            let mut subchain_block = subchain.lock().unwrap().get_latest_block().clone();
-
-            subchain.lock().unwrap().mine_block(&mut subchain_block, difficulty);
-            println!("Mined sub-chain block: {:?}", subchain_block);
+            let difficulty = args[2].parse::<usize>().unwrap_or_else(|e| fail(format!("Invalid difficulty {}: {}", args[2], e)));
+            match client.call::<SubChainBlock>("minesubchainblock", json!([difficulty])) {
+                Ok(block) => println!("Mined sub-chain block: {:?}", block),
+                Err(e) => rpc_failed(e),
+            }
         }
         "subchain_balance" => {
             if args.len() < 3 {
@@ -165,52 +466,25 @@ fn main() {
             }
 
             let address = &args[2];
-            let balance = subchain.lock().unwrap().get_balance(address);
-            println!("Balance of {} on sub-chain: {}", address, balance);
+            match client.call::<f64>("getsubchainbalance", json!([address])) {
+                Ok(balance) => println!("Balance of {} on sub-chain: {}", address, balance),
+                Err(e) => rpc_failed(e),
+            }
         }
         "create_pix_block" => {
-            println!("Creating PiX block");
-
-            for _ in 0..10 {
-                let mut subchain_lock = subchain.lock().unwrap();
-
-                // Get the last Pi value from the previous block or initialize the first value
-                let last_block_number = subchain_lock.blocks.len();
-
-                // Find the next Pi decimal value based on the current block number
-                let next_pi = pix::find_next_pi(last_block_number);
-
-                println!("New Pi decimal found: {:?}", next_pi);
-
-                let prev_block_hash = if let Some(last_block) = subchain_lock.blocks.last() {
-                    last_block.hash.clone()
-                } else {
-                    String::new()
-                };
-
-                let mut subchain_block = SubChainBlock {
-                    block_number: subchain_lock.blocks.len() as u64,
-                    timestamp: Utc::now().timestamp() as u64,
-                    result: next_pi.to_string(),
-                    prev_block_hash: prev_block_hash,
-                    nonce: 0,
-                    hash: String::new(),
-                };
-
-                subchain_block.hash = calculate_hash(&subchain_block);
-
-                println!("PiX block details: {:?}", subchain_block);
-
-                subchain_lock.add_block(subchain_block);
+            println!("Creating PiX blocks");
+            match client.call::<Vec<SubChainBlock>>("createpixblocks", json!([])) {
+                Ok(blocks) => {
+                    for block in blocks {
+                        println!("PiX block details: {:?}", block);
+                    }
+                    println!("PiX blocks successfully added.");
+                }
+                Err(e) => rpc_failed(e),
             }
-
-            println!("PiX blocks successfully added.");
-
-            // Print all blocks including genesis
-            print_all_blocks(&subchain.lock().unwrap());
         }
         _ => {
             eprintln!("Unknown command");
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+//! Proof-of-work mining on top of a running [`Node`].
+//!
+//! Nonces are searched in batches with the chain unlocked, so that blocks and transactions
+//! from peers keep being accepted while mining. Between batches the template is rebuilt if
+//! the tip moved, and mined blocks go through the node so that peers hear about them.
+
+use imc::block::Block;
+use imc::error::ChainError;
+use imc::p2p::node::Node;
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const NONCES_PER_BATCH: u64 = 10_000;
+// Rebuilt this often even if the tip did not move, to pick up new transactions
+const TEMPLATE_LIFETIME: Duration = Duration::from_secs(10);
+
+/// Mines one block on the tip paying `address` and submits it. Returns `None` if `stop` was
+/// set first.
+pub fn mine_block(node: &Node, address: &str, stop: &AtomicBool) -> Option<Result<String, ChainError>> {
+    loop {
+        let mut block = node.chain().lock().unwrap().block_template(address.to_string());
+        let built = Instant::now();
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            if search(&mut block) {
+                let hash = block.hash.clone();
+                return Some(node.submit_block(block).map(|_| hash));
+            }
+            let tip_moved = node.chain().lock().unwrap().blocks.last().unwrap().hash != block.header.previous_hash;
+            if tip_moved || built.elapsed() > TEMPLATE_LIFETIME {
+                break;
+            }
+        }
+    }
+}
+
+// Tries the next batch of nonces and reports whether one of them meets the target
+fn search(block: &mut Block) -> bool {
+    for _ in 0..NONCES_PER_BATCH {
+        if block.header.meets_target(&block.hash) {
+            return true;
+        }
+        block.header.nonce = block.header.nonce.wrapping_add(1);
+        block.hash = block.calculate_hash();
+    }
+    false
+}
+
+/// Mines blocks paying `address` on a thread of its own until stopped.
+pub struct Miner {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Miner {
+    pub fn start(node: Arc<Node>, address: String) -> Miner {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            info!("Mining blocks paying {}", address);
+            while let Some(result) = mine_block(&node, &address, &stopping) {
+                match result {
+                    Ok(hash) => info!("Mined block {}", hash),
+                    Err(e) => warn!("Mined block was rejected: {}", e),
+                }
+            }
+        });
+        Miner { stop, thread: Some(thread) }
+    }
+
+    pub fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            info!("Stopped mining");
+        }
+    }
+}
+
+impl Drop for Miner {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
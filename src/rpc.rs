@@ -0,0 +1,286 @@
+//! JSON-RPC 2.0 over HTTP, as served by `infinimathd` and used by the `infinimath` client.
+//!
+//! A request is an HTTP `POST` whose body is one JSON-RPC request object, and the response
+//! body is the JSON-RPC response object. Batches are not supported, and the connection is
+//! closed after every response. The server only ever listens where it is configured to,
+//! localhost by default, and does no authentication.
+
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Error codes defined by JSON-RPC 2.0
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The request was well-formed but the node refused it.
+pub const SERVER_ERROR: i64 = -32000;
+
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+const MAX_HEADER_LINES: usize = 100;
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The `error` member of a failed response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into(), data: None }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    pub fn server(message: impl Into<String>) -> Self {
+        Self::new(SERVER_ERROR, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Answers a call to `method` with `params`, which is `null` when the request has none.
+pub type Handler = dyn Fn(&str, Value) -> Result<Value, RpcError> + Send + Sync;
+
+pub struct RpcServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RpcServer {
+    /// Starts answering requests on `addr` with `handler`, one thread per connection.
+    pub fn start(addr: SocketAddr, handler: Arc<Handler>) -> io::Result<RpcServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&shutdown);
+        let thread = thread::spawn(move || {
+            while !stopping.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        let handler = Arc::clone(&handler);
+                        thread::spawn(move || {
+                            if let Err(e) = serve(stream, &*handler) {
+                                debug!("RPC connection from {} failed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+                    Err(e) => {
+                        warn!("Failed to accept an RPC connection: {}", e);
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+        info!("Listening for RPC requests on {}", local_addr);
+        Ok(RpcServer { local_addr, shutdown, thread: Some(thread) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting connections. Requests already being answered are finished.
+    pub fn shutdown(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            info!("Stopped listening for RPC requests on {}", self.local_addr);
+        }
+    }
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn serve(stream: TcpStream, handler: &Handler) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let (status, body) = match read_http_message(&mut reader) {
+        Ok((start_line, body)) if start_line.starts_with("POST ") => (200, respond(&body, handler)),
+        Ok(_) => (405, json!({ "error": "use POST" })),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => (400, json!({ "error": e.to_string() })),
+        Err(e) => return Err(e),
+    };
+    write_http_message(&mut writer, &format!("HTTP/1.1 {} {}", status, reason(status)), &body)
+}
+
+// The response object for a request body
+fn respond(body: &[u8], handler: &Handler) -> Value {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, RpcError::new(INVALID_REQUEST, "request has no method"));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    debug!("RPC call {}", method);
+    match handler(method, params) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(e) => error_response(id, e),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "error": error, "id": id })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    }
+}
+
+// Reads a start line, headers and a body of `Content-Length` bytes. Malformed messages are
+// reported as `InvalidData`.
+fn read_http_message<R: BufRead>(reader: &mut R) -> io::Result<(String, Vec<u8>)> {
+    let mut start_line = String::new();
+    reader.read_line(&mut start_line)?;
+    let start_line = start_line.trim_end().to_string();
+    if start_line.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed before a request"));
+    }
+
+    let mut content_length = 0;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length > MAX_BODY_SIZE {
+                return Err(invalid_data(format!("body of {} bytes is too large", content_length)));
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body)?;
+            return Ok((start_line, body));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| invalid_data(format!("invalid Content-Length {}", value.trim())))?;
+            }
+        }
+    }
+    Err(invalid_data("too many header lines".to_string()))
+}
+
+fn write_http_message<W: Write>(writer: &mut W, start_line: &str, body: &Value) -> io::Result<()> {
+    let body = serde_json::to_vec(body)?;
+    write!(writer, "{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", start_line, body.len())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Why a call made through [`RpcClient`] failed.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server could not be reached, or the connection failed.
+    Io(io::Error),
+    /// The server answered with something other than a JSON-RPC response.
+    Protocol(String),
+    /// The server refused the call.
+    Rpc(RpcError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "cannot reach the node: {}", e),
+            ClientError::Protocol(message) => write!(f, "invalid response from the node: {}", message),
+            ClientError::Rpc(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    addr: SocketAddr,
+}
+
+impl RpcClient {
+    pub fn new(addr: SocketAddr) -> Self {
+        RpcClient { addr }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Calls `method` and decodes its result as `T`.
+    pub fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, ClientError> {
+        let result = self.call_raw(method, params)?;
+        serde_json::from_value(result).map_err(|e| ClientError::Protocol(format!("unexpected result of {}: {}", method, e)))
+    }
+
+    /// Calls `method` and returns its result as it came.
+    pub fn call_raw(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, IO_TIMEOUT)?;
+        stream.set_read_timeout(None)?; // Calls such as `generate` may take a while
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        write_http_message(&mut stream, &format!("POST / HTTP/1.1\r\nHost: {}", self.addr), &request)?;
+
+        let mut reader = BufReader::new(stream);
+        let (status_line, body) = read_http_message(&mut reader).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => ClientError::Protocol(e.to_string()),
+            _ => ClientError::Io(e),
+        })?;
+        let mut response: Value = serde_json::from_slice(&body)
+            .map_err(|e| ClientError::Protocol(format!("{} with a body that is not JSON: {}", status_line, e)))?;
+        if let Some(error) = response.get_mut("error").map(Value::take) {
+            let error = serde_json::from_value(error).map_err(|e| ClientError::Protocol(format!("malformed error: {}", e)))?;
+            return Err(ClientError::Rpc(error));
+        }
+        response.get_mut("result").map(Value::take).ok_or_else(|| ClientError::Protocol(format!("{} without a result", status_line)))
+    }
+}
@@ -1,5 +0,0 @@
-pub mod subchain;
-pub mod subchain_block;
-pub mod subchain_pow;
-pub mod subchain_block_time;
-pub mod utils;
\ No newline at end of file
@@ -1,9 +1,19 @@
+//! Useful-work sub-chains, whose blocks record the result of a mathematical search such as
+//! the next prime or the next digits of pi.
+//!
+//! Each search lives in [`utils`] behind its own feature (`primex`, `pix`), both on by
+//! default, so a crate that only needs the chain types can leave out the big-number
+//! dependencies.
+
 pub mod subchain;
 pub mod subchain_block;
 pub mod subchain_pow;
 pub mod subchain_block_time;
+pub mod subchain_store;
 pub mod utils {
+    #[cfg(feature = "primex")]
     pub mod primex; // sub chain to find and store prime numbers
+    #[cfg(feature = "pix")]
     pub mod pix; //subchain too find new decimals in pi
 }
 
@@ -11,4 +21,12 @@ pub mod utils {
 pub use subchain::*;
 pub use subchain_block::*;
 pub use subchain_pow::*;
-pub use subchain_block_time::*;
\ No newline at end of file
+pub use subchain_block_time::*;
+pub use subchain_store::*;
+
+pub mod prelude {
+    pub use crate::subchain::SubChain;
+    pub use crate::subchain_block::SubChainBlock;
+    pub use crate::subchain_pow::{calculate_subchain_hash, mine_subchain_block};
+    pub use crate::subchain_store::SubChainError;
+}
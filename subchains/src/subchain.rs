@@ -1,11 +1,22 @@
 use crate::subchain_block::SubChainBlock;
 use crate::subchain_pow::{calculate_subchain_hash, mine_subchain_block};
+use crate::subchain_store::{SubChainError, SubChainState, SubChainStore};
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 use chrono::Utc;
 
 pub struct SubChain {
     pub blocks: Vec<SubChainBlock>,
     pub balances: HashMap<String, f64>,
+    store: Option<SubChainStore>,
+    persisted_blocks: usize, // Number of leading blocks already written to `store`
+}
+
+impl Default for SubChain {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SubChain {
@@ -22,12 +33,70 @@ impl SubChain {
         let mut subchain = SubChain {
             blocks: vec![genesis_block],
             balances: HashMap::new(),
+            store: None,
+            persisted_blocks: 0,
         };
 
         subchain.blocks[0].hash = calculate_subchain_hash(&subchain.blocks[0]);
         subchain
     }
 
+    /// Opens the sub-chain stored in `path`, starting a new one from genesis if the directory
+    /// is empty. Every stored block is re-hashed and its link to the previous block checked.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SubChainError> {
+        let store = SubChainStore::open(path)?;
+        let mut subchain = match store.read_state()? {
+            Some(state) => {
+                let subchain = SubChain {
+                    blocks: store.read_blocks(state.height)?,
+                    balances: state.balances,
+                    store: None,
+                    persisted_blocks: state.height as usize + 1,
+                };
+                subchain.verify()?;
+                subchain
+            }
+            None => Self::new(),
+        };
+        subchain.store = Some(store);
+        subchain.flush()?;
+        Ok(subchain)
+    }
+
+    /// Checks that every block's `hash` matches its contents and that each `prev_block_hash`
+    /// points at the block before it.
+    pub fn verify(&self) -> Result<(), SubChainError> {
+        for (index, block) in self.blocks.iter().enumerate() {
+            let index = index as u64;
+            if block.hash != calculate_subchain_hash(block) {
+                return Err(SubChainError::InvalidHash { index });
+            }
+            if index > 0 && block.prev_block_hash != self.blocks[index as usize - 1].hash {
+                return Err(SubChainError::BrokenLink { index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes new blocks and balances to the directory this sub-chain was opened from.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+
+        for (index, block) in self.blocks.iter().enumerate().skip(self.persisted_blocks) {
+            store.write_block(index as u64, block)?;
+        }
+        store.write_state(&SubChainState {
+            height: self.blocks.len() as u64 - 1,
+            balances: self.balances.clone(),
+        })?;
+
+        self.persisted_blocks = self.blocks.len();
+        Ok(())
+    }
+
     pub fn add_block(&mut self, block: SubChainBlock) {
         self.blocks.push(block);
     }
@@ -41,6 +110,9 @@ impl SubChain {
     }
 
     pub fn mine_block(&mut self, block: &mut SubChainBlock, difficulty: usize) {
+        // The mined block goes on top of the current tip, so link it there before hashing
+        block.block_number = self.blocks.len() as u64;
+        block.prev_block_hash = self.get_last_block_hash();
         mine_subchain_block(block, difficulty);
         self.add_block(block.clone());
     }
@@ -48,4 +120,4 @@ impl SubChain {
     pub fn get_balance(&self, address: &str) -> f64 {
         *self.balances.get(address).unwrap_or(&0.0)
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+use common::encoding::{Decode, DecodeError, Decoder, Encode, Encoder};
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -8,4 +9,37 @@ pub struct SubChainBlock {
     pub prev_block_hash: String,
     pub nonce: u64,
     pub hash: String,
-}
\ No newline at end of file
+}
+
+impl SubChainBlock {
+    // Everything except the hash itself, so the hash can be recomputed from a stored block
+    pub(crate) fn encode_unhashed(&self, encoder: &mut Encoder) {
+        encoder.put_u64(self.block_number);
+        encoder.put_u64(self.timestamp);
+        encoder.put_str(&self.result);
+        encoder.put_str(&self.prev_block_hash);
+        encoder.put_u64(self.nonce);
+    }
+}
+
+impl Encode for SubChainBlock {
+    const VERSION: u8 = 1;
+
+    fn encode_to(&self, encoder: &mut Encoder) {
+        self.encode_unhashed(encoder);
+        encoder.put_str(&self.hash);
+    }
+}
+
+impl Decode for SubChainBlock {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(SubChainBlock {
+            block_number: decoder.get_u64()?,
+            timestamp: decoder.get_u64()?,
+            result: decoder.get_string()?,
+            prev_block_hash: decoder.get_string()?,
+            nonce: decoder.get_u64()?,
+            hash: decoder.get_string()?,
+        })
+    }
+}
@@ -1,10 +1,12 @@
 use crate::subchain_block::SubChainBlock;
+use common::encoding::{Encode, Encoder};
 use sha2::{Sha256, Digest};
 
 pub fn calculate_subchain_hash(block: &SubChainBlock) -> String {
-    let block_data = serde_json::to_string(&block).unwrap();
+    let mut encoder = Encoder::with_version(SubChainBlock::VERSION);
+    block.encode_unhashed(&mut encoder);
     let mut hasher = Sha256::new();
-    hasher.update(block_data);
+    hasher.update(encoder.finish());
     format!("{:x}", hasher.finalize())
 }
 
@@ -13,7 +15,7 @@ pub fn mine_subchain_block(block: &mut SubChainBlock, difficulty: usize) -> u64
     let mut nonce = 0;
     loop {
         block.nonce = nonce;
-        block.hash = calculate_subchain_hash(&block);
+        block.hash = calculate_subchain_hash(block);
         if block.hash.starts_with(&target) {
             break;
         }
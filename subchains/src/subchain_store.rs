@@ -0,0 +1,97 @@
+use crate::subchain_block::SubChainBlock;
+use common::encoding::{decode, encode};
+use common::storage::{read_json, write_atomic, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "state.json";
+const BLOCKS_DIR: &str = "blocks";
+
+#[derive(Debug)]
+pub enum SubChainError {
+    Io(io::Error),
+    MissingBlock { index: u64 },
+    InvalidHash { index: u64 },
+    BrokenLink { index: u64 },
+}
+
+impl fmt::Display for SubChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubChainError::Io(e) => write!(f, "sub-chain storage error: {}", e),
+            SubChainError::MissingBlock { index } => write!(f, "sub-chain block {} is missing", index),
+            SubChainError::InvalidHash { index } => {
+                write!(f, "sub-chain block {} has been tampered with: its hash does not match its contents", index)
+            }
+            SubChainError::BrokenLink { index } => {
+                write!(f, "sub-chain block {} has been tampered with: prev_block_hash does not match block {}", index, index.saturating_sub(1))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SubChainError {}
+
+impl From<io::Error> for SubChainError {
+    fn from(e: io::Error) -> Self {
+        SubChainError::Io(e)
+    }
+}
+
+/// Sub-chain state stored next to the blocks. Like the main chain store, the state file is
+/// written last and records the height it belongs to, so stray block files past `height`
+/// from an interrupted flush are ignored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubChainState {
+    pub height: u64,
+    pub balances: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubChainStore {
+    root: PathBuf,
+}
+
+impl SubChainStore {
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(BLOCKS_DIR))?;
+        Ok(SubChainStore { root })
+    }
+
+    fn block_path(&self, index: u64) -> PathBuf {
+        self.root.join(BLOCKS_DIR).join(format!("{:010}.bin", index))
+    }
+
+    pub fn write_block(&self, index: u64, block: &SubChainBlock) -> io::Result<()> {
+        write_atomic(self.block_path(index), &encode(block))
+    }
+
+    pub fn read_block(&self, index: u64) -> io::Result<Option<SubChainBlock>> {
+        match fs::read(self.block_path(index)) {
+            Ok(bytes) => decode(&bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("sub-chain block {}: {}", index, e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write_state(&self, state: &SubChainState) -> io::Result<()> {
+        write_json_atomic(self.root.join(STATE_FILE), state)
+    }
+
+    pub fn read_state(&self) -> io::Result<Option<SubChainState>> {
+        read_json(self.root.join(STATE_FILE))
+    }
+
+    pub fn read_blocks(&self, height: u64) -> Result<Vec<SubChainBlock>, SubChainError> {
+        (0..=height)
+            .map(|index| self.read_block(index)?.ok_or(SubChainError::MissingBlock { index }))
+            .collect()
+    }
+}
@@ -0,0 +1,46 @@
+// Golden hashes for sub-chain blocks. These must only change together with
+// `SubChainBlock`'s `Encode::VERSION`.
+
+use common::encoding::{decode, encode, DecodeError};
+use subchains::{calculate_subchain_hash, SubChainBlock};
+
+fn sample_block() -> SubChainBlock {
+    SubChainBlock {
+        block_number: 1,
+        timestamp: 1700000000,
+        result: "3".to_string(),
+        prev_block_hash: "ab".to_string(),
+        nonce: 42,
+        hash: String::new(),
+    }
+}
+
+#[test]
+fn subchain_block_vector() {
+    let mut block = sample_block();
+    assert_eq!(calculate_subchain_hash(&block), "79909a6349d70c71501faa9dbd679b090d849544a1ff11da9b00998b2b9c7337");
+
+    // The stored hash is not part of the hash input
+    block.hash = "anything".to_string();
+    assert_eq!(calculate_subchain_hash(&block), "79909a6349d70c71501faa9dbd679b090d849544a1ff11da9b00998b2b9c7337");
+
+    let decoded: SubChainBlock = decode(&encode(&block)).unwrap();
+    assert_eq!(decoded.hash, "anything");
+    assert_eq!(calculate_subchain_hash(&decoded), calculate_subchain_hash(&block));
+}
+
+#[test]
+fn stored_blocks_still_verify_after_decoding() {
+    let mut block = sample_block();
+    block.hash = calculate_subchain_hash(&block);
+    let mut bytes = encode(&block);
+
+    let decoded: SubChainBlock = decode(&bytes).unwrap();
+    assert_eq!(calculate_subchain_hash(&decoded), block.hash);
+
+    // The version byte is hashed, so a block read at any other version would fail that check
+    for version in [0, 2, 6] {
+        bytes[0] = version;
+        assert_eq!(decode::<SubChainBlock>(&bytes).err(), Some(DecodeError::UnsupportedVersion(version)));
+    }
+}
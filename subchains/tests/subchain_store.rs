@@ -0,0 +1,99 @@
+// Stored sub-chains are checked when opened, and a block edited on disk is reported by its
+// number.
+
+use common::encoding::{decode, encode};
+use std::fs;
+use std::path::{Path, PathBuf};
+use subchains::{calculate_subchain_hash, SubChain, SubChainBlock, SubChainError};
+
+fn data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("subchains-store-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+// A sub-chain stored in `dir` with blocks 0 to 3
+fn stored_subchain(dir: &Path) {
+    let mut subchain = SubChain::open(dir).unwrap();
+    for result in ["2", "3", "5"] {
+        let mut block = SubChainBlock {
+            block_number: 0,
+            timestamp: 1_700_000_000,
+            result: result.to_string(),
+            prev_block_hash: String::new(),
+            nonce: 0,
+            hash: String::new(),
+        };
+        subchain.mine_block(&mut block, 1);
+    }
+    subchain.flush().unwrap();
+}
+
+fn block_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join("blocks").join(format!("{:010}.bin", index))
+}
+
+// Rewrites stored block `index` after `edit`
+fn edit_block<F: FnOnce(&mut SubChainBlock)>(dir: &Path, index: u64, edit: F) {
+    let path = block_path(dir, index);
+    let mut block: SubChainBlock = decode(&fs::read(&path).unwrap()).unwrap();
+    edit(&mut block);
+    fs::write(&path, encode(&block)).unwrap();
+}
+
+fn open_error(dir: &Path) -> SubChainError {
+    match SubChain::open(dir) {
+        Ok(_) => panic!("a tampered sub-chain opened"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn stored_subchains_open_as_they_were() {
+    let dir = data_dir("reopen");
+    stored_subchain(&dir);
+    let subchain = SubChain::open(&dir).unwrap();
+    assert_eq!(subchain.blocks.len(), 4);
+    assert_eq!(subchain.blocks.iter().map(|block| block.result.as_str()).collect::<Vec<_>>(), ["Genesis Block", "2", "3", "5"]);
+    subchain.verify().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn an_edited_block_is_named_when_opening() {
+    let dir = data_dir("edited");
+    stored_subchain(&dir);
+    edit_block(&dir, 2, |block| block.result = "4".to_string());
+
+    let error = open_error(&dir);
+    assert!(matches!(error, SubChainError::InvalidHash { index: 2 }), "{:?}", error);
+    assert!(error.to_string().contains("sub-chain block 2 "), "{}", error);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_rehashed_block_still_breaks_the_link() {
+    let dir = data_dir("relinked");
+    stored_subchain(&dir);
+    edit_block(&dir, 2, |block| {
+        block.prev_block_hash = "0".repeat(64);
+        block.hash = calculate_subchain_hash(block);
+    });
+
+    let error = open_error(&dir);
+    assert!(matches!(error, SubChainError::BrokenLink { index: 2 }), "{:?}", error);
+    assert!(error.to_string().contains("sub-chain block 2 "), "{}", error);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn an_unreadable_block_is_named_when_opening() {
+    let dir = data_dir("garbled");
+    stored_subchain(&dir);
+    fs::write(block_path(&dir, 3), b"garbage").unwrap();
+
+    let error = open_error(&dir);
+    assert!(matches!(error, SubChainError::Io(_)), "{:?}", error);
+    assert!(error.to_string().contains("sub-chain block 3:"), "{}", error);
+    let _ = fs::remove_dir_all(&dir);
+}
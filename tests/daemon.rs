@@ -0,0 +1,106 @@
+// A daemon that is shut down and started again on the same data directory carries on from
+// where it stopped: same tip, balances, pending transactions and sub-chain.
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::signature::OptionalSerializableSignature;
+use imc::prelude::*;
+use infinimath::api::NodeInfo;
+use infinimath::config::{DaemonConfig, P2pConfig, RpcConfig};
+use infinimath::daemon::Daemon;
+use infinimath::rpc::RpcClient;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use subchains::SubChainBlock;
+
+fn key() -> SigningKey {
+    SigningKey::from_slice(&[9u8; 32]).unwrap()
+}
+
+fn funded() -> String {
+    address_from_public_key(&VerifyingKey::from(&key()))
+}
+
+fn other() -> String {
+    address_from_public_key(&VerifyingKey::from(&SigningKey::from_slice(&[10u8; 32]).unwrap()))
+}
+
+// Regtest with coins for `funded()` at genesis
+fn genesis() -> GenesisSpec {
+    let mut genesis = GenesisSpec::regtest();
+    genesis.consensus.emission.genesis_allocation.insert(funded(), BigDecimal::from(1_000));
+    genesis.consensus.emission.max_supply += BigDecimal::from(1_000);
+    genesis
+}
+
+fn config(dir: &Path) -> DaemonConfig {
+    DaemonConfig {
+        network: "regtest".to_string(),
+        data_dir: dir.to_path_buf(),
+        p2p: P2pConfig { listen: Some("127.0.0.1:0".parse().unwrap()), ..P2pConfig::default() },
+        rpc: RpcConfig { listen: Some("127.0.0.1:0".parse().unwrap()) },
+        ..DaemonConfig::default()
+    }
+}
+
+fn transaction(amount: u32, nonce: u64) -> Transaction {
+    let mut transaction = Transaction {
+        sender: funded(),
+        receiver: other(),
+        amount: BigDecimal::from(amount),
+        fee: BigDecimal::from(1),
+        nonce,
+        chain_id: genesis().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key());
+    transaction
+}
+
+#[test]
+fn a_restarted_daemon_resumes_from_its_tip() {
+    let dir = std::env::temp_dir().join(format!("infinimath-daemon-restart-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let daemon = Daemon::start(&config(&dir), &genesis()).unwrap();
+    let client = RpcClient::new(daemon.rpc_addr());
+    client.call::<String>("sendrawtransaction", json!([transaction(100, 0)])).unwrap();
+    for _ in 0..3 {
+        client.call::<String>("generate", json!([funded()])).unwrap();
+    }
+    let pending: String = client.call("sendrawtransaction", json!([transaction(50, 1)])).unwrap();
+    let subchain_block: SubChainBlock = client.call("createsubchainblock", json!([])).unwrap();
+    let before: NodeInfo = client.call("getinfo", json!([])).unwrap();
+    let balance: BigDecimal = client.call("getbalance", json!([other()])).unwrap();
+    assert_eq!(before.height, 3);
+    assert_eq!(balance, BigDecimal::from(100));
+    daemon.shutdown();
+
+    // Both chains are kept under the network's own directory
+    assert!(dir.join("regtest").join("imc").is_dir());
+    assert!(dir.join("regtest").join("subchain").exists());
+    assert!(!dir.join("subchain").exists());
+
+    let daemon = Daemon::start(&config(&dir), &genesis()).unwrap();
+    let client = RpcClient::new(daemon.rpc_addr());
+    let after: NodeInfo = client.call("getinfo", json!([])).unwrap();
+    assert_eq!((after.height, &after.best_hash), (before.height, &before.best_hash));
+    assert_eq!(after.mempool_size, 1);
+    assert_eq!(client.call::<BigDecimal>("getbalance", json!([other()])).unwrap(), balance);
+    let mempool: Vec<Transaction> = client.call("getmempool", json!([])).unwrap();
+    assert_eq!(mempool.iter().map(Transaction::hash).collect::<Vec<_>>(), vec![pending]);
+    assert_eq!(client.call::<SubChainBlock>("getsubchainblock", json!([subchain_block.block_number])).unwrap().hash, subchain_block.hash);
+    client.call::<()>("verifychain", json!([])).unwrap();
+
+    // Mining carries on from the restored tip, and confirms the transaction kept pending
+    let hash: String = client.call("generate", json!([funded()])).unwrap();
+    let next: NodeInfo = client.call("getinfo", json!([])).unwrap();
+    assert_eq!((next.height, next.best_hash), (4, hash));
+    assert_eq!(client.call::<BigDecimal>("getbalance", json!([other()])).unwrap(), BigDecimal::from(150));
+    daemon.shutdown();
+
+    let _ = fs::remove_dir_all(&dir);
+}
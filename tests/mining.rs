@@ -0,0 +1,171 @@
+// Miners outside the daemon get work with `getblocktemplate` and hand back solved headers
+// with `submitblock`; the daemon checks them like any other block.
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::signature::OptionalSerializableSignature;
+use imc::prelude::*;
+use infinimath::api::{NodeInfo, SubmittedBlock};
+use infinimath::config::{DaemonConfig, P2pConfig, RpcConfig};
+use infinimath::daemon::Daemon;
+use infinimath::errors::{INSUFFICIENT_PROOF_OF_WORK, INVALID_COINBASE, UNKNOWN_TEMPLATE};
+use infinimath::rpc::{ClientError, RpcClient, INVALID_PARAMS};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::from_slice(&[seed; 32]).unwrap()
+}
+
+fn address(seed: u8) -> String {
+    address_from_public_key(&VerifyingKey::from(&key(seed)))
+}
+
+// Regtest with coins for `address(1)` at genesis
+fn genesis() -> GenesisSpec {
+    let mut genesis = GenesisSpec::regtest();
+    genesis.consensus.emission.genesis_allocation.insert(address(1), BigDecimal::from(1_000));
+    genesis.consensus.emission.max_supply += BigDecimal::from(1_000);
+    genesis
+}
+
+struct TestDaemon {
+    daemon: Option<Daemon>,
+    client: RpcClient,
+    dir: PathBuf,
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        self.daemon.take().unwrap().shutdown();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn start(name: &str) -> TestDaemon {
+    let dir = std::env::temp_dir().join(format!("infinimath-mining-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = DaemonConfig {
+        network: "regtest".to_string(),
+        data_dir: dir.clone(),
+        p2p: P2pConfig { listen: Some("127.0.0.1:0".parse().unwrap()), ..P2pConfig::default() },
+        rpc: RpcConfig { listen: Some("127.0.0.1:0".parse().unwrap()) },
+        ..DaemonConfig::default()
+    };
+    let daemon = Daemon::start(&config, &genesis()).unwrap();
+    let client = RpcClient::new(daemon.rpc_addr());
+    TestDaemon { daemon: Some(daemon), client, dir }
+}
+
+fn template(client: &RpcClient, address: &str) -> BlockTemplate {
+    client.call("getblocktemplate", json!([address])).unwrap()
+}
+
+fn submit(client: &RpcClient, header: &BlockHeader) -> Result<SubmittedBlock, ClientError> {
+    client.call("submitblock", json!([header]))
+}
+
+fn error_code<T: std::fmt::Debug>(result: Result<T, ClientError>) -> i64 {
+    match result {
+        Err(ClientError::Rpc(e)) => e.code,
+        other => panic!("expected an RPC error, got {:?}", other),
+    }
+}
+
+#[test]
+fn miners_outside_the_daemon_extend_the_chain() {
+    let test = start("parallel");
+    let addr = test.client.addr();
+
+    // Each miner has its own connection and only ever sees templates and headers
+    let miners: Vec<_> = (2..6u8).map(|seed| thread::spawn(move || {
+        let client = RpcClient::new(addr);
+        let mut mined = 0;
+        while client.call::<NodeInfo>("getinfo", json!([])).unwrap().height < 40 {
+            let template = template(&client, &address(seed));
+            let header = template.solve(0..u64::MAX).unwrap();
+            if submit(&client, &header).unwrap().status == "extended_tip" {
+                mined += 1;
+            }
+        }
+        mined
+    })).collect();
+    let mined: u64 = miners.into_iter().map(|miner| miner.join().unwrap()).sum();
+
+    let node = test.daemon.as_ref().unwrap().node();
+    let chain = node.chain().lock().unwrap();
+    assert!(chain.blocks.len() > 40);
+    assert!(mined >= 40);
+    assert!(chain.is_valid().is_ok());
+    assert!(chain.blocks[1..].iter().all(|block| (2..6).any(|seed| block.header.miner == address(seed))));
+}
+
+#[test]
+fn templates_carry_pending_transactions_and_pay_the_miner() {
+    let test = start("transactions");
+    let mut transaction = Transaction {
+        sender: address(1),
+        receiver: address(2),
+        amount: BigDecimal::from(50),
+        fee: BigDecimal::from(2),
+        nonce: 0,
+        chain_id: genesis().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key(1));
+    let tx_id: String = test.client.call("sendrawtransaction", json!([transaction])).unwrap();
+
+    let template = template(&test.client, &address(3));
+    assert_eq!(template.height, 1);
+    assert_eq!(template.transactions, vec![tx_id]);
+    assert_eq!(template.header.miner, address(3));
+    assert_eq!(template.target.len(), 64);
+    assert!(template.coinbase.outputs.iter().any(|output| output.address == address(3)));
+
+    let header = template.solve(0..u64::MAX).unwrap();
+    let submitted = submit(&test.client, &header).unwrap();
+    assert_eq!(submitted.hash, header.calculate_hash());
+    assert_eq!(submitted.status, "extended_tip");
+
+    let balance: BigDecimal = test.client.call("getbalance", json!([address(2)])).unwrap();
+    assert_eq!(balance, BigDecimal::from(50));
+    let coinbase_total: BigDecimal = template.coinbase.outputs.iter().map(|output| &output.amount).sum();
+    let miner_balance: BigDecimal = test.client.call("getbalance", json!([address(3)])).unwrap();
+    assert_eq!(miner_balance, coinbase_total);
+}
+
+#[test]
+fn submitted_headers_are_checked() {
+    let test = start("checked");
+    let template = template(&test.client, &address(2));
+    let solved = template.solve(0..u64::MAX).unwrap();
+
+    // A header for a body the daemon never handed out
+    let mut unknown = solved.clone();
+    unknown.merkle_root = "00".repeat(32);
+    assert_eq!(error_code(submit(&test.client, &unknown)), UNKNOWN_TEMPLATE);
+
+    // Claiming the reward for another miner breaks the committed coinbase
+    let mut stolen = template.header.clone();
+    stolen.miner = address(3);
+    let stolen = BlockTemplate { header: stolen, ..template.clone() }.solve(0..u64::MAX).unwrap();
+    assert_eq!(error_code(submit(&test.client, &stolen)), INVALID_COINBASE);
+
+    let mut unsolved = template.header.clone();
+    unsolved.nonce = (0..).find(|&nonce| {
+        unsolved.nonce = nonce;
+        !unsolved.meets_target(&unsolved.calculate_hash())
+    }).unwrap();
+    assert_eq!(error_code(submit(&test.client, &unsolved)), INSUFFICIENT_PROOF_OF_WORK);
+
+    assert_eq!(error_code(test.client.call::<BlockTemplate>("getblocktemplate", json!(["nobody"]))), INVALID_PARAMS);
+    assert_eq!(submit(&test.client, &solved).unwrap().status, "extended_tip");
+
+    // Work on a template whose tip has moved on still counts, on a side branch
+    let stale = template.solve(solved.nonce + 1..u64::MAX).unwrap();
+    assert_eq!(submit(&test.client, &stale).unwrap().status, "side_branch");
+}
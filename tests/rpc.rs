@@ -0,0 +1,229 @@
+// Every RPC method against an in-process daemon, through the same client the CLI uses,
+// including the error codes of refused requests.
+
+use bigdecimal::BigDecimal;
+use common::address::address_from_public_key;
+use common::signature::OptionalSerializableSignature;
+use imc::prelude::*;
+use infinimath::api::{BlockInfo, DifficultyInfo, TransactionInfo};
+use infinimath::config::{DaemonConfig, P2pConfig, RpcConfig};
+use infinimath::daemon::Daemon;
+use infinimath::errors::*;
+use infinimath::rpc::{ClientError, RpcClient, INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use subchains::SubChainBlock;
+
+fn key() -> SigningKey {
+    SigningKey::from_slice(&[9u8; 32]).unwrap()
+}
+
+fn funded() -> String {
+    address_from_public_key(&VerifyingKey::from(&key()))
+}
+
+fn other() -> String {
+    address_from_public_key(&VerifyingKey::from(&SigningKey::from_slice(&[10u8; 32]).unwrap()))
+}
+
+// Regtest with coins for `funded()` at genesis
+fn genesis() -> GenesisSpec {
+    let mut genesis = GenesisSpec::regtest();
+    genesis.consensus.emission.genesis_allocation.insert(funded(), BigDecimal::from(1_000));
+    genesis.consensus.emission.max_supply += BigDecimal::from(1_000);
+    genesis
+}
+
+// A daemon in its own data directory, removed again when dropped
+struct TestDaemon {
+    daemon: Option<Daemon>,
+    client: RpcClient,
+    dir: PathBuf,
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        self.daemon.take().unwrap().shutdown();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn start(name: &str) -> TestDaemon {
+    let dir = std::env::temp_dir().join(format!("infinimath-rpc-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = DaemonConfig {
+        network: "regtest".to_string(),
+        data_dir: dir.clone(),
+        p2p: P2pConfig { listen: Some("127.0.0.1:0".parse().unwrap()), ..P2pConfig::default() },
+        rpc: RpcConfig { listen: Some("127.0.0.1:0".parse().unwrap()) },
+        ..DaemonConfig::default()
+    };
+    let daemon = Daemon::start(&config, &genesis()).unwrap();
+    let client = RpcClient::new(daemon.rpc_addr());
+    TestDaemon { daemon: Some(daemon), client, dir }
+}
+
+fn transaction(amount: u32, nonce: u64) -> Transaction {
+    let mut transaction = Transaction {
+        sender: funded(),
+        receiver: other(),
+        amount: BigDecimal::from(amount),
+        fee: BigDecimal::from(1),
+        nonce,
+        chain_id: genesis().chain_id,
+        sender_public_key: None,
+        signature: OptionalSerializableSignature(None),
+    };
+    transaction.sign(&key());
+    transaction
+}
+
+fn error_code(result: Result<Value, ClientError>) -> i64 {
+    match result {
+        Err(ClientError::Rpc(e)) => e.code,
+        other => panic!("expected an RPC error, got {:?}", other),
+    }
+}
+
+// Sends `request` as is and returns the status line and body of the response
+fn raw_http(test: &TestDaemon, request: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(test.client.addr()).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[test]
+fn blocks_can_be_looked_up_by_hash_and_height() {
+    let test = start("blocks");
+    let hashes: Vec<String> = (0..3).map(|_| test.client.call("generate", json!([other()])).unwrap()).collect();
+
+    let by_height: BlockInfo = test.client.call("getblockbyheight", json!([2])).unwrap();
+    assert_eq!(by_height.block.hash, hashes[1]);
+    assert_eq!(by_height.height, 2);
+    assert_eq!(by_height.confirmations, 2);
+
+    let by_hash: BlockInfo = test.client.call("getblock", json!([hashes[2]])).unwrap();
+    assert_eq!(by_hash.height, 3);
+    assert_eq!(by_hash.confirmations, 1);
+    assert_eq!(by_hash.block.header.previous_hash, hashes[1]);
+    assert_eq!(by_hash.block.coinbase.outputs.iter().map(|output| &output.amount).sum::<BigDecimal>(), BigDecimal::from(40));
+
+    let difficulty: DifficultyInfo = test.client.call("getdifficulty", json!([])).unwrap();
+    assert_eq!(difficulty.bits, by_hash.block.header.bits);
+    assert_eq!(difficulty.next_target.len(), 64);
+    assert!(difficulty.chain_work.parse::<u128>().unwrap() > 0);
+
+    assert_eq!(error_code(test.client.call_raw("getblock", json!(["00".repeat(32)]))), NOT_FOUND);
+    assert_eq!(error_code(test.client.call_raw("getblockbyheight", json!([4]))), NOT_FOUND);
+}
+
+#[test]
+fn transactions_are_tracked_from_mempool_to_block() {
+    let test = start("transactions");
+    let sent = transaction(100, 0);
+    let tx_id: String = test.client.call("sendrawtransaction", json!([sent])).unwrap();
+    assert_eq!(tx_id, sent.hash());
+
+    let pending: TransactionInfo = test.client.call("gettransaction", json!([tx_id])).unwrap();
+    assert_eq!((pending.block_hash, pending.height, pending.confirmations), (None, None, 0));
+    let mempool: Vec<Transaction> = test.client.call("getmempool", json!([])).unwrap();
+    assert_eq!(mempool.iter().map(Transaction::hash).collect::<Vec<_>>(), vec![tx_id.clone()]);
+    let unrelated: Vec<Transaction> = test.client.call("getmempool", json!(["Miner"])).unwrap();
+    assert!(unrelated.is_empty());
+
+    let hash: String = test.client.call("generate", json!([funded()])).unwrap();
+    let confirmed: TransactionInfo = test.client.call("gettransaction", json!([tx_id])).unwrap();
+    assert_eq!(confirmed.block_hash, Some(hash));
+    assert_eq!(confirmed.height, Some(1));
+    assert_eq!(confirmed.confirmations, 1);
+    assert_eq!(confirmed.transaction.amount, BigDecimal::from(100));
+    assert!(test.client.call::<Vec<Transaction>>("getmempool", json!([])).unwrap().is_empty());
+
+    let balance: BigDecimal = test.client.call("getbalance", json!([other()])).unwrap();
+    assert_eq!(balance, BigDecimal::from(100));
+    assert_eq!(error_code(test.client.call_raw("gettransaction", json!(["00".repeat(32)]))), NOT_FOUND);
+}
+
+#[test]
+fn refused_transactions_carry_the_code_of_their_error() {
+    let test = start("refused");
+    let send = |transaction: Transaction| error_code(test.client.call_raw("sendrawtransaction", json!([transaction])));
+
+    assert_eq!(send(transaction(5_000, 0)), INSUFFICIENT_BALANCE);
+    assert_eq!(send(transaction(10, 1)), NONCE_GAP);
+
+    let mut wrong_chain = transaction(10, 0);
+    wrong_chain.chain_id += 1;
+    wrong_chain.sign(&key());
+    assert_eq!(send(wrong_chain), WRONG_CHAIN);
+
+    let mut unsigned = transaction(10, 0);
+    unsigned.signature = OptionalSerializableSignature(None);
+    assert_eq!(send(unsigned), MISSING_SIGNATURE);
+
+    let mut tampered = transaction(10, 0);
+    tampered.amount = BigDecimal::from(20);
+    assert_eq!(send(tampered), INVALID_SIGNATURE);
+
+    let mut bad_receiver = transaction(10, 0);
+    bad_receiver.receiver = "nobody".to_string();
+    bad_receiver.sign(&key());
+    assert_eq!(send(bad_receiver), INVALID_ADDRESS);
+
+    test.client.call::<String>("sendrawtransaction", json!([transaction(10, 0)])).unwrap();
+    assert_eq!(send(transaction(10, 0)), DUPLICATE_NONCE);
+    assert_eq!(send(transaction(11, 0)), REPLACEMENT_FEE_TOO_LOW);
+    test.client.call::<String>("generate", json!([other()])).unwrap();
+    assert_eq!(send(transaction(10, 0)), STALE_NONCE);
+}
+
+#[test]
+fn malformed_requests_get_protocol_errors() {
+    let test = start("malformed");
+    assert_eq!(error_code(test.client.call_raw("nosuchmethod", json!([]))), METHOD_NOT_FOUND);
+    assert_eq!(error_code(test.client.call_raw("getblock", json!([]))), INVALID_PARAMS);
+    assert_eq!(error_code(test.client.call_raw("getblockbyheight", json!(["tip"]))), INVALID_PARAMS);
+    assert_eq!(error_code(test.client.call_raw("sendrawtransaction", json!([{ "sender": "me" }]))), INVALID_PARAMS);
+
+    let body = "{not json";
+    let (status, response) = raw_http(&test, &format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let response: Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["error"]["code"], PARSE_ERROR);
+    assert_eq!(response["id"], Value::Null);
+
+    let body = r#"{"jsonrpc":"2.0","method":"getinfo","id":"abc"}"#;
+    let (_, response) = raw_http(&test, &format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+    let response: Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["id"], "abc");
+    assert_eq!(response["result"]["network"], "regtest");
+
+    let (status, _) = raw_http(&test, "GET / HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+}
+
+#[test]
+fn subchain_blocks_and_contracts_are_served() {
+    let test = start("subchain");
+    let created: SubChainBlock = test.client.call("createsubchainblock", json!([])).unwrap();
+    let fetched: SubChainBlock = test.client.call("getsubchainblock", json!([created.block_number])).unwrap();
+    assert_eq!(fetched.hash, created.hash);
+    assert_eq!(fetched.result, created.result);
+    assert_eq!(error_code(test.client.call_raw("getsubchainblock", json!([created.block_number + 1]))), NOT_FOUND);
+
+    let node = test.daemon.as_ref().unwrap().node();
+    node.chain().lock().unwrap().create_smart_contract("store".to_string(), funded(), String::new()).unwrap();
+    let set: String = test.client.call("callcontract", json!(["store", "set", { "key": "answer", "value": "42" }])).unwrap();
+    assert_eq!(set, "Set state 'answer' to '42'");
+    let get: String = test.client.call("callcontract", json!(["store", "get", { "key": "answer" }])).unwrap();
+    assert!(get.contains("'42'"));
+    assert_eq!(error_code(test.client.call_raw("callcontract", json!(["store", "delete"]))), CONTRACT_ERROR);
+    assert_eq!(error_code(test.client.call_raw("callcontract", json!(["missing", "get", {}]))), NOT_FOUND);
+}